@@ -0,0 +1,95 @@
+// Solvers for the challenges /login and /signup hand back once an IP has too many failed sign-ins.
+// The answer goes in the retried request's `challenge_response` field.
+
+export interface Challenge {
+  kind: "pow" | "hcaptcha" | "turnstile";
+  // Proof of work
+  challenge?: string;
+  difficulty?: number;
+  // CAPTCHA providers
+  site_key?: string;
+}
+
+interface CaptchaWidgetApi {
+  render(container: HTMLElement, options: { sitekey: string; callback: (token: string) => void }): unknown;
+}
+
+const CAPTCHA_SCRIPTS = {
+  hcaptcha: { src: "https://js.hcaptcha.com/1/api.js?render=explicit", global: "hcaptcha" },
+  turnstile: {
+    src: "https://challenges.cloudflare.com/turnstile/v0/api.js?render=explicit",
+    global: "turnstile",
+  },
+} as const;
+
+function leadingZeroBits(bytes: Uint8Array): number {
+  let bits = 0;
+  for (const byte of bytes) {
+    if (byte === 0) {
+      bits += 8;
+      continue;
+    }
+    bits += Math.clz32(byte) - 24;
+    break;
+  }
+  return bits;
+}
+
+async function solveProofOfWork(challenge: string, difficulty: number): Promise<string> {
+  const encoder = new TextEncoder();
+  for (let counter = 0; ; counter++) {
+    const answer = `${challenge}:${counter}`;
+    const digest = await crypto.subtle.digest("SHA-256", encoder.encode(answer));
+    if (leadingZeroBits(new Uint8Array(digest)) >= difficulty) {
+      return answer;
+    }
+  }
+}
+
+function loadCaptcha(kind: "hcaptcha" | "turnstile"): Promise<CaptchaWidgetApi> {
+  const { src, global } = CAPTCHA_SCRIPTS[kind];
+  const loaded = (window as unknown as Record<string, CaptchaWidgetApi | undefined>)[global];
+  if (loaded) {
+    return Promise.resolve(loaded);
+  }
+
+  return new Promise((resolve, reject) => {
+    const script = document.createElement("script");
+    script.src = src;
+    script.async = true;
+    script.onload = () => {
+      const api = (window as unknown as Record<string, CaptchaWidgetApi | undefined>)[global];
+      if (api) {
+        resolve(api);
+      } else {
+        reject(new Error(`${kind} failed to load`));
+      }
+    };
+    script.onerror = () => reject(new Error(`${kind} failed to load`));
+    document.head.appendChild(script);
+  });
+}
+
+async function solveCaptcha(
+  kind: "hcaptcha" | "turnstile",
+  siteKey: string,
+  container: HTMLElement,
+): Promise<string> {
+  const api = await loadCaptcha(kind);
+  container.replaceChildren();
+  return new Promise((resolve) => {
+    api.render(container, { sitekey: siteKey, callback: resolve });
+  });
+}
+
+// Resolves with the value for `challenge_response`. CAPTCHA widgets render into `container`
+// and resolve once the user completes them.
+export async function solveChallenge(challenge: Challenge, container: HTMLElement): Promise<string> {
+  switch (challenge.kind) {
+    case "pow":
+      return solveProofOfWork(challenge.challenge ?? "", challenge.difficulty ?? 0);
+    case "hcaptcha":
+    case "turnstile":
+      return solveCaptcha(challenge.kind, challenge.site_key ?? "", container);
+  }
+}
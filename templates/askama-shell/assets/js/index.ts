@@ -8,11 +8,25 @@ import focus from "@alpinejs/focus"; // https://alpinejs.dev/plugins/focus
 import collapse from "@alpinejs/collapse"; // https://alpinejs.dev/plugins/collapse
 import resize from "@alpinejs/resize"; // https://alpinejs.dev/plugins/resize
 import ajax from "@imacrayon/alpine-ajax"; // https://alpine-ajax.js.org/reference
+import { solveChallenge } from "./challenge";
+import { connectLiveUpdates, listenForEvents } from "./live";
+import { loginWithPasskey, registerPasskey } from "./webauthn";
 
 declare global {
   interface Window {
     MathJax: typeof MathJax;
     Alpine: typeof Alpine;
+    passkeys: {
+      register: typeof registerPasskey;
+      login: typeof loginWithPasskey;
+    };
+    abuseChallenge: {
+      solve: typeof solveChallenge;
+    };
+    liveUpdates: {
+      connect: typeof connectLiveUpdates;
+      listen: typeof listenForEvents;
+    };
   }
 }
 
@@ -26,4 +40,18 @@ Alpine.plugin(collapse);
 Alpine.plugin(resize);
 Alpine.plugin(ajax);
 
+window.passkeys = {
+  register: registerPasskey,
+  login: loginWithPasskey,
+};
+
+window.abuseChallenge = {
+  solve: solveChallenge,
+};
+
+window.liveUpdates = {
+  connect: connectLiveUpdates,
+  listen: listenForEvents,
+};
+
 Alpine.start();
\ No newline at end of file
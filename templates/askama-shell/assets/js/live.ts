@@ -0,0 +1,78 @@
+// Clients for /ws and /events, which push server events to signed-in pages as JSON tagged by `type`.
+
+export interface UserLoggedInEvent {
+  type: "user_logged_in";
+  username: string;
+  display_name: string | null;
+  at: string;
+}
+
+export interface DataExportEvent {
+  type: "data_export_ready" | "data_export_failed";
+  export_id: string;
+}
+
+export type LiveEvent = UserLoggedInEvent | DataExportEvent;
+
+const MAX_RECONNECT_DELAY_MS = 30_000;
+
+// Opens the socket and reconnects with backoff until `close` is called
+export function connectLiveUpdates(onEvent: (event: LiveEvent) => void): { close(): void } {
+  const url = `${window.location.protocol === "https:" ? "wss:" : "ws:"}//${window.location.host}/ws`;
+  let socket: WebSocket | null = null;
+  let delay = 1_000;
+  let closed = false;
+
+  const open = () => {
+    socket = new WebSocket(url);
+    socket.onopen = () => {
+      delay = 1_000;
+    };
+    socket.onmessage = (message) => {
+      try {
+        onEvent(JSON.parse(message.data) as LiveEvent);
+      } catch (error) {
+        console.error("Malformed live event:", error);
+      }
+    };
+    socket.onclose = () => {
+      if (closed) {
+        return;
+      }
+      setTimeout(open, delay);
+      delay = Math.min(delay * 2, MAX_RECONNECT_DELAY_MS);
+    };
+  };
+
+  open();
+
+  return {
+    close() {
+      closed = true;
+      socket?.close();
+    },
+  };
+}
+
+// Server-sent events are enough for pages that only listen; the browser reconnects on its own
+export function listenForEvents(
+  names: LiveEvent["type"][],
+  onEvent: (event: LiveEvent) => void,
+): { close(): void } {
+  const source = new EventSource("/events");
+  for (const name of names) {
+    source.addEventListener(name, (message) => {
+      try {
+        onEvent(JSON.parse((message as MessageEvent).data) as LiveEvent);
+      } catch (error) {
+        console.error("Malformed live event:", error);
+      }
+    });
+  }
+
+  return {
+    close() {
+      source.close();
+    },
+  };
+}
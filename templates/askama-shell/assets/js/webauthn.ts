@@ -0,0 +1,103 @@
+// Passkey helpers for the /webauthn/* endpoints.
+// The server speaks base64url for every binary field, the browser API wants ArrayBuffers.
+
+function base64UrlToBuffer(value: string): ArrayBuffer {
+  const base64 = value.replace(/-/g, "+").replace(/_/g, "/");
+  const padded = base64.padEnd(base64.length + ((4 - (base64.length % 4)) % 4), "=");
+  const binary = atob(padded);
+  const bytes = new Uint8Array(binary.length);
+  for (let i = 0; i < binary.length; i++) {
+    bytes[i] = binary.charCodeAt(i);
+  }
+  return bytes.buffer;
+}
+
+function bufferToBase64Url(buffer: ArrayBuffer | null): string | null {
+  if (!buffer) {
+    return null;
+  }
+  const bytes = new Uint8Array(buffer);
+  let binary = "";
+  for (const byte of bytes) {
+    binary += String.fromCharCode(byte);
+  }
+  return btoa(binary).replace(/\+/g, "-").replace(/\//g, "_").replace(/=+$/, "");
+}
+
+function csrfToken(): string {
+  return document.querySelector('meta[name="csrf-token"]')?.getAttribute("content") ?? "";
+}
+
+async function postJson(url: string, body?: unknown): Promise<any> {
+  const response = await fetch(url, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+      "X-CSRF-Token": csrfToken(),
+    },
+    body: body === undefined ? undefined : JSON.stringify(body),
+  });
+  return response.json();
+}
+
+export async function registerPasskey(name?: string): Promise<any> {
+  const start = await postJson("/webauthn/register/start");
+  if (!start.success) {
+    return start;
+  }
+
+  const publicKey = start.options.publicKey;
+  publicKey.challenge = base64UrlToBuffer(publicKey.challenge);
+  publicKey.user.id = base64UrlToBuffer(publicKey.user.id);
+  publicKey.excludeCredentials = (publicKey.excludeCredentials || []).map((credential: any) => ({
+    ...credential,
+    id: base64UrlToBuffer(credential.id),
+  }));
+
+  const credential = (await navigator.credentials.create({ publicKey })) as PublicKeyCredential;
+  const response = credential.response as AuthenticatorAttestationResponse;
+
+  return postJson("/webauthn/register/finish", {
+    name,
+    credential: {
+      id: credential.id,
+      rawId: bufferToBase64Url(credential.rawId),
+      type: credential.type,
+      extensions: credential.getClientExtensionResults(),
+      response: {
+        attestationObject: bufferToBase64Url(response.attestationObject),
+        clientDataJSON: bufferToBase64Url(response.clientDataJSON),
+      },
+    },
+  });
+}
+
+export async function loginWithPasskey(identifier: string): Promise<any> {
+  const start = await postJson("/webauthn/login/start", { identifier });
+  if (!start.success) {
+    return start;
+  }
+
+  const publicKey = start.options.publicKey;
+  publicKey.challenge = base64UrlToBuffer(publicKey.challenge);
+  publicKey.allowCredentials = (publicKey.allowCredentials || []).map((credential: any) => ({
+    ...credential,
+    id: base64UrlToBuffer(credential.id),
+  }));
+
+  const credential = (await navigator.credentials.get({ publicKey })) as PublicKeyCredential;
+  const response = credential.response as AuthenticatorAssertionResponse;
+
+  return postJson("/webauthn/login/finish", {
+    id: credential.id,
+    rawId: bufferToBase64Url(credential.rawId),
+    type: credential.type,
+    extensions: credential.getClientExtensionResults(),
+    response: {
+      authenticatorData: bufferToBase64Url(response.authenticatorData),
+      clientDataJSON: bufferToBase64Url(response.clientDataJSON),
+      signature: bufferToBase64Url(response.signature),
+      userHandle: bufferToBase64Url(response.userHandle),
+    },
+  });
+}
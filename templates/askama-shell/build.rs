@@ -11,6 +11,14 @@ fn main() {
     println!("cargo:rerun-if-changed=tsconfig.json");
     println!("cargo:rerun-if-changed=input.css");
 
+    // Generate the gRPC server from the protobuf definitions
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/");
+        tonic_build::compile_protos("proto/users.proto")
+            .expect("Failed to compile proto/users.proto (is protoc installed?)");
+    }
+
     let out_dir = env::var("OUT_DIR").unwrap();
     let assets_dir = Path::new("assets");
     let dist_dir = assets_dir.join("dist");
@@ -40,6 +40,9 @@
             nodePackages.typescript
             nodePackages.tailwindcss
 
+            # protoc, for building with --features grpc
+            protobuf
+
             # Database tools
             sqlite
             postgresql
@@ -0,0 +1,9 @@
+-- Create failed login attempts table
+CREATE TABLE IF NOT EXISTS failed_login_attempts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    attempted_at DATETIME NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_failed_login_attempts_user_id_attempted_at ON failed_login_attempts(user_id, attempted_at);
@@ -0,0 +1,14 @@
+-- Track email changes that are waiting for confirmation
+ALTER TABLE users ADD COLUMN pending_email TEXT;
+
+-- Create email change tokens table
+CREATE TABLE IF NOT EXISTS email_change_tokens (
+    token TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    new_email TEXT NOT NULL,
+    expires_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_email_change_tokens_user_id ON email_change_tokens(user_id);
@@ -0,0 +1,5 @@
+-- Soft-deleted accounts are purged after a retention window
+ALTER TABLE users ADD COLUMN deleted_at DATETIME;
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_users_deleted_at ON users(deleted_at);
@@ -0,0 +1,12 @@
+-- Create user sessions table so a user's logins can be listed and revoked
+CREATE TABLE IF NOT EXISTS user_sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_agent TEXT,
+    ip_address TEXT,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    last_seen_at DATETIME NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_user_sessions_user_id ON user_sessions(user_id);
@@ -0,0 +1,9 @@
+-- Create sessions table for the SQLite session store
+CREATE TABLE IF NOT EXISTS tower_sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    data BLOB NOT NULL,
+    expiry_date INTEGER NOT NULL
+);
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_tower_sessions_expiry_date ON tower_sessions(expiry_date);
@@ -0,0 +1,16 @@
+-- Create known devices table so sign-ins from new devices can be reported
+CREATE TABLE IF NOT EXISTS known_devices (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_agent TEXT NOT NULL,
+    ip_address TEXT NOT NULL,
+    first_seen_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    last_seen_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (user_id, user_agent, ip_address)
+);
+
+-- Users can opt out of new sign-in emails
+ALTER TABLE users ADD COLUMN notify_new_sign_in BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_known_devices_user_id ON known_devices(user_id);
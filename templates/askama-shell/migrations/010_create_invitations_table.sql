@@ -0,0 +1,17 @@
+-- Create invitations table for invite-only signup
+CREATE TABLE IF NOT EXISTS invitations (
+    id TEXT PRIMARY KEY NOT NULL,
+    code TEXT UNIQUE NOT NULL,
+    created_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+    max_uses INTEGER NOT NULL DEFAULT 1,
+    use_count INTEGER NOT NULL DEFAULT 0,
+    expires_at DATETIME,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Remember which invitation each user signed up with
+ALTER TABLE users ADD COLUMN invitation_id TEXT REFERENCES invitations(id) ON DELETE SET NULL;
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_invitations_code ON invitations(code);
+CREATE INDEX IF NOT EXISTS idx_users_invitation_id ON users(invitation_id);
@@ -0,0 +1,25 @@
+-- Create roles table
+CREATE TABLE IF NOT EXISTS roles (
+    name TEXT PRIMARY KEY NOT NULL,
+    description TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Create user roles table
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+    granted_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (user_id, role)
+);
+
+-- Seed the built-in roles
+INSERT OR IGNORE INTO roles (name, description) VALUES
+    ('admin', 'Can manage users, roles, and invitations'),
+    ('user', 'Regular signed-up account');
+
+-- Every existing account is a regular user
+INSERT OR IGNORE INTO user_roles (user_id, role) SELECT id, 'user' FROM users;
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_user_roles_role ON user_roles(role);
@@ -0,0 +1,15 @@
+-- Create data exports table for users downloading a copy of their data
+CREATE TABLE IF NOT EXISTS data_exports (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- One of 'pending', 'ready', or 'failed'
+    status TEXT NOT NULL DEFAULT 'pending',
+    data TEXT,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    completed_at DATETIME,
+    expires_at DATETIME
+);
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_data_exports_user_id ON data_exports(user_id);
+CREATE INDEX IF NOT EXISTS idx_data_exports_expires_at ON data_exports(expires_at);
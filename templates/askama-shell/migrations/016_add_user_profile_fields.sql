@@ -0,0 +1,5 @@
+-- Add public profile fields to users
+ALTER TABLE users ADD COLUMN display_name TEXT;
+ALTER TABLE users ADD COLUMN bio TEXT;
+-- Set when an avatar is uploaded, NULL when the user has none
+ALTER TABLE users ADD COLUMN avatar_updated_at DATETIME;
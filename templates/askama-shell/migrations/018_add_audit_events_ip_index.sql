@@ -0,0 +1,2 @@
+-- Failed sign-ins are counted per client IP to decide when to require a challenge
+CREATE INDEX IF NOT EXISTS idx_audit_events_ip_address ON audit_events(ip_address, created_at);
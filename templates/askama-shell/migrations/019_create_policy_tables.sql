@@ -0,0 +1,33 @@
+-- Create policy documents table; publishing a new version adds a row
+CREATE TABLE IF NOT EXISTS policy_documents (
+    id TEXT PRIMARY KEY NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('terms', 'privacy')),
+    version INTEGER NOT NULL,
+    -- HTML shown on /terms and /privacy
+    body TEXT NOT NULL,
+    published_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (kind, version)
+);
+
+-- Create policy acceptances table recording which versions each user agreed to
+CREATE TABLE IF NOT EXISTS policy_acceptances (
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    policy_document_id TEXT NOT NULL REFERENCES policy_documents(id) ON DELETE CASCADE,
+    ip_address TEXT,
+    accepted_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (user_id, policy_document_id)
+);
+
+-- Seed placeholder first versions; publish real ones with `publish-policy`
+INSERT OR IGNORE INTO policy_documents (id, kind, version, body) VALUES
+    ('terms-v1', 'terms', 1, '<p>These are placeholder Terms of Service. Replace them before launch.</p>'),
+    ('privacy-v1', 'privacy', 1, '<p>This is a placeholder Privacy Policy. Replace it before launch.</p>');
+
+-- Existing accounts agreed to the original documents when they signed up
+INSERT OR IGNORE INTO policy_acceptances (user_id, policy_document_id, accepted_at)
+    SELECT id, 'terms-v1', created_at FROM users;
+INSERT OR IGNORE INTO policy_acceptances (user_id, policy_document_id, accepted_at)
+    SELECT id, 'privacy-v1', created_at FROM users;
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_policy_acceptances_policy_document_id ON policy_acceptances(policy_document_id);
@@ -0,0 +1,46 @@
+-- Full-text indexes behind /search, kept in sync with triggers. The IDs are
+-- stored unindexed rather than using external content tables, since neither
+-- source table has an INTEGER PRIMARY KEY and VACUUM may renumber its rowids.
+CREATE VIRTUAL TABLE IF NOT EXISTS notes_fts USING fts5(
+    note_id UNINDEXED,
+    title,
+    body,
+    tokenize = 'porter unicode61 remove_diacritics 2'
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS users_fts USING fts5(
+    user_id UNINDEXED,
+    username,
+    display_name,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO notes_fts (note_id, title, body) SELECT id, title, body FROM notes;
+INSERT INTO users_fts (user_id, username, display_name)
+    SELECT id, username, COALESCE(display_name, '') FROM users;
+
+CREATE TRIGGER IF NOT EXISTS notes_fts_insert AFTER INSERT ON notes BEGIN
+    INSERT INTO notes_fts (note_id, title, body) VALUES (new.id, new.title, new.body);
+END;
+
+CREATE TRIGGER IF NOT EXISTS notes_fts_update AFTER UPDATE OF title, body ON notes BEGIN
+    UPDATE notes_fts SET title = new.title, body = new.body WHERE note_id = new.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS notes_fts_delete AFTER DELETE ON notes BEGIN
+    DELETE FROM notes_fts WHERE note_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS users_fts_insert AFTER INSERT ON users BEGIN
+    INSERT INTO users_fts (user_id, username, display_name)
+        VALUES (new.id, new.username, COALESCE(new.display_name, ''));
+END;
+
+CREATE TRIGGER IF NOT EXISTS users_fts_update AFTER UPDATE OF username, display_name ON users BEGIN
+    UPDATE users_fts SET username = new.username, display_name = COALESCE(new.display_name, '')
+        WHERE user_id = new.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS users_fts_delete AFTER DELETE ON users BEGIN
+    DELETE FROM users_fts WHERE user_id = old.id;
+END;
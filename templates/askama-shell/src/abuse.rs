@@ -0,0 +1,493 @@
+use crate::audit::AuditAction;
+use crate::handlers::ClientInfo;
+use crate::models::AuditEvent;
+use crate::negotiate::ResponseFormat;
+use axum::{
+    Json, async_trait,
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Sign-in and sign-up bodies are small; larger ones aren't inspected
+const MAX_INSPECTED_BODY_BYTES: usize = 64 * 1024;
+// How long an issued proof-of-work challenge can be answered
+const CHALLENGE_TTL: Duration = Duration::from_secs(5 * 60);
+const CAPTCHA_TIMEOUT: Duration = Duration::from_secs(10);
+const HCAPTCHA_VERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+const TURNSTILE_VERIFY_URL: &str = "https://challenges.cloudflare.com/turnstile/v0/siteverify";
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChallengeError {
+    #[error("verification request failed: {0}")]
+    Http(#[from] reqwest::Error),
+}
+
+/// Issues challenges to the browser and checks the answers. The client
+/// script picks a solver by the challenge's `kind`.
+#[async_trait]
+pub trait ChallengeVerifier: Send + Sync {
+    /// A fresh challenge, sent to the browser as JSON
+    fn challenge(&self) -> serde_json::Value;
+
+    /// Whether `response` answers a challenge this verifier issued
+    async fn verify(&self, response: &str, remote_ip: &str) -> Result<bool, ChallengeError>;
+}
+
+/// Built-in challenge that needs no third party: the browser searches for a
+/// counter whose SHA-256 over `<challenge>:<counter>` starts with
+/// `difficulty` zero bits. Challenges are signed so the server doesn't have
+/// to remember them, and each one is accepted only once.
+pub struct ProofOfWork {
+    key: [u8; 32],
+    difficulty: u32,
+    spent: Mutex<HashMap<String, Instant>>,
+}
+
+impl ProofOfWork {
+    pub fn new(key: [u8; 32], difficulty: u32) -> Self {
+        Self {
+            key,
+            difficulty,
+            spent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn mac(&self, payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    // Checks the signature and age of `<issued_at>.<nonce>.<signature>`
+    fn is_authentic(&self, challenge: &str) -> bool {
+        let Some((payload, signature)) = challenge.rsplit_once('.') else {
+            return false;
+        };
+        let Some(issued_at) = payload
+            .split_once('.')
+            .and_then(|(issued_at, _)| issued_at.parse::<u64>().ok())
+        else {
+            return false;
+        };
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        if self.mac(payload).verify_slice(&signature).is_err() {
+            return false;
+        }
+
+        let now = unix_now();
+        issued_at <= now && now - issued_at <= CHALLENGE_TTL.as_secs()
+    }
+
+    // Marks the challenge used, returning false if it already was
+    fn spend(&self, challenge: &str) -> bool {
+        let mut spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+        spent.retain(|_, used_at| used_at.elapsed() < CHALLENGE_TTL);
+        spent
+            .insert(challenge.to_string(), Instant::now())
+            .is_none()
+    }
+}
+
+#[async_trait]
+impl ChallengeVerifier for ProofOfWork {
+    fn challenge(&self) -> serde_json::Value {
+        let mut nonce = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let payload = format!("{}.{}", unix_now(), hex::encode(nonce));
+        let signature = hex::encode(self.mac(&payload).finalize().into_bytes());
+
+        json!({
+            "kind": "pow",
+            "challenge": format!("{}.{}", payload, signature),
+            "difficulty": self.difficulty
+        })
+    }
+
+    async fn verify(&self, response: &str, _remote_ip: &str) -> Result<bool, ChallengeError> {
+        let Some((challenge, counter)) = response.rsplit_once(':') else {
+            return Ok(false);
+        };
+        if counter.parse::<u64>().is_err() || !self.is_authentic(challenge) {
+            return Ok(false);
+        }
+        if leading_zero_bits(&Sha256::digest(response.as_bytes())) < self.difficulty {
+            return Ok(false);
+        }
+
+        Ok(self.spend(challenge))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptchaProvider {
+    HCaptcha,
+    Turnstile,
+}
+
+impl CaptchaProvider {
+    fn kind(&self) -> &'static str {
+        match self {
+            CaptchaProvider::HCaptcha => "hcaptcha",
+            CaptchaProvider::Turnstile => "turnstile",
+        }
+    }
+
+    fn verify_url(&self) -> &'static str {
+        match self {
+            CaptchaProvider::HCaptcha => HCAPTCHA_VERIFY_URL,
+            CaptchaProvider::Turnstile => TURNSTILE_VERIFY_URL,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+}
+
+/// hCaptcha or Cloudflare Turnstile. The browser renders the provider's
+/// widget and the token it produces is checked against the provider's
+/// `siteverify` endpoint.
+pub struct CaptchaVerifier {
+    provider: CaptchaProvider,
+    site_key: String,
+    secret_key: String,
+    client: reqwest::Client,
+}
+
+impl CaptchaVerifier {
+    pub fn new(
+        provider: CaptchaProvider,
+        site_key: String,
+        secret_key: String,
+    ) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(CAPTCHA_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            provider,
+            site_key,
+            secret_key,
+            client,
+        })
+    }
+}
+
+#[async_trait]
+impl ChallengeVerifier for CaptchaVerifier {
+    fn challenge(&self) -> serde_json::Value {
+        json!({
+            "kind": self.provider.kind(),
+            "site_key": self.site_key
+        })
+    }
+
+    async fn verify(&self, response: &str, remote_ip: &str) -> Result<bool, ChallengeError> {
+        let result: SiteVerifyResponse = self
+            .client
+            .post(self.provider.verify_url())
+            .form(&[
+                ("secret", self.secret_key.as_str()),
+                ("response", response),
+                ("remoteip", remote_ip),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(result.success)
+    }
+}
+
+/// Endpoints a challenge can be required on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectedRoute {
+    Login,
+    Signup,
+}
+
+impl ProtectedRoute {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "login" => Some(ProtectedRoute::Login),
+            "signup" => Some(ProtectedRoute::Signup),
+            _ => None,
+        }
+    }
+}
+
+/// Decides when sign-in and sign-up requests must answer a challenge: once
+/// the client's IP has racked up enough failed sign-ins within the window.
+#[derive(Clone)]
+pub struct AbuseProtection {
+    /// `None` turns challenges off
+    verifier: Option<Arc<dyn ChallengeVerifier>>,
+    routes: Vec<ProtectedRoute>,
+    /// Failed sign-ins from one IP before challenges start; 0 always challenges
+    failure_threshold: u32,
+    window: chrono::Duration,
+}
+
+impl AbuseProtection {
+    /// Reads `ABUSE_CHALLENGE` (`pow`, the default, `hcaptcha`, `turnstile`,
+    /// or `none`), `ABUSE_CHALLENGE_ROUTES` (default `login,signup`),
+    /// `ABUSE_CHALLENGE_AFTER_FAILURES` (default 3, 0 challenges every
+    /// request), and `ABUSE_CHALLENGE_WINDOW_MINUTES` (default 15).
+    ///
+    /// Proof of work uses `POW_DIFFICULTY` (default 16 bits) and
+    /// `POW_SECRET`, which must be shared by every instance behind a load
+    /// balancer; without it each process signs with a random key. The
+    /// CAPTCHA providers need `CAPTCHA_SITE_KEY` and `CAPTCHA_SECRET_KEY`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let verifier: Option<Arc<dyn ChallengeVerifier>> =
+            match env::var("ABUSE_CHALLENGE").as_deref() {
+                Ok("none") => None,
+                Ok("pow") | Err(_) => Some(Arc::new(proof_of_work_from_env()?)),
+                Ok("hcaptcha") => Some(Arc::new(captcha_from_env(CaptchaProvider::HCaptcha)?)),
+                Ok("turnstile") => Some(Arc::new(captcha_from_env(CaptchaProvider::Turnstile)?)),
+                Ok(other) => anyhow::bail!(
+                    "ABUSE_CHALLENGE must be 'pow', 'hcaptcha', 'turnstile', or 'none', got '{}'",
+                    other
+                ),
+            };
+
+        let routes = env::var("ABUSE_CHALLENGE_ROUTES")
+            .unwrap_or_else(|_| "login,signup".to_string())
+            .split(',')
+            .filter(|route| !route.trim().is_empty())
+            .map(|route| {
+                ProtectedRoute::parse(route).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "ABUSE_CHALLENGE_ROUTES entries must be 'login' or 'signup', got '{}'",
+                        route.trim()
+                    )
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let failure_threshold = env::var("ABUSE_CHALLENGE_AFTER_FAILURES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(3);
+        let window_minutes = env::var("ABUSE_CHALLENGE_WINDOW_MINUTES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(15);
+
+        Ok(Self {
+            verifier,
+            routes,
+            failure_threshold,
+            window: chrono::Duration::minutes(window_minutes),
+        })
+    }
+
+    /// State for the [`require_challenge`] middleware on one route
+    pub fn gate(&self, pool: SqlitePool, route: ProtectedRoute) -> ChallengeGate {
+        ChallengeGate {
+            protection: self.clone(),
+            pool,
+            route,
+        }
+    }
+
+    fn verifier_for(&self, route: ProtectedRoute) -> Option<&Arc<dyn ChallengeVerifier>> {
+        self.verifier
+            .as_ref()
+            .filter(|_| self.routes.contains(&route))
+    }
+
+    async fn is_elevated(&self, pool: &SqlitePool, ip_address: &str) -> Result<bool, sqlx::Error> {
+        if self.failure_threshold == 0 {
+            return Ok(true);
+        }
+
+        let failures = AuditEvent::count_from_ip(
+            pool,
+            AuditAction::LoginFailed.as_str(),
+            ip_address,
+            chrono::Utc::now() - self.window,
+        )
+        .await?;
+
+        Ok(failures >= i64::from(self.failure_threshold))
+    }
+}
+
+fn proof_of_work_from_env() -> anyhow::Result<ProofOfWork> {
+    let difficulty: u32 = match env::var("POW_DIFFICULTY") {
+        Ok(value) => value
+            .parse()
+            .ok()
+            .filter(|bits| *bits <= 32)
+            .ok_or_else(|| anyhow::anyhow!("POW_DIFFICULTY must be a number of bits up to 32"))?,
+        Err(_) => 16,
+    };
+
+    let key = match env::var("POW_SECRET") {
+        Ok(secret) if !secret.is_empty() => Sha256::digest(secret.as_bytes()).into(),
+        _ => {
+            let mut key = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            key
+        }
+    };
+
+    Ok(ProofOfWork::new(key, difficulty))
+}
+
+fn captcha_from_env(provider: CaptchaProvider) -> anyhow::Result<CaptchaVerifier> {
+    let site_key = env::var("CAPTCHA_SITE_KEY")
+        .ok()
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("CAPTCHA_SITE_KEY is required for {}", provider.kind()))?;
+    let secret_key = env::var("CAPTCHA_SECRET_KEY")
+        .ok()
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("CAPTCHA_SECRET_KEY is required for {}", provider.kind()))?;
+
+    CaptchaVerifier::new(provider, site_key, secret_key)
+}
+
+#[derive(Clone)]
+pub struct ChallengeGate {
+    protection: AbuseProtection,
+    pool: SqlitePool,
+    route: ProtectedRoute,
+}
+
+#[derive(Deserialize)]
+struct ChallengeAnswer {
+    challenge_response: Option<String>,
+    // Sent to `/api/auth/token`, where only password grants guess credentials
+    grant_type: Option<String>,
+}
+
+/// Holds back sign-in and sign-up requests, including password grants on
+/// `/api/auth/token`, from IPs with an elevated failure rate until the JSON
+/// body carries a `challenge_response` the verifier accepts. Otherwise the
+/// reply is `{"success": false, "challenge": {...}}` with a fresh challenge
+/// for the client script to solve before retrying.
+pub async fn require_challenge(
+    State(gate): State<ChallengeGate>,
+    client: ClientInfo,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(verifier) = gate.protection.verifier_for(gate.route) else {
+        return next.run(request).await;
+    };
+
+    match gate
+        .protection
+        .is_elevated(&gate.pool, &client.ip_address)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return next.run(request).await,
+        Err(e) => {
+            tracing::error!("Database error counting failed logins: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+        }
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_INSPECTED_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => return (StatusCode::PAYLOAD_TOO_LARGE, "Request too large").into_response(),
+    };
+
+    let body = serde_json::from_slice::<ChallengeAnswer>(&bytes).ok();
+    if body
+        .as_ref()
+        .is_some_and(|body| body.grant_type.as_deref() == Some("refresh_token"))
+    {
+        return next
+            .run(Request::from_parts(parts, Body::from(bytes)))
+            .await;
+    }
+    let answer = body
+        .and_then(|body| body.challenge_response)
+        .filter(|answer| !answer.is_empty());
+
+    let passed = match &answer {
+        Some(answer) => match verifier.verify(answer, &client.ip_address).await {
+            Ok(passed) => passed,
+            Err(e) => {
+                tracing::error!("Challenge verification error: {}", e);
+                false
+            }
+        },
+        None => false,
+    };
+
+    if passed {
+        return next
+            .run(Request::from_parts(parts, Body::from(bytes)))
+            .await;
+    }
+
+    tracing::warn!(
+        "Challenge required for {} from {}",
+        parts.uri.path(),
+        client.ip_address
+    );
+    // Challenges are solved by the page's script, so plain form posts can't pass
+    if ResponseFormat::from_parts(&parts) == ResponseFormat::Html {
+        return (
+            StatusCode::FORBIDDEN,
+            Html("<!DOCTYPE html><html><head><title>Verification Required</title></head><body><h1>Verification Required</h1><p>Please enable JavaScript and try again.</p></body></html>"),
+        )
+            .into_response();
+    }
+
+    let message = if answer.is_some() {
+        "Verification failed. Please try again."
+    } else {
+        "Please complete a quick check to continue."
+    };
+    Json(json!({
+        "success": false,
+        "message": message,
+        "challenge": verifier.challenge()
+    }))
+    .into_response()
+}
+
+fn leading_zero_bits(digest: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in digest {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
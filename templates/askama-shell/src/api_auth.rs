@@ -0,0 +1,207 @@
+use crate::config::JwtConfig;
+use crate::handlers::get_user_from_session;
+use crate::jwt::decode_access_token;
+use crate::models::{API_TOKEN_PREFIX, ApiToken, User, UserResponse};
+use crate::state::AppState;
+use axum::{
+    Json, async_trait,
+    extract::{FromRef, FromRequestParts, Request, State},
+    http::{HeaderValue, StatusCode, header, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use sqlx::SqlitePool;
+use tower_sessions::Session;
+
+/// Extracts the caller of an API route. Requests are authenticated by an
+/// `Authorization: Bearer` header holding either a personal access token or a
+/// JWT access token, and by the session cookie when no `Authorization` header
+/// is sent at all.
+#[derive(Clone)]
+pub struct ApiUser {
+    pub user: UserResponse,
+    /// The personal access token the request was made with, `None` for
+    /// session and JWT requests
+    pub token: Option<ApiToken>,
+}
+
+impl ApiUser {
+    /// Session and JWT requests may do anything the user can; personal access
+    /// tokens are limited to the scopes granted when they were created.
+    pub fn require_scope(&self, scope: &'static str) -> Result<(), MissingScope> {
+        match &self.token {
+            Some(token) if !token.has_scope(scope) => Err(MissingScope(scope)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a personal access token or JWT access token, as sent in an
+    /// `Authorization: Bearer` header. Shared by the HTTP extractor and the
+    /// gRPC server.
+    pub async fn from_bearer(
+        pool: &SqlitePool,
+        jwt: &JwtConfig,
+        secret: &str,
+    ) -> Result<Self, BearerError> {
+        if !secret.starts_with(API_TOKEN_PREFIX) {
+            let claims = decode_access_token(jwt, secret).map_err(|_| BearerError::Invalid)?;
+            return match User::find_by_id(pool, &claims.sub).await {
+                Ok(Some(user)) if user.is_active && user.deleted_at.is_none() => Ok(Self {
+                    user: user.into(),
+                    token: None,
+                }),
+                Ok(_) => Err(BearerError::Invalid),
+                Err(e) => {
+                    tracing::error!("Database error loading token owner: {}", e);
+                    Err(BearerError::Database)
+                }
+            };
+        }
+
+        let token = match ApiToken::find_valid(pool, secret).await {
+            Ok(Some(token)) => token,
+            Ok(None) => return Err(BearerError::Invalid),
+            Err(e) => {
+                tracing::error!("Database error checking API token: {}", e);
+                return Err(BearerError::Database);
+            }
+        };
+
+        // Tokens stop working as soon as their owner is deactivated or deleted
+        let user = match User::find_by_id(pool, &token.user_id).await {
+            Ok(Some(user)) if user.is_active && user.deleted_at.is_none() => user,
+            Ok(_) => return Err(BearerError::Invalid),
+            Err(e) => {
+                tracing::error!("Database error loading token owner: {}", e);
+                return Err(BearerError::Database);
+            }
+        };
+
+        if let Err(e) = ApiToken::touch(pool, &token.id).await {
+            tracing::warn!("Failed to update last use of API token {}: {}", token.id, e);
+        }
+
+        Ok(Self {
+            user: user.into(),
+            token: Some(token),
+        })
+    }
+}
+
+/// Rejection for token requests lacking the scope a route needs
+#[derive(Debug)]
+pub struct MissingScope(pub &'static str);
+
+impl IntoResponse for MissingScope {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "success": false,
+                "message": format!("Token is missing the {} scope", self.0)
+            })),
+        )
+            .into_response()
+    }
+}
+
+impl From<MissingScope> for Response {
+    fn from(missing_scope: MissingScope) -> Self {
+        missing_scope.into_response()
+    }
+}
+
+/// Why a bearer credential was turned away
+#[derive(Debug)]
+pub enum BearerError {
+    /// Unknown, expired, or revoked, or its owner can no longer sign in
+    Invalid,
+    /// The lookup failed; the error has already been logged
+    Database,
+}
+
+// Helper function to build the 401 returned for missing or invalid credentials
+pub(crate) fn unauthorized(message: &str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        Json(json!({
+            "success": false,
+            "message": message
+        })),
+    )
+        .into_response()
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiUser
+where
+    SqlitePool: FromRef<S>,
+    JwtConfig: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // Already checked by `require_api_user`
+        if let Some(api_user) = parts.extensions.get::<ApiUser>() {
+            return Ok(api_user.clone());
+        }
+
+        let pool = SqlitePool::from_ref(state);
+
+        let Some(authorization) = parts.headers.get(header::AUTHORIZATION) else {
+            let session = Session::from_request_parts(parts, state)
+                .await
+                .map_err(|rejection| rejection.into_response())?;
+            return match get_user_from_session(&session, &pool).await {
+                Some(user) => Ok(Self { user, token: None }),
+                None => Err(unauthorized("Authentication required")),
+            };
+        };
+
+        // Any other scheme is refused rather than falling back to the cookie,
+        // since the CSRF check only lets bearer requests through
+        let Some(secret) = bearer_secret(authorization) else {
+            return Err(unauthorized("Expected an Authorization: Bearer token"));
+        };
+
+        match Self::from_bearer(&pool, &JwtConfig::from_ref(state), secret).await {
+            Ok(api_user) => Ok(api_user),
+            Err(BearerError::Invalid) => Err(unauthorized("Invalid or expired token")),
+            Err(BearerError::Database) => {
+                Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+            }
+        }
+    }
+}
+
+/// The credential in an `Authorization: Bearer <token>` header, if that's
+/// what was sent
+pub(crate) fn bearer_secret(authorization: &HeaderValue) -> Option<&str> {
+    authorization
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|secret| !secret.is_empty())
+}
+
+/// Middleware for route groups only open to authenticated callers: rejects
+/// anyone else before the body is read, and leaves the `ApiUser` in the
+/// request extensions for later middleware and the handler's extractor.
+pub async fn require_api_user(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (mut parts, body) = request.into_parts();
+    let api_user = match ApiUser::from_request_parts(&mut parts, &state).await {
+        Ok(api_user) => api_user,
+        Err(rejection) => return rejection,
+    };
+    parts.extensions.insert(api_user);
+
+    next.run(Request::from_parts(parts, body)).await
+}
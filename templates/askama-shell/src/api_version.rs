@@ -0,0 +1,144 @@
+use crate::api_auth::require_api_user;
+use crate::rate_limit::{limit_api_clients, limit_api_requests};
+use crate::state::AppState;
+use axum::{
+    Router,
+    extract::{DefaultBodyLimit, Request, State},
+    http::{HeaderName, HeaderValue, header},
+    middleware::{self, Next},
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+
+// API requests are JSON; anything bigger needs its own route-level limit
+const API_MAX_BODY_BYTES: usize = 256 * 1024;
+
+/// One version of the REST API, mounted at `/api/<name>`
+pub struct ApiVersion {
+    name: &'static str,
+    routes: Router<AppState>,
+    deprecation: Option<Deprecation>,
+}
+
+impl ApiVersion {
+    pub fn new(name: &'static str, routes: Router<AppState>) -> Self {
+        Self {
+            name,
+            routes,
+            deprecation: None,
+        }
+    }
+
+    /// Announces on every response that clients should move to a newer version
+    pub fn deprecated(mut self, deprecation: Deprecation) -> Self {
+        self.deprecation = Some(deprecation);
+        self
+    }
+}
+
+/// When an API version was deprecated, and optionally when it goes away and
+/// where to read about migrating off it. Sent as the `Deprecation` (RFC 9745),
+/// `Sunset` (RFC 8594), and `Link` response headers.
+#[derive(Debug, Clone)]
+pub struct Deprecation {
+    since: DateTime<Utc>,
+    sunset: Option<DateTime<Utc>>,
+    link: Option<String>,
+}
+
+impl Deprecation {
+    pub fn since(since: DateTime<Utc>) -> Self {
+        Self {
+            since,
+            sunset: None,
+            link: None,
+        }
+    }
+
+    pub fn sunset(mut self, sunset: DateTime<Utc>) -> Self {
+        self.sunset = Some(sunset);
+        self
+    }
+
+    /// Migration guide or changelog for clients of the deprecated version
+    pub fn link(mut self, link: impl Into<String>) -> Self {
+        self.link = Some(link.into());
+        self
+    }
+
+    fn headers(&self) -> Vec<(HeaderName, String)> {
+        let mut headers = vec![(
+            HeaderName::from_static("deprecation"),
+            format!("@{}", self.since.timestamp()),
+        )];
+        if let Some(sunset) = self.sunset {
+            headers.push((
+                HeaderName::from_static("sunset"),
+                sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+            ));
+        }
+        if let Some(link) = &self.link {
+            headers.push((header::LINK, format!("<{}>; rel=\"deprecation\"", link)));
+        }
+        headers
+    }
+}
+
+/// Mounts each version under `/api/<name>` behind the middleware every API
+/// route shares: per-IP rate limits, authentication, per-user rate limits, a
+/// JSON-sized body limit, and deprecation headers for versions marked
+/// deprecated. Handlers still take `ApiUser` for the caller, which is only
+/// looked up once.
+pub fn routes(
+    state: &AppState,
+    versions: impl IntoIterator<Item = ApiVersion>,
+) -> Router<AppState> {
+    versions.into_iter().fold(Router::new(), |router, version| {
+        // Layers wrap what came before, so the last one added runs first
+        let routes = version
+            .routes
+            .layer(DefaultBodyLimit::max(API_MAX_BODY_BYTES))
+            .layer(middleware::from_fn_with_state(
+                state.api_rate_limiter.clone(),
+                limit_api_requests,
+            ))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_api_user,
+            ))
+            .layer(middleware::from_fn_with_state(
+                state.api_rate_limiter.clone(),
+                limit_api_clients,
+            ));
+        let routes = match version.deprecation {
+            Some(deprecation) => routes.layer(middleware::from_fn_with_state(
+                deprecation,
+                add_deprecation_headers,
+            )),
+            None => routes,
+        };
+
+        router.nest(&format!("/api/{}", version.name), routes)
+    })
+}
+
+// Also sent on errors, so clients notice even when their requests fail
+async fn add_deprecation_headers(
+    State(deprecation): State<Deprecation>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+
+    for (name, value) in deprecation.headers() {
+        match HeaderValue::try_from(value) {
+            Ok(value) => {
+                headers.append(name, value);
+            }
+            Err(e) => tracing::warn!("Invalid {} header for deprecated API: {}", name, e),
+        }
+    }
+
+    response
+}
@@ -0,0 +1,153 @@
+use crate::handlers::ClientInfo;
+use crate::models::AuditEvent;
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use std::convert::Infallible;
+
+// Security-relevant actions written to the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    LoginSucceeded,
+    LoginFailed,
+    PasswordChanged,
+    PasswordReset,
+    RoleGranted,
+    RoleRevoked,
+    UserActivated,
+    UserDeactivated,
+    UserUnlocked,
+    EmailVerified,
+    AccountDeleted,
+    InvitationCreated,
+    ApiTokenCreated,
+    ApiTokenRevoked,
+    DataExportRequested,
+    ImpersonationStarted,
+    ImpersonationStopped,
+    OrganizationCreated,
+    OrganizationMemberInvited,
+    OrganizationMemberJoined,
+    OrganizationMemberRemoved,
+    PolicyAccepted,
+    PolicyPublished,
+    WebhookCreated,
+    WebhookDeleted,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 25] = [
+        AuditAction::LoginSucceeded,
+        AuditAction::LoginFailed,
+        AuditAction::PasswordChanged,
+        AuditAction::PasswordReset,
+        AuditAction::RoleGranted,
+        AuditAction::RoleRevoked,
+        AuditAction::UserActivated,
+        AuditAction::UserDeactivated,
+        AuditAction::UserUnlocked,
+        AuditAction::EmailVerified,
+        AuditAction::AccountDeleted,
+        AuditAction::InvitationCreated,
+        AuditAction::ApiTokenCreated,
+        AuditAction::ApiTokenRevoked,
+        AuditAction::DataExportRequested,
+        AuditAction::ImpersonationStarted,
+        AuditAction::ImpersonationStopped,
+        AuditAction::OrganizationCreated,
+        AuditAction::OrganizationMemberInvited,
+        AuditAction::OrganizationMemberJoined,
+        AuditAction::OrganizationMemberRemoved,
+        AuditAction::PolicyAccepted,
+        AuditAction::PolicyPublished,
+        AuditAction::WebhookCreated,
+        AuditAction::WebhookDeleted,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::LoginSucceeded => "login.succeeded",
+            AuditAction::LoginFailed => "login.failed",
+            AuditAction::PasswordChanged => "password.changed",
+            AuditAction::PasswordReset => "password.reset",
+            AuditAction::RoleGranted => "role.granted",
+            AuditAction::RoleRevoked => "role.revoked",
+            AuditAction::UserActivated => "user.activated",
+            AuditAction::UserDeactivated => "user.deactivated",
+            AuditAction::UserUnlocked => "user.unlocked",
+            AuditAction::EmailVerified => "user.email_verified",
+            AuditAction::AccountDeleted => "account.deleted",
+            AuditAction::InvitationCreated => "invitation.created",
+            AuditAction::ApiTokenCreated => "api_token.created",
+            AuditAction::ApiTokenRevoked => "api_token.revoked",
+            AuditAction::DataExportRequested => "data_export.requested",
+            AuditAction::ImpersonationStarted => "impersonation.start",
+            AuditAction::ImpersonationStopped => "impersonation.stop",
+            AuditAction::OrganizationCreated => "organization.created",
+            AuditAction::OrganizationMemberInvited => "organization.member_invited",
+            AuditAction::OrganizationMemberJoined => "organization.member_joined",
+            AuditAction::OrganizationMemberRemoved => "organization.member_removed",
+            AuditAction::PolicyAccepted => "policy.accepted",
+            AuditAction::PolicyPublished => "policy.published",
+            AuditAction::WebhookCreated => "webhook.created",
+            AuditAction::WebhookDeleted => "webhook.deleted",
+        }
+    }
+}
+
+// Records audit events for the current request, tagged with the caller's IP address
+#[derive(Clone)]
+pub struct AuditLogger {
+    pool: SqlitePool,
+    ip_address: Option<String>,
+}
+
+impl AuditLogger {
+    pub fn new(pool: SqlitePool, client: Option<&ClientInfo>) -> Self {
+        Self {
+            pool,
+            ip_address: client.map(|client| client.ip_address.clone()),
+        }
+    }
+
+    /// Records who did what to whom. A failed write is logged rather than
+    /// returned so auditing never blocks the action being audited.
+    pub async fn record(
+        &self,
+        action: AuditAction,
+        actor_id: Option<&str>,
+        target_user_id: Option<&str>,
+        details: Value,
+    ) {
+        if let Err(e) = AuditEvent::record(
+            &self.pool,
+            actor_id,
+            action.as_str(),
+            target_user_id,
+            self.ip_address.as_deref(),
+            details,
+        )
+        .await
+        {
+            tracing::error!("Failed to record audit event {}: {}", action.as_str(), e);
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuditLogger
+where
+    SqlitePool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let client = ClientInfo::from_request_parts(parts, state).await?;
+        Ok(Self::new(SqlitePool::from_ref(state), Some(&client)))
+    }
+}
@@ -0,0 +1,80 @@
+use crate::config::AvatarStorage;
+use image::imageops::FilterType;
+use image::{ImageFormat, ImageReader, Limits};
+use std::io::Cursor;
+use std::path::PathBuf;
+
+// Avatars are stored as square PNGs of this size
+pub const AVATAR_SIZE: u32 = 256;
+// Largest upload accepted, before resizing
+pub const AVATAR_MAX_BYTES: usize = 5 * 1024 * 1024;
+// Refuse to decode images with huge dimensions, which can exhaust memory
+const MAX_SOURCE_DIMENSION: u32 = 8192;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AvatarError {
+    #[error("avatars must be PNG, JPEG, GIF, or WebP images")]
+    UnsupportedFormat,
+    #[error("avatars must be smaller than 5 MB")]
+    TooLarge,
+    #[error("invalid image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("storage error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Decodes an uploaded image, crops it to a centered square, scales it to
+/// [`AVATAR_SIZE`], and re-encodes it as PNG. Re-encoding also strips any
+/// metadata such as location tags. This is CPU heavy, so call it from
+/// `spawn_blocking`.
+pub fn process_avatar(bytes: &[u8]) -> Result<Vec<u8>, AvatarError> {
+    if bytes.len() > AVATAR_MAX_BYTES {
+        return Err(AvatarError::TooLarge);
+    }
+
+    let format = image::guess_format(bytes).map_err(|_| AvatarError::UnsupportedFormat)?;
+    if !matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP
+    ) {
+        return Err(AvatarError::UnsupportedFormat);
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let avatar = reader
+        .decode()?
+        .resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3);
+
+    let mut png = Vec::new();
+    avatar.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+impl AvatarStorage {
+    fn path_for(&self, user_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.png", user_id))
+    }
+
+    pub async fn save(&self, user_id: &str, png: &[u8]) -> Result<(), AvatarError> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(self.path_for(user_id), png).await?;
+        Ok(())
+    }
+
+    pub async fn load(&self, user_id: &str) -> Result<Vec<u8>, AvatarError> {
+        Ok(tokio::fs::read(self.path_for(user_id)).await?)
+    }
+
+    pub async fn delete(&self, user_id: &str) -> Result<(), AvatarError> {
+        match tokio::fs::remove_file(self.path_for(user_id)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
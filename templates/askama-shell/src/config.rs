@@ -0,0 +1,479 @@
+use axum::http::{HeaderValue, Method};
+use std::env;
+use tower_sessions::cookie::SameSite;
+
+#[derive(Debug, Clone)]
+pub struct LockoutPolicy {
+    pub max_attempts: u32,
+    pub window: chrono::Duration,
+}
+
+impl LockoutPolicy {
+    /// Reads `LOCKOUT_MAX_ATTEMPTS` (default 5) and `LOCKOUT_WINDOW_MINUTES`
+    /// (default 15). Setting the attempt limit to 0 disables lockout.
+    pub fn from_env() -> Self {
+        let max_attempts = env::var("LOCKOUT_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(5);
+        let window_minutes = env::var("LOCKOUT_WINDOW_MINUTES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(15);
+
+        Self {
+            max_attempts,
+            window: chrono::Duration::minutes(window_minutes),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PasswordHashing {
+    pub params: argon2::Params,
+}
+
+impl PasswordHashing {
+    /// Reads Argon2id cost settings from `ARGON2_MEMORY_KIB`,
+    /// `ARGON2_ITERATIONS`, and `ARGON2_PARALLELISM`, defaulting to the
+    /// argon2 crate's recommended parameters.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let defaults = argon2::Params::default();
+        let memory_kib = env::var("ARGON2_MEMORY_KIB")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.m_cost());
+        let iterations = env::var("ARGON2_ITERATIONS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.t_cost());
+        let parallelism = env::var("ARGON2_PARALLELISM")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.p_cost());
+
+        let params = argon2::Params::new(memory_kib, iterations, parallelism, None)
+            .map_err(|e| anyhow::anyhow!("Invalid Argon2 parameters: {}", e))?;
+
+        Ok(Self { params })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AccountRetention {
+    pub retention: chrono::Duration,
+}
+
+impl AccountRetention {
+    /// Reads `ACCOUNT_RETENTION_DAYS` (default 30), the number of days a
+    /// deleted account is kept before it's purged for good.
+    pub fn from_env() -> Self {
+        let retention_days = env::var("ACCOUNT_RETENTION_DAYS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(30);
+
+        Self {
+            retention: chrono::Duration::days(retention_days),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SessionBackend {
+    Sqlite,
+    Memory,
+    #[cfg(feature = "redis")]
+    Redis(tower_sessions_redis_store::fred::prelude::RedisConfig),
+}
+
+impl SessionBackend {
+    /// Reads `SESSION_STORE`, either `sqlite` (the default, sessions survive
+    /// restarts and are shared between instances), `memory` for tests, or
+    /// `redis` with `REDIS_URL` when built with the `redis` feature.
+    pub fn from_env() -> anyhow::Result<Self> {
+        match env::var("SESSION_STORE").as_deref() {
+            Ok("sqlite") | Err(_) => Ok(Self::Sqlite),
+            Ok("memory") => Ok(Self::Memory),
+            #[cfg(feature = "redis")]
+            Ok("redis") => {
+                let redis_url = env::var("REDIS_URL").map_err(|_| {
+                    anyhow::anyhow!("REDIS_URL must be set when SESSION_STORE=redis")
+                })?;
+                let redis_config =
+                    tower_sessions_redis_store::fred::prelude::RedisConfig::from_url(&redis_url)
+                        .map_err(|e| anyhow::anyhow!("Invalid REDIS_URL: {}", e))?;
+                Ok(Self::Redis(redis_config))
+            }
+            #[cfg(not(feature = "redis"))]
+            Ok("redis") => Err(anyhow::anyhow!(
+                "SESSION_STORE=redis requires building with the redis feature"
+            )),
+            Ok(other) => Err(anyhow::anyhow!("Unknown SESSION_STORE: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignupMode {
+    Open,
+    InviteOnly,
+}
+
+impl SignupMode {
+    /// Reads `SIGNUP_MODE`, either `open` (the default) or `invite_only`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        match env::var("SIGNUP_MODE").as_deref() {
+            Ok("open") | Err(_) => Ok(Self::Open),
+            Ok("invite_only") => Ok(Self::InviteOnly),
+            Ok(other) => Err(anyhow::anyhow!("Unknown SIGNUP_MODE: {}", other)),
+        }
+    }
+}
+
+// Shortest HMAC secret accepted for signing JWTs
+const MIN_JWT_SECRET_BYTES: usize = 32;
+
+#[derive(Clone)]
+pub struct JwtSigningKey {
+    pub kid: String,
+    pub secret: Vec<u8>,
+}
+
+// Keep secrets out of logs
+impl std::fmt::Debug for JwtSigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtSigningKey")
+            .field("kid", &self.kid)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JwtConfig {
+    /// The first key signs new tokens, the others are only used to verify
+    pub signing_keys: Vec<JwtSigningKey>,
+    pub access_ttl: chrono::Duration,
+    pub refresh_ttl: chrono::Duration,
+}
+
+impl JwtConfig {
+    /// Reads `JWT_SIGNING_KEYS`, a comma-separated list of `kid:secret` pairs.
+    /// Keeping a retired key after the new one lets tokens it signed run out
+    /// instead of failing right away. Without it a random key is generated and
+    /// tokens stop working on restart. Access tokens live for
+    /// `JWT_ACCESS_TTL_MINUTES` (default 15) and refresh tokens for
+    /// `JWT_REFRESH_TTL_DAYS` (default 30).
+    pub fn from_env() -> anyhow::Result<Self> {
+        let signing_keys = match env::var("JWT_SIGNING_KEYS") {
+            Ok(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| {
+                    let (kid, secret) = entry.split_once(':').ok_or_else(|| {
+                        anyhow::anyhow!("JWT_SIGNING_KEYS entries must look like kid:secret")
+                    })?;
+                    if secret.len() < MIN_JWT_SECRET_BYTES {
+                        return Err(anyhow::anyhow!(
+                            "JWT signing key {} must be at least {} bytes",
+                            kid,
+                            MIN_JWT_SECRET_BYTES
+                        ));
+                    }
+                    Ok(JwtSigningKey {
+                        kid: kid.to_string(),
+                        secret: secret.as_bytes().to_vec(),
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            Err(_) => {
+                tracing::warn!("JWT_SIGNING_KEYS is not set, using a random key for this run");
+                let secret: Vec<u8> = (0..MIN_JWT_SECRET_BYTES)
+                    .map(|_| rand::random::<u8>())
+                    .collect();
+                vec![JwtSigningKey {
+                    kid: "ephemeral".to_string(),
+                    secret,
+                }]
+            }
+        };
+        if signing_keys.is_empty() {
+            return Err(anyhow::anyhow!(
+                "JWT_SIGNING_KEYS must contain at least one key"
+            ));
+        }
+
+        let access_ttl_minutes = env::var("JWT_ACCESS_TTL_MINUTES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(15);
+        let refresh_ttl_days = env::var("JWT_REFRESH_TTL_DAYS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(30);
+
+        Ok(Self {
+            signing_keys,
+            access_ttl: chrono::Duration::minutes(access_ttl_minutes),
+            refresh_ttl: chrono::Duration::days(refresh_ttl_days),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AvatarStorage {
+    pub dir: std::path::PathBuf,
+}
+
+impl AvatarStorage {
+    /// Reads `AVATAR_DIR` (default `uploads/avatars`), the directory resized
+    /// avatars are written to. Mount it on a persistent volume in production.
+    pub fn from_env() -> Self {
+        let dir = env::var("AVATAR_DIR").unwrap_or_else(|_| "uploads/avatars".to_string());
+
+        Self { dir: dir.into() }
+    }
+}
+
+// Allows the stylesheet and scripts that base.html inlines, and Alpine's
+// expression evaluator, which needs 'unsafe-eval'
+const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+    script-src 'self' 'unsafe-inline' 'unsafe-eval'; \
+    style-src 'self' 'unsafe-inline' https://fonts.googleapis.com; \
+    font-src 'self' https://fonts.gstatic.com; \
+    img-src 'self' data: blob:; \
+    connect-src 'self'; \
+    object-src 'none'; \
+    base-uri 'self'; \
+    form-action 'self'; \
+    frame-ancestors 'none'";
+
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    /// `None` leaves the header off
+    pub content_security_policy: Option<HeaderValue>,
+    /// Send the policy as `Content-Security-Policy-Report-Only`
+    pub csp_report_only: bool,
+    pub strict_transport_security: Option<HeaderValue>,
+    pub referrer_policy: HeaderValue,
+}
+
+impl SecurityHeaders {
+    /// Reads `CONTENT_SECURITY_POLICY` (set it empty to drop the header),
+    /// `CSP_REPORT_ONLY` to try out a stricter policy without enforcing it,
+    /// `HSTS_MAX_AGE_SECONDS` (default one year, 0 disables HSTS), and
+    /// `REFERRER_POLICY` (default `strict-origin-when-cross-origin`).
+    pub fn from_env() -> anyhow::Result<Self> {
+        let content_security_policy = match env::var("CONTENT_SECURITY_POLICY") {
+            Ok(policy) if policy.trim().is_empty() => None,
+            Ok(policy) => Some(policy),
+            Err(_) => Some(DEFAULT_CONTENT_SECURITY_POLICY.to_string()),
+        }
+        .map(|policy| {
+            HeaderValue::from_str(&policy)
+                .map_err(|_| anyhow::anyhow!("CONTENT_SECURITY_POLICY is not a valid header value"))
+        })
+        .transpose()?;
+
+        let csp_report_only = env::var("CSP_REPORT_ONLY")
+            .map(|value| value == "true" || value == "1")
+            .unwrap_or(false);
+
+        let hsts_max_age: u64 = match env::var("HSTS_MAX_AGE_SECONDS") {
+            Ok(value) => value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("HSTS_MAX_AGE_SECONDS must be a number of seconds"))?,
+            Err(_) => 60 * 60 * 24 * 365,
+        };
+        let strict_transport_security = (hsts_max_age > 0)
+            .then(|| HeaderValue::from_str(&format!("max-age={}", hsts_max_age)))
+            .transpose()?;
+
+        let referrer_policy = env::var("REFERRER_POLICY")
+            .unwrap_or_else(|_| "strict-origin-when-cross-origin".to_string());
+        let referrer_policy = HeaderValue::from_str(&referrer_policy)
+            .map_err(|_| anyhow::anyhow!("REFERRER_POLICY is not a valid header value"))?;
+
+        Ok(Self {
+            content_security_policy,
+            csp_report_only,
+            strict_transport_security,
+            referrer_policy,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    /// Empty means only same-origin requests are allowed
+    pub allowed_origins: Vec<HeaderValue>,
+    pub allowed_methods: Vec<Method>,
+    pub allow_credentials: bool,
+    pub max_age: std::time::Duration,
+}
+
+impl CorsConfig {
+    /// Reads `CORS_ALLOWED_ORIGINS`, a comma-separated list of origins such as
+    /// `https://app.example.com` (unset allows none, so only same-origin
+    /// requests work), `CORS_ALLOWED_METHODS` (default `GET,POST`),
+    /// `CORS_ALLOW_CREDENTIALS` to let those origins send cookies, and
+    /// `CORS_MAX_AGE_SECONDS` (default 600) for caching preflight responses.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let allowed_origins = env::var("CORS_ALLOWED_ORIGINS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(parse_origin)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let allowed_methods = env::var("CORS_ALLOWED_METHODS")
+            .unwrap_or_else(|_| "GET,POST".to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|method| !method.is_empty())
+            .map(|method| {
+                Method::from_bytes(method.to_ascii_uppercase().as_bytes()).map_err(|_| {
+                    anyhow::anyhow!("Invalid method in CORS_ALLOWED_METHODS: {}", method)
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let allow_credentials = env::var("CORS_ALLOW_CREDENTIALS")
+            .map(|value| value == "true" || value == "1")
+            .unwrap_or(false);
+
+        let max_age_seconds = match env::var("CORS_MAX_AGE_SECONDS") {
+            Ok(value) => value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("CORS_MAX_AGE_SECONDS must be a number of seconds"))?,
+            Err(_) => 600,
+        };
+
+        Ok(Self {
+            allowed_origins,
+            allowed_methods,
+            allow_credentials,
+            max_age: std::time::Duration::from_secs(max_age_seconds),
+        })
+    }
+}
+
+// Origins are compared byte for byte, so insist on the exact form browsers send:
+// scheme and host, an optional port, and nothing else
+fn parse_origin(origin: &str) -> anyhow::Result<HeaderValue> {
+    let invalid = || {
+        anyhow::anyhow!(
+            "Invalid origin in CORS_ALLOWED_ORIGINS: {} (expected something like https://app.example.com)",
+            origin
+        )
+    };
+
+    let url = url::Url::parse(origin).map_err(|_| invalid())?;
+    if !matches!(url.scheme(), "http" | "https") || url.origin().ascii_serialization() != origin {
+        return Err(invalid());
+    }
+
+    HeaderValue::from_str(origin).map_err(|_| invalid())
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionCookie {
+    pub name: String,
+    pub secure: bool,
+    pub same_site: SameSite,
+    pub domain: Option<String>,
+    /// How long a signed-out session, e.g. one holding a CSRF token or flash
+    /// messages, lasts without requests
+    pub inactivity_timeout: chrono::Duration,
+    /// How long a "remember me" sign-in lasts without requests
+    pub remembered_timeout: chrono::Duration,
+    /// How long any other sign-in lasts without requests. Its cookie also
+    /// ends with the browser session.
+    pub short_inactivity_timeout: chrono::Duration,
+}
+
+impl SessionCookie {
+    pub const DEFAULT_SHORT_INACTIVITY_MINUTES: i64 = 120;
+
+    /// Reads `SESSION_COOKIE_NAME` (default `id`), `SESSION_COOKIE_SECURE`
+    /// (defaults to on unless `APP_BASE_URL` is plain `http://`, as in local
+    /// development), `SESSION_COOKIE_SAME_SITE` (`strict`, `lax`, the default,
+    /// or `none`), `SESSION_COOKIE_DOMAIN` to share the cookie with
+    /// subdomains, `SESSION_INACTIVITY_DAYS` for signed-out sessions
+    /// (default 7), `SESSION_REMEMBER_DAYS` for "remember me" sign-ins
+    /// (default 30), and `SESSION_SHORT_INACTIVITY_MINUTES` for other
+    /// sign-ins (default 120).
+    pub fn from_env() -> anyhow::Result<Self> {
+        let name = env::var("SESSION_COOKIE_NAME").unwrap_or_else(|_| "id".to_string());
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(anyhow::anyhow!(
+                "SESSION_COOKIE_NAME may only contain letters, digits, '_' and '-'"
+            ));
+        }
+
+        let secure = match env::var("SESSION_COOKIE_SECURE").as_deref() {
+            Ok("true") | Ok("1") => true,
+            Ok("false") | Ok("0") => false,
+            Ok(other) => {
+                return Err(anyhow::anyhow!(
+                    "SESSION_COOKIE_SECURE must be true or false, got {}",
+                    other
+                ));
+            }
+            Err(_) => !env::var("APP_BASE_URL")
+                .unwrap_or_default()
+                .starts_with("http://"),
+        };
+
+        let same_site = match env::var("SESSION_COOKIE_SAME_SITE").as_deref() {
+            Ok("lax") | Err(_) => SameSite::Lax,
+            Ok("strict") => SameSite::Strict,
+            Ok("none") => SameSite::None,
+            Ok(other) => {
+                return Err(anyhow::anyhow!(
+                    "Unknown SESSION_COOKIE_SAME_SITE: {}",
+                    other
+                ));
+            }
+        };
+        // Browsers drop SameSite=None cookies that aren't also Secure
+        if same_site == SameSite::None && !secure {
+            return Err(anyhow::anyhow!(
+                "SESSION_COOKIE_SAME_SITE=none requires SESSION_COOKIE_SECURE=true"
+            ));
+        }
+
+        let domain = env::var("SESSION_COOKIE_DOMAIN")
+            .ok()
+            .filter(|domain| !domain.is_empty());
+
+        let inactivity_days = env::var("SESSION_INACTIVITY_DAYS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(7);
+        let remember_days = env::var("SESSION_REMEMBER_DAYS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(30);
+        let short_inactivity_minutes = env::var("SESSION_SHORT_INACTIVITY_MINUTES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(Self::DEFAULT_SHORT_INACTIVITY_MINUTES);
+
+        Ok(Self {
+            name,
+            secure,
+            same_site,
+            domain,
+            inactivity_timeout: chrono::Duration::days(inactivity_days),
+            remembered_timeout: chrono::Duration::days(remember_days),
+            short_inactivity_timeout: chrono::Duration::minutes(short_inactivity_minutes),
+        })
+    }
+}
@@ -0,0 +1,76 @@
+use crate::handlers::IMPERSONATOR_KEY;
+use crate::models::PolicyDocument;
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use serde_json::json;
+use sqlx::SqlitePool;
+use tower_sessions::Session;
+
+/// Page where signed-in users accept new policy versions
+pub const ACCEPT_POLICIES_PATH: &str = "/policies/accept";
+
+// Reachable while acceptance is pending, so users can read the documents or
+// leave, and declining users can still export their data and delete the account
+const EXEMPT_PATHS: [&str; 7] = [
+    ACCEPT_POLICIES_PATH,
+    "/terms",
+    "/privacy",
+    "/logout",
+    "/settings",
+    "/settings/delete",
+    "/settings/export",
+];
+// Export downloads, under /settings/export/:id
+const EXEMPT_PREFIXES: [&str; 1] = ["/settings/export/"];
+
+/// Holds signed-in users at the re-acceptance page until they've accepted the
+/// latest Terms of Service and Privacy Policy. Page loads are redirected there;
+/// other requests get 403 with a JSON message. Bearer-token API calls carry no
+/// session and pass through, as do admins signed in as another user.
+pub async fn require_current_policies(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if EXEMPT_PATHS.contains(&path)
+        || EXEMPT_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+    {
+        return next.run(request).await;
+    }
+
+    let Ok(Some(user_id)) = session.get::<String>("user_id").await else {
+        return next.run(request).await;
+    };
+    if let Ok(Some(_)) = session.get::<String>(IMPERSONATOR_KEY).await {
+        return next.run(request).await;
+    }
+
+    match PolicyDocument::find_unaccepted(&pool, &user_id).await {
+        Ok(pending) if pending.is_empty() => next.run(request).await,
+        Ok(_) if request.method() == Method::GET => {
+            Redirect::to(ACCEPT_POLICIES_PATH).into_response()
+        }
+        Ok(_) => (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "success": false,
+                "message": "Please accept the updated Terms of Service and Privacy Policy to continue",
+                "redirect": ACCEPT_POLICIES_PATH
+            })),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Database error checking policy acceptance: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
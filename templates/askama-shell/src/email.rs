@@ -0,0 +1,423 @@
+use crate::models::{EmailChangeToken, EmailVerificationToken, OrganizationInvitation, User};
+use askama::Template;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use sqlx::SqlitePool;
+use std::env;
+
+// Verification links stay valid for one day
+const VERIFICATION_TOKEN_TTL_HOURS: i64 = 24;
+const EMAIL_CHANGE_TOKEN_TTL_HOURS: i64 = 24;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EmailError {
+    #[error("invalid email address: {0}")]
+    Address(#[from] lettre::address::AddressError),
+    #[error("failed to build message: {0}")]
+    Message(#[from] lettre::error::Error),
+    #[error("SMTP error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("template error: {0}")]
+    Template(#[from] askama::Error),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Template)]
+#[template(path = "emails/verify_email.html")]
+struct VerifyEmailHtml<'a> {
+    username: &'a str,
+    verify_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/verify_email.txt")]
+struct VerifyEmailText<'a> {
+    username: &'a str,
+    verify_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/confirm_email_change.html")]
+struct ConfirmEmailChangeHtml<'a> {
+    username: &'a str,
+    new_email: &'a str,
+    confirm_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/confirm_email_change.txt")]
+struct ConfirmEmailChangeText<'a> {
+    username: &'a str,
+    new_email: &'a str,
+    confirm_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/email_change_notice.html")]
+struct EmailChangeNoticeHtml<'a> {
+    username: &'a str,
+    new_email: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/email_change_notice.txt")]
+struct EmailChangeNoticeText<'a> {
+    username: &'a str,
+    new_email: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/data_export_ready.html")]
+struct DataExportReadyHtml<'a> {
+    username: &'a str,
+    download_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/data_export_ready.txt")]
+struct DataExportReadyText<'a> {
+    username: &'a str,
+    download_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/organization_invitation.html")]
+struct OrganizationInvitationHtml<'a> {
+    inviter: &'a str,
+    organization_name: &'a str,
+    accept_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/organization_invitation.txt")]
+struct OrganizationInvitationText<'a> {
+    inviter: &'a str,
+    organization_name: &'a str,
+    accept_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/new_sign_in.html")]
+struct NewSignInHtml<'a> {
+    username: &'a str,
+    signed_in_at: &'a str,
+    ip_address: &'a str,
+    device: &'a str,
+    settings_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/new_sign_in.txt")]
+struct NewSignInText<'a> {
+    username: &'a str,
+    signed_in_at: &'a str,
+    ip_address: &'a str,
+    device: &'a str,
+    settings_url: &'a str,
+}
+
+#[derive(Clone)]
+enum Transport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    // Used when no SMTP server is configured; messages are written to the log instead
+    Log,
+}
+
+#[derive(Clone)]
+pub struct Mailer {
+    transport: Transport,
+    from: Mailbox,
+    base_url: String,
+}
+
+impl Mailer {
+    /// Builds a mailer from `SMTP_*` environment variables, falling back to
+    /// logging messages when `SMTP_HOST` is unset.
+    pub fn from_env() -> Result<Self, EmailError> {
+        let from = env::var("SMTP_FROM")
+            .unwrap_or_else(|_| "Rust Web Shell <no-reply@localhost>".to_string())
+            .parse::<Mailbox>()?;
+
+        let base_url = env::var("APP_BASE_URL")
+            .unwrap_or_else(|_| "http://127.0.0.1:3000".to_string())
+            .trim_end_matches('/')
+            .to_string();
+
+        let transport = match env::var("SMTP_HOST") {
+            Ok(host) => {
+                let port = env::var("SMTP_PORT")
+                    .ok()
+                    .and_then(|port| port.parse::<u16>().ok())
+                    .unwrap_or(587);
+
+                let mut builder =
+                    AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?.port(port);
+                if let (Ok(username), Ok(password)) =
+                    (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD"))
+                {
+                    builder = builder.credentials(Credentials::new(username, password));
+                }
+
+                Transport::Smtp(builder.build())
+            }
+            Err(_) => {
+                tracing::warn!("SMTP_HOST not set, outgoing emails will only be logged");
+                Transport::Log
+            }
+        };
+
+        Ok(Self {
+            transport,
+            from,
+            base_url,
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub async fn send(
+        &self,
+        to: &str,
+        subject: &str,
+        text_body: String,
+        html_body: String,
+    ) -> Result<(), EmailError> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .multipart(MultiPart::alternative_plain_html(
+                text_body.clone(),
+                html_body,
+            ))?;
+
+        match &self.transport {
+            Transport::Smtp(transport) => {
+                transport.send(message).await?;
+            }
+            Transport::Log => {
+                tracing::info!("Email to {} ({}):\n{}", to, subject, text_body);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn send_verification_email(
+    pool: &SqlitePool,
+    mailer: &Mailer,
+    user: &User,
+) -> Result<(), EmailError> {
+    // Only the most recent link should work
+    EmailVerificationToken::delete_for_user(pool, &user.id).await?;
+    let token = EmailVerificationToken::create(
+        pool,
+        &user.id,
+        chrono::Duration::hours(VERIFICATION_TOKEN_TTL_HOURS),
+    )
+    .await?;
+
+    let verify_url = format!("{}/verify-email/{}", mailer.base_url(), token.token);
+    let html = VerifyEmailHtml {
+        username: &user.username,
+        verify_url: &verify_url,
+    }
+    .render()?;
+    let text = VerifyEmailText {
+        username: &user.username,
+        verify_url: &verify_url,
+    }
+    .render()?;
+
+    mailer
+        .send(&user.email, "Verify your email address", text, html)
+        .await
+}
+
+pub async fn send_email_change_emails(
+    pool: &SqlitePool,
+    mailer: &Mailer,
+    user: &User,
+    new_email: &str,
+) -> Result<(), EmailError> {
+    // Only the most recent request should be confirmable
+    EmailChangeToken::delete_for_user(pool, &user.id).await?;
+    let token = EmailChangeToken::create(
+        pool,
+        &user.id,
+        new_email,
+        chrono::Duration::hours(EMAIL_CHANGE_TOKEN_TTL_HOURS),
+    )
+    .await?;
+
+    let confirm_url = format!("{}/confirm-email/{}", mailer.base_url(), token.token);
+    let html = ConfirmEmailChangeHtml {
+        username: &user.username,
+        new_email,
+        confirm_url: &confirm_url,
+    }
+    .render()?;
+    let text = ConfirmEmailChangeText {
+        username: &user.username,
+        new_email,
+        confirm_url: &confirm_url,
+    }
+    .render()?;
+    mailer
+        .send(new_email, "Confirm your new email address", text, html)
+        .await?;
+
+    // Let the current address know in case the change wasn't requested by its owner
+    let html = EmailChangeNoticeHtml {
+        username: &user.username,
+        new_email,
+    }
+    .render()?;
+    let text = EmailChangeNoticeText {
+        username: &user.username,
+        new_email,
+    }
+    .render()?;
+    mailer
+        .send(
+            &user.email,
+            "Your email address is being changed",
+            text,
+            html,
+        )
+        .await
+}
+
+pub async fn send_new_sign_in_email(
+    mailer: &Mailer,
+    user: &User,
+    user_agent: &str,
+    ip_address: &str,
+) -> Result<(), EmailError> {
+    let signed_in_at = chrono::Utc::now()
+        .format("%B %d, %Y at %H:%M UTC")
+        .to_string();
+    let device = describe_user_agent(user_agent);
+    let settings_url = format!("{}/settings", mailer.base_url());
+
+    let html = NewSignInHtml {
+        username: &user.username,
+        signed_in_at: &signed_in_at,
+        ip_address,
+        device: &device,
+        settings_url: &settings_url,
+    }
+    .render()?;
+    let text = NewSignInText {
+        username: &user.username,
+        signed_in_at: &signed_in_at,
+        ip_address,
+        device: &device,
+        settings_url: &settings_url,
+    }
+    .render()?;
+
+    mailer
+        .send(&user.email, "New sign-in to your account", text, html)
+        .await
+}
+
+pub async fn send_data_export_ready_email(
+    mailer: &Mailer,
+    user: &User,
+    download_url: &str,
+) -> Result<(), EmailError> {
+    let html = DataExportReadyHtml {
+        username: &user.username,
+        download_url,
+    }
+    .render()?;
+    let text = DataExportReadyText {
+        username: &user.username,
+        download_url,
+    }
+    .render()?;
+
+    mailer
+        .send(&user.email, "Your data export is ready", text, html)
+        .await
+}
+
+pub async fn send_organization_invitation_email(
+    mailer: &Mailer,
+    invitation: &OrganizationInvitation,
+    organization_name: &str,
+    inviter: &str,
+) -> Result<(), EmailError> {
+    let accept_url = format!(
+        "{}/organizations/invitations/{}",
+        mailer.base_url(),
+        invitation.token
+    );
+    let html = OrganizationInvitationHtml {
+        inviter,
+        organization_name,
+        accept_url: &accept_url,
+    }
+    .render()?;
+    let text = OrganizationInvitationText {
+        inviter,
+        organization_name,
+        accept_url: &accept_url,
+    }
+    .render()?;
+
+    mailer
+        .send(
+            &invitation.email,
+            &format!("Join {} on Rust Web Shell", organization_name),
+            text,
+            html,
+        )
+        .await
+}
+
+// Reduce a User-Agent header to something readable like "Firefox on Linux"
+fn describe_user_agent(user_agent: &str) -> String {
+    let browser = if user_agent.contains("Edg/") {
+        Some("Edge")
+    } else if user_agent.contains("OPR/") {
+        Some("Opera")
+    } else if user_agent.contains("Firefox/") {
+        Some("Firefox")
+    } else if user_agent.contains("Chrome/") {
+        Some("Chrome")
+    } else if user_agent.contains("Safari/") {
+        Some("Safari")
+    } else {
+        None
+    };
+
+    let os = if user_agent.contains("Windows") {
+        Some("Windows")
+    } else if user_agent.contains("Android") {
+        Some("Android")
+    } else if user_agent.contains("iPhone") || user_agent.contains("iPad") {
+        Some("iOS")
+    } else if user_agent.contains("Mac OS X") {
+        Some("macOS")
+    } else if user_agent.contains("CrOS") {
+        Some("ChromeOS")
+    } else if user_agent.contains("Linux") {
+        Some("Linux")
+    } else {
+        None
+    };
+
+    match (browser, os) {
+        (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+        (Some(browser), None) => browser.to_string(),
+        (None, Some(os)) => format!("Unknown browser on {}", os),
+        (None, None) => "Unknown device".to_string(),
+    }
+}
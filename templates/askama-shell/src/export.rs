@@ -0,0 +1,134 @@
+use crate::config::AvatarStorage;
+use crate::email::{Mailer, send_data_export_ready_email};
+use crate::live::{Hub, LiveEvent};
+use crate::models::{
+    ApiToken, AuditEvent, DataExport, KnownDevice, Membership, Note, PolicyAcceptance, Role, User,
+    UserResponse, UserSession, WebauthnCredential,
+};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_json::{Value, json};
+use sqlx::SqlitePool;
+
+// How long a finished export can be downloaded
+const DATA_EXPORT_TTL_DAYS: i64 = 7;
+
+/// Assembles the user's data in the background, then emails them a link to
+/// download it and tells their open pages. The export is marked failed if
+/// anything goes wrong.
+pub fn spawn_data_export(
+    pool: SqlitePool,
+    mailer: Mailer,
+    hub: Hub,
+    avatars: AvatarStorage,
+    user: User,
+    export_id: String,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let data = match collect_user_data(&pool, &avatars, &user).await {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::error!("Failed to assemble data export {}: {}", export_id, e);
+                if let Err(e) = DataExport::fail(&pool, &export_id).await {
+                    tracing::error!("Failed to mark data export {} failed: {}", export_id, e);
+                }
+                hub.publish_to(&user.id, LiveEvent::DataExportFailed { export_id });
+                return;
+            }
+        };
+
+        let export = match DataExport::complete(
+            &pool,
+            &export_id,
+            &data.to_string(),
+            chrono::Duration::days(DATA_EXPORT_TTL_DAYS),
+        )
+        .await
+        {
+            Ok(export) => export,
+            Err(e) => {
+                tracing::error!("Failed to save data export {}: {}", export_id, e);
+                return;
+            }
+        };
+
+        hub.publish_to(
+            &user.id,
+            LiveEvent::DataExportReady {
+                export_id: export.id.clone(),
+            },
+        );
+
+        let download_url = format!("{}/settings/export/{}", mailer.base_url(), export.id);
+        if let Err(e) = send_data_export_ready_email(&mailer, &user, &download_url).await {
+            tracing::error!("Failed to send data export email to {}: {}", user.id, e);
+        }
+    })
+}
+
+// Helper function to gather everything stored about a user
+async fn collect_user_data(
+    pool: &SqlitePool,
+    avatars: &AvatarStorage,
+    user: &User,
+) -> Result<Value, sqlx::Error> {
+    let roles = Role::find_for_user(pool, &user.id).await?;
+    let sessions = UserSession::find_by_user(pool, &user.id).await?;
+    let known_devices = KnownDevice::find_by_user(pool, &user.id).await?;
+    let api_tokens = ApiToken::find_by_user(pool, &user.id).await?;
+    let passkeys = WebauthnCredential::find_by_user(pool, &user.id).await?;
+    let audit_events = AuditEvent::find_for_user(pool, &user.id).await?;
+    let memberships = Membership::find_for_user(pool, &user.id).await?;
+    let policy_acceptances = PolicyAcceptance::find_for_user(pool, &user.id).await?;
+    let notes = Note::find_all_for_user(pool, &user.id).await?;
+    // Uploaded files are embedded, so the export is a single document
+    let avatar = match user.avatar_updated_at {
+        Some(_) => match avatars.load(&user.id).await {
+            Ok(png) => Some(format!("data:image/png;base64,{}", BASE64.encode(png))),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to read avatar of user {} for export: {}",
+                    user.id,
+                    e
+                );
+                None
+            }
+        },
+        None => None,
+    };
+
+    Ok(json!({
+        "exported_at": chrono::Utc::now(),
+        // Includes the display name and bio
+        "profile": UserResponse::from(user.clone()),
+        "avatar": avatar,
+        "roles": roles.iter().map(|role| &role.name).collect::<Vec<_>>(),
+        "sessions": sessions,
+        "known_devices": known_devices,
+        "api_tokens": api_tokens,
+        "memberships": memberships,
+        "policy_acceptances": policy_acceptances,
+        "notes": notes,
+        // Public keys are left out, they're only meaningful to this server
+        "passkeys": passkeys
+            .iter()
+            .map(|passkey| json!({
+                "name": passkey.name,
+                "created_at": passkey.created_at,
+                "last_used_at": passkey.last_used_at,
+            }))
+            .collect::<Vec<_>>(),
+        "audit_events": audit_events
+            .iter()
+            .map(|event| json!({
+                "action": event.action,
+                "actor_id": event.actor_id,
+                "target_user_id": event.target_user_id,
+                "ip_address": event.ip_address,
+                "details": serde_json::from_str::<Value>(&event.details)
+                    .unwrap_or(Value::Null),
+                "created_at": event.created_at,
+            }))
+            .collect::<Vec<_>>(),
+    }))
+}
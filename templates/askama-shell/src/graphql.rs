@@ -0,0 +1,406 @@
+use crate::api_auth::ApiUser;
+use crate::handlers::dashboard::get_or_create_csrf_token;
+use crate::models::{CreateNoteRequest, Note, NoteSort, UpdateNoteRequest, User};
+use crate::pagination::{Paginated, SortDirection, SortKey};
+use crate::rate_limit::{ApiRateLimiter, limit_api_clients};
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{
+    Context, EmptySubscription, Error, ErrorExtensions, ID, InputObject, Object, Result, Schema,
+    SimpleObject,
+};
+use axum::{
+    Extension, Json, Router,
+    extract::State,
+    http::{HeaderMap, HeaderValue, header},
+    middleware,
+    response::{Html, IntoResponse, Response},
+    routing::post,
+};
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use tower_sessions::Session;
+use validator::Validate;
+
+use crate::csrf::CSRF_HEADER;
+use crate::state::AppState;
+
+// GraphiQL is loaded from unpkg, which the site-wide policy doesn't allow
+const GRAPHIQL_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+    script-src 'self' 'unsafe-inline' https://unpkg.com; \
+    style-src 'self' 'unsafe-inline' https://unpkg.com; \
+    img-src 'self' data: https://unpkg.com https://graphql.org; \
+    font-src 'self' data: https://unpkg.com; \
+    connect-src 'self'; \
+    frame-ancestors 'none'";
+// Deeper or costlier queries are rejected before any resolver runs. The
+// introspection query GraphiQL sends nests up to 15 levels deep.
+const MAX_QUERY_DEPTH: usize = 16;
+const MAX_QUERY_COMPLEXITY: usize = 500;
+// Matches the gRPC BatchGetUsers limit
+const MAX_BATCH_USERS: usize = 100;
+
+pub type AppSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+/// Routes for `/graphql`: POST runs queries, GET serves the GraphiQL
+/// playground when `GRAPHIQL_ENABLED` is on (the default in debug builds).
+/// Requests are authenticated and rate limited the same way as `/api` routes.
+pub fn routes(state: &AppState) -> Router<AppState> {
+    let graphiql_enabled = env::var("GRAPHIQL_ENABLED")
+        .map(|value| value == "true" || value == "1")
+        .unwrap_or(cfg!(debug_assertions));
+
+    let route = if graphiql_enabled {
+        post(graphql_handler).get(graphiql)
+    } else {
+        post(graphql_handler)
+    };
+
+    Router::new()
+        .route("/graphql", route)
+        .layer(Extension(build_schema(state.pool.clone())))
+        .layer(middleware::from_fn_with_state(
+            state.api_rate_limiter.clone(),
+            limit_api_clients,
+        ))
+}
+
+pub fn build_schema(pool: SqlitePool) -> AppSchema {
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .data(DataLoader::new(
+            UserLoader { pool: pool.clone() },
+            tokio::spawn,
+        ))
+        .data(pool)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish()
+}
+
+async fn graphql_handler(
+    Extension(schema): Extension<AppSchema>,
+    headers: HeaderMap,
+    State(limiter): State<ApiRateLimiter>,
+    viewer: Result<ApiUser, Response>,
+    Json(request): Json<async_graphql::Request>,
+) -> Result<Json<async_graphql::Response>, Response> {
+    let request = match viewer {
+        Ok(viewer) => {
+            if let Some(response) = limiter.check_user(&viewer.user.id) {
+                tracing::warn!("Rate limited GraphQL requests from user {}", viewer.user.id);
+                return Err(response);
+            }
+            request.data(Viewer(viewer))
+        }
+        // A bad token is an error; no credentials at all is an anonymous request
+        Err(response) if headers.contains_key(header::AUTHORIZATION) => return Err(response),
+        Err(_) => request,
+    };
+    Ok(Json(schema.execute(request).await))
+}
+
+async fn graphiql(session: Session) -> Result<Response, Response> {
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+    let html = GraphiQLSource::build()
+        .endpoint("/graphql")
+        .header(CSRF_HEADER, &csrf_token)
+        .finish();
+
+    Ok((
+        [(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static(GRAPHIQL_CONTENT_SECURITY_POLICY),
+        )],
+        Html(html),
+    )
+        .into_response())
+}
+
+// The authenticated caller, absent for anonymous requests
+struct Viewer(ApiUser);
+
+// Resolves the caller, checking personal access token scopes
+fn viewer<'a>(ctx: &'a Context<'_>, scope: &'static str) -> Result<&'a ApiUser> {
+    let viewer = ctx.data_opt::<Viewer>().ok_or_else(|| {
+        Error::new("Authentication required").extend_with(|_, e| e.set("code", "UNAUTHENTICATED"))
+    })?;
+    viewer.0.require_scope(scope).map_err(|missing| {
+        Error::new(format!("Token is missing the {} scope", missing.0))
+            .extend_with(|_, e| e.set("code", "FORBIDDEN"))
+    })?;
+    Ok(&viewer.0)
+}
+
+fn database_error(e: sqlx::Error) -> Error {
+    tracing::error!("Database error in GraphQL resolver: {}", e);
+    Error::new("Database error")
+}
+
+fn validation_error(validation_errors: validator::ValidationErrors) -> Error {
+    let mut errors = HashMap::new();
+    for (field, field_errors) in validation_errors.field_errors() {
+        let error_message = field_errors[0]
+            .message
+            .as_ref()
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| "Invalid input".to_string());
+        errors.insert(field.to_string(), error_message);
+    }
+
+    Error::new("Invalid input").extend_with(|_, e| {
+        e.set("code", "BAD_USER_INPUT");
+        for (field, message) in &errors {
+            e.set(field.as_str(), message.as_str());
+        }
+    })
+}
+
+/// Batches user lookups from many resolvers into one query per tick
+pub struct UserLoader {
+    pool: SqlitePool,
+}
+
+impl Loader<String> for UserLoader {
+    type Value = User;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, User>, Self::Error> {
+        let users = User::find_by_ids(&self.pool, keys)
+            .await
+            .map_err(Arc::new)?;
+        Ok(users
+            .into_iter()
+            .map(|user| (user.id.clone(), user))
+            .collect())
+    }
+}
+
+/// Public profile of an account
+#[derive(SimpleObject)]
+#[graphql(name = "User")]
+pub struct UserObject {
+    id: ID,
+    username: String,
+    display_name: Option<String>,
+    bio: Option<String>,
+    avatar_url: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<User> for UserObject {
+    fn from(user: User) -> Self {
+        let avatar_url = user.avatar_url();
+        Self {
+            id: ID(user.id),
+            username: user.username,
+            display_name: user.display_name,
+            bio: user.bio,
+            avatar_url,
+            created_at: user.created_at,
+        }
+    }
+}
+
+pub struct NoteObject(Note);
+
+#[Object(name = "Note")]
+impl NoteObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone())
+    }
+
+    async fn title(&self) -> &str {
+        &self.0.title
+    }
+
+    async fn body(&self) -> &str {
+        &self.0.body
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn updated_at(&self) -> DateTime<Utc> {
+        self.0.updated_at
+    }
+
+    /// Loaded through `UserLoader`, so a page of notes costs one user query
+    async fn author(&self, ctx: &Context<'_>) -> Result<Option<UserObject>> {
+        let loader = ctx.data_unchecked::<DataLoader<UserLoader>>();
+        let author = loader.load_one(self.0.user_id.clone()).await?;
+        Ok(author.map(UserObject::from))
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct NotePage {
+    notes: Vec<NoteObject>,
+    page: i64,
+    per_page: i64,
+    total: i64,
+    total_pages: i64,
+}
+
+#[derive(InputObject)]
+pub struct CreateNoteInput {
+    title: String,
+    #[graphql(default)]
+    body: String,
+}
+
+#[derive(InputObject)]
+pub struct UpdateNoteInput {
+    title: Option<String>,
+    body: Option<String>,
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// The signed-in user
+    async fn viewer(&self, ctx: &Context<'_>) -> Result<UserObject> {
+        let viewer = viewer(ctx, "profile:read")?;
+        let loader = ctx.data_unchecked::<DataLoader<UserLoader>>();
+        loader
+            .load_one(viewer.user.id.clone())
+            .await?
+            .map(UserObject::from)
+            .ok_or_else(|| Error::new("User not found"))
+    }
+
+    /// Public profile by username
+    async fn user(&self, ctx: &Context<'_>, username: String) -> Result<Option<UserObject>> {
+        let pool = ctx.data_unchecked::<SqlitePool>();
+        let user = User::find_by_username(pool, &username)
+            .await
+            .map_err(database_error)?;
+        Ok(user
+            .filter(|user| user.is_active && user.deleted_at.is_none())
+            .map(UserObject::from))
+    }
+
+    /// Public profiles by ID (at most 100), in the order given; unknown IDs
+    /// are skipped
+    async fn users(&self, ctx: &Context<'_>, ids: Vec<ID>) -> Result<Vec<UserObject>> {
+        if ids.len() > MAX_BATCH_USERS {
+            return Err(Error::new(format!(
+                "At most {} IDs can be requested at once",
+                MAX_BATCH_USERS
+            ))
+            .extend_with(|_, e| e.set("code", "BAD_USER_INPUT")));
+        }
+
+        let loader = ctx.data_unchecked::<DataLoader<UserLoader>>();
+        let ids: Vec<String> = ids.into_iter().map(|id| id.0).collect();
+        let mut users = loader.load_many(ids.iter().cloned()).await?;
+        Ok(ids
+            .iter()
+            .filter_map(|id| users.remove(id))
+            .filter(|user| user.is_active && user.deleted_at.is_none())
+            .map(UserObject::from)
+            .collect())
+    }
+
+    /// The signed-in user's notes, newest first
+    async fn notes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: i64,
+        #[graphql(default_with = "NoteSort::DEFAULT_PER_PAGE")] per_page: i64,
+    ) -> Result<NotePage> {
+        let viewer = viewer(ctx, "notes:read")?;
+        let pool = ctx.data_unchecked::<SqlitePool>();
+
+        let page = Paginated::new(
+            page,
+            per_page,
+            NoteSort::default(),
+            SortDirection::default(),
+        );
+        let (notes, total) = tokio::try_join!(
+            Note::list_for_user(pool, &viewer.user.id, None, &page),
+            Note::count_for_user(pool, &viewer.user.id, None)
+        )
+        .map_err(database_error)?;
+
+        Ok(NotePage {
+            notes: notes.into_iter().map(NoteObject).collect(),
+            page: page.page,
+            per_page: page.per_page,
+            total,
+            total_pages: (total + page.per_page - 1) / page.per_page,
+        })
+    }
+
+    async fn note(&self, ctx: &Context<'_>, id: ID) -> Result<Option<NoteObject>> {
+        let viewer = viewer(ctx, "notes:read")?;
+        let pool = ctx.data_unchecked::<SqlitePool>();
+        let note = Note::find_for_user(pool, &id, &viewer.user.id)
+            .await
+            .map_err(database_error)?;
+        Ok(note.map(NoteObject))
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    async fn create_note(&self, ctx: &Context<'_>, input: CreateNoteInput) -> Result<NoteObject> {
+        let viewer = viewer(ctx, "notes:write")?;
+        let pool = ctx.data_unchecked::<SqlitePool>();
+
+        let request = CreateNoteRequest {
+            title: input.title.trim().to_string(),
+            body: input.body,
+        };
+        request.validate().map_err(validation_error)?;
+
+        let note = Note::create(pool, &viewer.user.id, &request.title, &request.body)
+            .await
+            .map_err(database_error)?;
+        Ok(NoteObject(note))
+    }
+
+    /// Returns `null` if the note doesn't exist or isn't the caller's
+    async fn update_note(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        input: UpdateNoteInput,
+    ) -> Result<Option<NoteObject>> {
+        let viewer = viewer(ctx, "notes:write")?;
+        let pool = ctx.data_unchecked::<SqlitePool>();
+
+        let request = UpdateNoteRequest {
+            title: input.title.map(|title| title.trim().to_string()),
+            body: input.body,
+        };
+        request.validate().map_err(validation_error)?;
+
+        let note = Note::update(
+            pool,
+            &id,
+            &viewer.user.id,
+            request.title.as_deref(),
+            request.body.as_deref(),
+        )
+        .await
+        .map_err(database_error)?;
+        Ok(note.map(NoteObject))
+    }
+
+    /// Returns whether a note was deleted
+    async fn delete_note(&self, ctx: &Context<'_>, id: ID) -> Result<bool> {
+        let viewer = viewer(ctx, "notes:write")?;
+        let pool = ctx.data_unchecked::<SqlitePool>();
+        Note::delete(pool, &id, &viewer.user.id)
+            .await
+            .map_err(database_error)
+    }
+}
@@ -0,0 +1,188 @@
+use crate::api_auth::{ApiUser, BearerError};
+use crate::config::JwtConfig;
+use crate::models::User;
+use proto::get_user_request::Lookup;
+use proto::user_service_server::{UserService, UserServiceServer};
+use proto::{BatchGetUsersRequest, BatchGetUsersResponse, GetCurrentUserRequest, GetUserRequest};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::env;
+use std::net::SocketAddr;
+use tonic::{Request, Response, Status};
+
+/// Code generated from `proto/users.proto` by the build script
+pub mod proto {
+    tonic::include_proto!("users.v1");
+}
+
+// Keeps one call from loading an unbounded number of users
+const MAX_BATCH_GET_USERS: usize = 100;
+
+/// Where the gRPC server listens
+#[derive(Debug, Clone)]
+pub struct GrpcConfig {
+    pub addr: SocketAddr,
+}
+
+impl GrpcConfig {
+    /// Reads `GRPC_HOST` (defaults to `HOST`, then 127.0.0.1) and `GRPC_PORT`
+    /// (defaults to 50051)
+    pub fn from_env() -> anyhow::Result<Self> {
+        let host = env::var("GRPC_HOST")
+            .or_else(|_| env::var("HOST"))
+            .unwrap_or_else(|_| "127.0.0.1".to_string());
+        let port = env::var("GRPC_PORT").unwrap_or_else(|_| "50051".to_string());
+        let addr = format!("{}:{}", host, port)
+            .parse()
+            .map_err(|_| anyhow::anyhow!("GRPC_HOST and GRPC_PORT must form a valid address"))?;
+
+        Ok(Self { addr })
+    }
+}
+
+/// Runs the gRPC server in the background until the process exits
+pub fn spawn_grpc_server(
+    config: GrpcConfig,
+    pool: SqlitePool,
+    jwt: JwtConfig,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        tracing::info!("gRPC server starting on {}", config.addr);
+        let service = UserServiceServer::new(UserServiceImpl { pool, jwt });
+        if let Err(e) = tonic::transport::Server::builder()
+            .add_service(service)
+            .serve(config.addr)
+            .await
+        {
+            tracing::error!("gRPC server stopped: {}", e);
+        }
+    })
+}
+
+pub struct UserServiceImpl {
+    pool: SqlitePool,
+    jwt: JwtConfig,
+}
+
+impl UserServiceImpl {
+    // Helper function to check the bearer token in the request metadata
+    async fn authenticate<T>(&self, request: &Request<T>) -> Result<ApiUser, Status> {
+        let secret = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .ok_or_else(|| Status::unauthenticated("Authentication required"))?;
+
+        ApiUser::from_bearer(&self.pool, &self.jwt, secret)
+            .await
+            .map_err(|e| match e {
+                BearerError::Invalid => Status::unauthenticated("Invalid or expired token"),
+                BearerError::Database => Status::internal("Database error"),
+            })
+    }
+}
+
+fn database_error(e: sqlx::Error) -> Status {
+    tracing::error!("Database error in gRPC call: {}", e);
+    Status::internal("Database error")
+}
+
+fn is_visible(user: &User) -> bool {
+    user.is_active && user.deleted_at.is_none()
+}
+
+impl From<User> for proto::User {
+    fn from(user: User) -> Self {
+        let avatar_url = user.avatar_url();
+        Self {
+            id: user.id,
+            username: user.username,
+            display_name: user.display_name,
+            bio: user.bio,
+            avatar_url,
+            created_at: Some(prost_types::Timestamp {
+                seconds: user.created_at.timestamp(),
+                nanos: user.created_at.timestamp_subsec_nanos() as i32,
+            }),
+            email: None,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl UserService for UserServiceImpl {
+    async fn get_current_user(
+        &self,
+        request: Request<GetCurrentUserRequest>,
+    ) -> Result<Response<proto::User>, Status> {
+        let api_user = self.authenticate(&request).await?;
+        api_user.require_scope("profile:read").map_err(|missing| {
+            Status::permission_denied(format!("Token is missing the {} scope", missing.0))
+        })?;
+
+        match User::find_by_id(&self.pool, &api_user.user.id).await {
+            Ok(Some(user)) => {
+                let email = user.email.clone();
+                Ok(Response::new(proto::User {
+                    email: Some(email),
+                    ..user.into()
+                }))
+            }
+            Ok(None) => Err(Status::not_found("User not found")),
+            Err(e) => Err(database_error(e)),
+        }
+    }
+
+    async fn get_user(
+        &self,
+        request: Request<GetUserRequest>,
+    ) -> Result<Response<proto::User>, Status> {
+        self.authenticate(&request).await?;
+
+        let user = match request.into_inner().lookup {
+            Some(Lookup::Id(id)) => User::find_by_id(&self.pool, &id).await,
+            Some(Lookup::Username(username)) => User::find_by_username(&self.pool, &username).await,
+            None => return Err(Status::invalid_argument("Pass an id or a username")),
+        };
+
+        match user {
+            Ok(Some(user)) if is_visible(&user) => Ok(Response::new(user.into())),
+            Ok(_) => Err(Status::not_found("User not found")),
+            Err(e) => Err(database_error(e)),
+        }
+    }
+
+    async fn batch_get_users(
+        &self,
+        request: Request<BatchGetUsersRequest>,
+    ) -> Result<Response<BatchGetUsersResponse>, Status> {
+        self.authenticate(&request).await?;
+
+        let ids = request.into_inner().ids;
+        if ids.len() > MAX_BATCH_GET_USERS {
+            return Err(Status::invalid_argument(format!(
+                "At most {} IDs can be requested at once",
+                MAX_BATCH_GET_USERS
+            )));
+        }
+
+        let mut users: HashMap<String, User> = User::find_by_ids(&self.pool, &ids)
+            .await
+            .map_err(database_error)?
+            .into_iter()
+            .map(|user| (user.id.clone(), user))
+            .collect();
+
+        // In the order asked for
+        Ok(Response::new(BatchGetUsersResponse {
+            users: ids
+                .iter()
+                .filter_map(|id| users.remove(id))
+                .filter(is_visible)
+                .map(proto::User::from)
+                .collect(),
+        }))
+    }
+}
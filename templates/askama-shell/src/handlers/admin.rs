@@ -0,0 +1,772 @@
+use crate::audit::{AuditAction, AuditLogger};
+use crate::config::PasswordHashing;
+use crate::email::Mailer;
+use crate::handlers::auth::{FlashMessage, IMPERSONATOR_KEY};
+use crate::handlers::dashboard::get_or_create_csrf_token;
+use crate::models::{
+    AuditEventFilter, AuditEventRow, CreateInvitationRequest, Invitation, PolicyAcceptance,
+    PolicyAcceptanceRow, RefreshToken, Role, User, UserResponse, UserSession, UserSort,
+};
+use crate::pagination::{PageInfo, Paginated, SortDirection, SortKey};
+use crate::password::hash_password;
+use crate::rbac::{AdminRole, RequireRole, RoleName};
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use rand::{Rng, distributions::Alphanumeric};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use tower_sessions::Session;
+use validator::Validate;
+
+const AUDIT_EVENTS_PER_PAGE: i64 = 50;
+
+#[derive(Template)]
+#[template(path = "admin/users.html")]
+struct AdminUsersTemplate {
+    css: String,
+    js: String,
+    user: Option<UserResponse>,
+    flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+    users: Vec<User>,
+    query: String,
+    sort: &'static str,
+    ascending: bool,
+    pagination: PageInfo,
+}
+
+#[derive(Template)]
+#[template(path = "admin/user.html")]
+struct AdminUserTemplate {
+    css: String,
+    js: String,
+    user: Option<UserResponse>,
+    flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+    account: User,
+    roles: Vec<AdminRoleRow>,
+    policy_acceptances: Vec<PolicyAcceptanceRow>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/audit.html")]
+struct AdminAuditTemplate {
+    css: String,
+    js: String,
+    user: Option<UserResponse>,
+    flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+    events: Vec<AuditEventRow>,
+    actions: Vec<String>,
+    action: String,
+    user_filter: String,
+    since: String,
+    until: String,
+    page: i64,
+    total_pages: i64,
+    total_events: i64,
+}
+
+struct AdminRoleRow {
+    name: String,
+    description: String,
+    granted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminUsersQuery {
+    q: Option<String>,
+}
+
+// Filters accept empty strings so the browse form can be submitted as-is
+#[derive(Debug, Deserialize)]
+pub struct AuditEventsQuery {
+    action: Option<String>,
+    /// User id, email, or username matching either the actor or the target
+    user: Option<String>,
+    /// Inclusive start date, YYYY-MM-DD
+    since: Option<String>,
+    /// Inclusive end date, YYYY-MM-DD
+    until: Option<String>,
+    page: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateUserRoleRequest {
+    pub role: String,
+    pub granted: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserAction {
+    Activate,
+    Deactivate,
+    VerifyEmail,
+}
+
+impl UserAction {
+    fn audit_action(self) -> AuditAction {
+        match self {
+            UserAction::Activate => AuditAction::UserActivated,
+            UserAction::Deactivate => AuditAction::UserDeactivated,
+            UserAction::VerifyEmail => AuditAction::EmailVerified,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkUserActionRequest {
+    pub user_ids: Vec<String>,
+    pub action: UserAction,
+}
+
+// Helper function to get assets
+fn get_assets() -> (String, String) {
+    let css = include_str!(concat!(env!("OUT_DIR"), "/output.css"));
+    let js = include_str!(concat!(env!("OUT_DIR"), "/index.js"));
+    (css.to_string(), js.to_string())
+}
+
+// Helper function to apply an account action on behalf of an admin
+async fn apply_user_action(
+    pool: &SqlitePool,
+    user_id: &str,
+    action: UserAction,
+) -> Result<(), sqlx::Error> {
+    match action {
+        UserAction::Activate => User::activate(pool, user_id).await,
+        UserAction::Deactivate => {
+            User::deactivate(pool, user_id).await?;
+            RefreshToken::delete_for_user(pool, user_id).await?;
+            UserSession::delete_for_user(pool, user_id).await
+        }
+        UserAction::VerifyEmail => User::verify_email(pool, user_id).await,
+    }
+}
+
+// Helper function to load one page of audit events matching the query
+async fn load_audit_events(
+    pool: &SqlitePool,
+    query: &AuditEventsQuery,
+) -> Result<(Vec<AuditEventRow>, i64), Response> {
+    let non_empty = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let parse_date = |value: &Option<String>| {
+        non_empty(value)
+            .and_then(|value| chrono::NaiveDate::parse_from_str(&value, "%Y-%m-%d").ok())
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|datetime| datetime.and_utc())
+    };
+
+    // Resolve emails and usernames, falling back to treating the value as a user id
+    let user_id = match non_empty(&query.user) {
+        Some(user) => match User::find_by_email_or_username(pool, &user).await {
+            Ok(Some(found)) => Some(found.id),
+            Ok(None) => Some(user),
+            Err(e) => {
+                tracing::error!("Database error resolving audit user filter: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        },
+        None => None,
+    };
+
+    let filter = AuditEventFilter {
+        action: non_empty(&query.action),
+        user_id,
+        since: parse_date(&query.since),
+        until: parse_date(&query.until).map(|until| until + chrono::Duration::days(1)),
+    };
+    let page = query.page.unwrap_or(1).max(1);
+
+    match tokio::try_join!(
+        AuditEventRow::search(
+            pool,
+            &filter,
+            AUDIT_EVENTS_PER_PAGE,
+            (page - 1) * AUDIT_EVENTS_PER_PAGE
+        ),
+        AuditEventRow::count(pool, &filter)
+    ) {
+        Ok(result) => Ok(result),
+        Err(e) => {
+            tracing::error!("Database error loading audit events: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn list_invitations(
+    _admin: RequireRole<AdminRole>,
+    State(pool): State<SqlitePool>,
+) -> Result<Json<serde_json::Value>, Response> {
+    match Invitation::find_all(&pool).await {
+        Ok(invitations) => Ok(Json(json!({
+            "success": true,
+            "invitations": invitations
+        }))),
+        Err(e) => {
+            tracing::error!("Database error loading invitations: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn handle_create_invitation(
+    admin: RequireRole<AdminRole>,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    State(mailer): State<Mailer>,
+    Json(invitation_request): Json<CreateInvitationRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    // Validate the request
+    if let Err(validation_errors) = invitation_request.validate() {
+        let mut errors = HashMap::new();
+        for (field, field_errors) in validation_errors.field_errors() {
+            let error_message = field_errors[0]
+                .message
+                .as_ref()
+                .map(|m| m.as_ref())
+                .unwrap_or("Invalid input");
+            errors.insert(field, error_message);
+        }
+        return Ok(Json(json!({
+            "success": false,
+            "errors": errors
+        })));
+    }
+
+    match Invitation::create(
+        &pool,
+        Some(&admin.user.id),
+        invitation_request.max_uses,
+        invitation_request
+            .expires_in_days
+            .map(chrono::Duration::days),
+    )
+    .await
+    {
+        Ok(invitation) => {
+            audit
+                .record(
+                    AuditAction::InvitationCreated,
+                    Some(&admin.user.id),
+                    None,
+                    json!({ "invitation_id": invitation.id, "max_uses": invitation.max_uses }),
+                )
+                .await;
+
+            let invite_url = format!("{}/signup?invite={}", mailer.base_url(), invitation.code);
+            Ok(Json(json!({
+                "success": true,
+                "invitation": invitation,
+                "invite_url": invite_url
+            })))
+        }
+        Err(e) => {
+            tracing::error!("Database error creating invitation: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn show_admin_users(
+    admin: RequireRole<AdminRole>,
+    session: Session,
+    State(pool): State<SqlitePool>,
+    page: Paginated<UserSort>,
+    Query(query): Query<AdminUsersQuery>,
+) -> Result<Html<String>, Response> {
+    let (css, js) = get_assets();
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+
+    let search = query.q.unwrap_or_default().trim().to_string();
+
+    let total_users = match User::count_search(&pool, &search).await {
+        Ok(count) => count,
+        Err(e) => {
+            tracing::error!("Database error counting users: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let users = match User::search(&pool, &search, &page).await {
+        Ok(users) => users,
+        Err(e) => {
+            tracing::error!("Database error searching users: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+    let pagination = page.page_info(total_users, &users, |user| &user.id);
+
+    let template = AdminUsersTemplate {
+        css,
+        js,
+        user: Some(admin.user),
+        flash_messages: Vec::new(),
+        csrf_token,
+        users,
+        query: search,
+        sort: page.sort.column(),
+        ascending: page.dir == SortDirection::Asc,
+        pagination,
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Template error").into_response())
+        }
+    }
+}
+
+pub async fn show_admin_user(
+    admin: RequireRole<AdminRole>,
+    session: Session,
+    State(pool): State<SqlitePool>,
+    Path(user_id): Path<String>,
+) -> Result<Html<String>, Response> {
+    let (css, js) = get_assets();
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+
+    let account = match User::find_by_id(&pool, &user_id).await {
+        Ok(Some(account)) if account.deleted_at.is_none() => account,
+        Ok(_) => return Err((StatusCode::NOT_FOUND, "User not found").into_response()),
+        Err(e) => {
+            tracing::error!("Database error loading user: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let roles = match tokio::try_join!(
+        Role::find_all(&pool),
+        Role::find_for_user(&pool, &account.id)
+    ) {
+        Ok((roles, account_roles)) => roles
+            .into_iter()
+            .map(|role| AdminRoleRow {
+                granted: account_roles
+                    .iter()
+                    .any(|granted| granted.name == role.name),
+                name: role.name,
+                description: role.description,
+            })
+            .collect(),
+        Err(e) => {
+            tracing::error!("Database error loading roles: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let policy_acceptances = match PolicyAcceptance::find_for_user(&pool, &account.id).await {
+        Ok(policy_acceptances) => policy_acceptances,
+        Err(e) => {
+            tracing::error!("Database error loading policy acceptances: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let template = AdminUserTemplate {
+        css,
+        js,
+        user: Some(admin.user),
+        flash_messages: Vec::new(),
+        csrf_token,
+        account,
+        roles,
+        policy_acceptances,
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Template error").into_response())
+        }
+    }
+}
+
+pub async fn handle_admin_user_action(
+    admin: RequireRole<AdminRole>,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    Path((user_id, action)): Path<(String, UserAction)>,
+) -> Result<Json<serde_json::Value>, Response> {
+    if matches!(action, UserAction::Deactivate) && user_id == admin.user.id {
+        return Ok(Json(json!({
+            "success": false,
+            "message": "You can't deactivate your own account"
+        })));
+    }
+
+    match apply_user_action(&pool, &user_id, action).await {
+        Ok(()) => {
+            audit
+                .record(
+                    action.audit_action(),
+                    Some(&admin.user.id),
+                    Some(&user_id),
+                    json!({}),
+                )
+                .await;
+
+            Ok(Json(json!({
+                "success": true,
+                "message": "User updated"
+            })))
+        }
+        Err(e) => {
+            tracing::error!("Database error updating user {}: {}", user_id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn handle_admin_bulk_action(
+    admin: RequireRole<AdminRole>,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    Json(bulk_request): Json<BulkUserActionRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let mut updated = 0;
+    for user_id in &bulk_request.user_ids {
+        // Admins can't lock themselves out through a bulk action
+        if matches!(bulk_request.action, UserAction::Deactivate) && *user_id == admin.user.id {
+            continue;
+        }
+
+        if let Err(e) = apply_user_action(&pool, user_id, bulk_request.action).await {
+            tracing::error!("Database error updating user {}: {}", user_id, e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+        audit
+            .record(
+                bulk_request.action.audit_action(),
+                Some(&admin.user.id),
+                Some(user_id),
+                json!({ "bulk": true }),
+            )
+            .await;
+        updated += 1;
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "message": format!("Updated {} user(s)", updated)
+    })))
+}
+
+pub async fn handle_admin_reset_password(
+    admin: RequireRole<AdminRole>,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    State(password_hashing): State<PasswordHashing>,
+    Path(user_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Response> {
+    match User::find_by_id(&pool, &user_id).await {
+        Ok(Some(account)) if account.deleted_at.is_none() => {}
+        Ok(_) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "success": false,
+                    "message": "User not found"
+                })),
+            )
+                .into_response());
+        }
+        Err(e) => {
+            tracing::error!("Database error loading user: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    }
+
+    // The admin passes the temporary password on to the user out of band
+    let temporary_password: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect();
+
+    let password_hash = match hash_password(&temporary_password, &password_hashing) {
+        Ok(hash) => hash,
+        Err(e) => {
+            tracing::error!("Password hashing error: {}", e);
+            return Err(
+                (StatusCode::INTERNAL_SERVER_ERROR, "Password hashing error").into_response(),
+            );
+        }
+    };
+
+    if let Err(e) = User::update_password_hash(&pool, &user_id, &password_hash).await {
+        tracing::error!("Database error resetting password: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+    }
+
+    // Sign the user out everywhere so the old password can't keep a session alive
+    if let Err(e) = UserSession::delete_for_user(&pool, &user_id).await {
+        tracing::warn!("Failed to revoke sessions for user {}: {}", user_id, e);
+    }
+    if let Err(e) = RefreshToken::delete_for_user(&pool, &user_id).await {
+        tracing::warn!(
+            "Failed to revoke refresh tokens for user {}: {}",
+            user_id,
+            e
+        );
+    }
+
+    audit
+        .record(
+            AuditAction::PasswordReset,
+            Some(&admin.user.id),
+            Some(&user_id),
+            json!({}),
+        )
+        .await;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Password reset",
+        "temporary_password": temporary_password
+    })))
+}
+
+pub async fn handle_admin_update_role(
+    admin: RequireRole<AdminRole>,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    Path(user_id): Path<String>,
+    Json(role_request): Json<UpdateUserRoleRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    if !role_request.granted && role_request.role == AdminRole::NAME && user_id == admin.user.id {
+        return Ok(Json(json!({
+            "success": false,
+            "message": "You can't remove your own admin role"
+        })));
+    }
+
+    match Role::find_all(&pool).await {
+        Ok(roles) if roles.iter().any(|role| role.name == role_request.role) => {}
+        Ok(_) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "Unknown role"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Database error loading roles: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    }
+
+    let result = if role_request.granted {
+        Role::grant(&pool, &user_id, &role_request.role).await
+    } else {
+        Role::revoke(&pool, &user_id, &role_request.role).await
+    };
+
+    match result {
+        Ok(()) => {
+            let action = if role_request.granted {
+                AuditAction::RoleGranted
+            } else {
+                AuditAction::RoleRevoked
+            };
+            audit
+                .record(
+                    action,
+                    Some(&admin.user.id),
+                    Some(&user_id),
+                    json!({ "role": role_request.role }),
+                )
+                .await;
+
+            Ok(Json(json!({
+                "success": true,
+                "message": "Roles updated"
+            })))
+        }
+        Err(e) => {
+            tracing::error!("Database error updating roles: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn handle_start_impersonation(
+    admin: RequireRole<AdminRole>,
+    session: Session,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    Path(user_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Response> {
+    if user_id == admin.user.id {
+        return Ok(Json(json!({
+            "success": false,
+            "message": "You can't impersonate yourself"
+        })));
+    }
+
+    let target = match User::find_by_id(&pool, &user_id).await {
+        Ok(Some(user)) if user.is_active && user.deleted_at.is_none() => user,
+        Ok(_) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "Only active accounts can be impersonated"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Database error loading user {}: {}", user_id, e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    // Impersonating another admin would hand over their privileges
+    match Role::user_has_role(&pool, &target.id, AdminRole::NAME).await {
+        Ok(false) => {}
+        Ok(true) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "Admins can't be impersonated"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Database error checking roles: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    }
+
+    // Switching identity gets a fresh session ID, like signing in does
+    if session.cycle_id().await.is_err()
+        || session
+            .insert(IMPERSONATOR_KEY, &admin.user.id)
+            .await
+            .is_err()
+        || session.insert("user_id", &target.id).await.is_err()
+    {
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
+    }
+
+    audit
+        .record(
+            AuditAction::ImpersonationStarted,
+            Some(&admin.user.id),
+            Some(&target.id),
+            json!({ "username": target.username }),
+        )
+        .await;
+
+    tracing::info!(
+        "Admin {} started impersonating user {}",
+        admin.user.id,
+        target.id
+    );
+
+    Ok(Json(json!({
+        "success": true,
+        "message": format!("Signed in as {}", target.username),
+        "redirect": "/dashboard"
+    })))
+}
+
+pub async fn handle_stop_impersonation(
+    session: Session,
+    audit: AuditLogger,
+) -> Result<Redirect, Response> {
+    let Ok(Some(admin_id)) = session.get::<String>(IMPERSONATOR_KEY).await else {
+        return Ok(Redirect::to("/dashboard"));
+    };
+    let impersonated_id = session.get::<String>("user_id").await.ok().flatten();
+
+    if session.cycle_id().await.is_err()
+        || session.insert("user_id", &admin_id).await.is_err()
+        || session.remove::<String>(IMPERSONATOR_KEY).await.is_err()
+    {
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
+    }
+
+    audit
+        .record(
+            AuditAction::ImpersonationStopped,
+            Some(&admin_id),
+            impersonated_id.as_deref(),
+            json!({}),
+        )
+        .await;
+
+    tracing::info!("Admin {} stopped impersonating", admin_id);
+
+    match impersonated_id {
+        Some(user_id) => Ok(Redirect::to(&format!("/admin/users/{}", user_id))),
+        None => Ok(Redirect::to("/admin")),
+    }
+}
+
+pub async fn show_admin_audit(
+    admin: RequireRole<AdminRole>,
+    session: Session,
+    State(pool): State<SqlitePool>,
+    Query(query): Query<AuditEventsQuery>,
+) -> Result<Html<String>, Response> {
+    let (css, js) = get_assets();
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+    let (events, total_events) = load_audit_events(&pool, &query).await?;
+
+    let template = AdminAuditTemplate {
+        css,
+        js,
+        user: Some(admin.user),
+        flash_messages: Vec::new(),
+        csrf_token,
+        events,
+        actions: AuditAction::ALL
+            .iter()
+            .map(|action| action.as_str().to_string())
+            .collect(),
+        action: query.action.unwrap_or_default(),
+        user_filter: query.user.unwrap_or_default(),
+        since: query.since.unwrap_or_default(),
+        until: query.until.unwrap_or_default(),
+        page: query.page.unwrap_or(1).max(1),
+        total_pages: ((total_events + AUDIT_EVENTS_PER_PAGE - 1) / AUDIT_EVENTS_PER_PAGE).max(1),
+        total_events,
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Template error").into_response())
+        }
+    }
+}
+
+pub async fn list_audit_events(
+    _admin: RequireRole<AdminRole>,
+    State(pool): State<SqlitePool>,
+    Query(query): Query<AuditEventsQuery>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let (events, total) = load_audit_events(&pool, &query).await?;
+
+    Ok(Json(json!({
+        "success": true,
+        "events": events,
+        "total": total,
+        "page": query.page.unwrap_or(1).max(1),
+        "per_page": AUDIT_EVENTS_PER_PAGE
+    })))
+}
@@ -0,0 +1,169 @@
+use crate::api_auth::ApiUser;
+use crate::audit::{AuditAction, AuditLogger};
+use crate::config::{JwtConfig, LockoutPolicy, PasswordHashing};
+use crate::handlers::auth::{CredentialCheck, check_credentials};
+use crate::jwt::issue_access_token;
+use crate::models::{RefreshToken, User};
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "grant_type", rename_all = "snake_case")]
+pub enum TokenRequest {
+    Password {
+        identifier: String,
+        password: String,
+    },
+    RefreshToken {
+        refresh_token: String,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeTokenRequest {
+    pub refresh_token: String,
+}
+
+// Helper function to build the 401 returned when a grant is rejected
+fn invalid_grant(message: &str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(json!({
+            "success": false,
+            "message": message
+        })),
+    )
+        .into_response()
+}
+
+// Helper function to issue a fresh access and refresh token pair
+async fn issue_token_pair(
+    pool: &SqlitePool,
+    jwt: &JwtConfig,
+    user_id: &str,
+) -> Result<Json<serde_json::Value>, Response> {
+    let access_token = match issue_access_token(jwt, user_id) {
+        Ok(access_token) => access_token,
+        Err(e) => {
+            tracing::error!("Failed to sign access token: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Token signing error").into_response());
+        }
+    };
+
+    let (_, refresh_token) = match RefreshToken::create(pool, user_id, jwt.refresh_ttl).await {
+        Ok(refresh_token) => refresh_token,
+        Err(e) => {
+            tracing::error!("Database error creating refresh token: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    Ok(Json(json!({
+        "success": true,
+        "token_type": "Bearer",
+        "access_token": access_token,
+        "expires_in": jwt.access_ttl.num_seconds(),
+        "refresh_token": refresh_token
+    })))
+}
+
+pub async fn api_current_user(api_user: ApiUser) -> Result<Json<serde_json::Value>, Response> {
+    api_user.require_scope("profile:read")?;
+
+    Ok(Json(json!({
+        "success": true,
+        "user": api_user.user
+    })))
+}
+
+pub async fn handle_issue_token(
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    State(jwt): State<JwtConfig>,
+    State(lockout_policy): State<LockoutPolicy>,
+    State(password_hashing): State<PasswordHashing>,
+    Json(token_request): Json<TokenRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    match token_request {
+        TokenRequest::Password {
+            identifier,
+            password,
+        } => {
+            let user = match check_credentials(
+                &pool,
+                &audit,
+                &lockout_policy,
+                &password_hashing,
+                &identifier,
+                &password,
+            )
+            .await?
+            {
+                CredentialCheck::Valid(user) => *user,
+                CredentialCheck::Invalid(message) => return Err(invalid_grant(&message)),
+            };
+
+            audit
+                .record(
+                    AuditAction::LoginSucceeded,
+                    Some(&user.id),
+                    Some(&user.id),
+                    json!({ "method": "jwt" }),
+                )
+                .await;
+
+            if let Err(e) = User::update_last_login(&pool, &user.id).await {
+                tracing::warn!("Failed to update last login for user {}: {}", user.id, e);
+            }
+
+            issue_token_pair(&pool, &jwt, &user.id).await
+        }
+        TokenRequest::RefreshToken { refresh_token } => {
+            // Refresh tokens are single use, each refresh hands out a new one
+            let consumed = match RefreshToken::consume(&pool, &refresh_token).await {
+                Ok(Some(consumed)) => consumed,
+                Ok(None) => return Err(invalid_grant("Invalid or expired refresh token")),
+                Err(e) => {
+                    tracing::error!("Database error consuming refresh token: {}", e);
+                    return Err(
+                        (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+                    );
+                }
+            };
+
+            match User::find_by_id(&pool, &consumed.user_id).await {
+                Ok(Some(user)) if user.is_active && user.deleted_at.is_none() => {
+                    issue_token_pair(&pool, &jwt, &user.id).await
+                }
+                Ok(_) => Err(invalid_grant("Invalid or expired refresh token")),
+                Err(e) => {
+                    tracing::error!("Database error loading user: {}", e);
+                    Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+                }
+            }
+        }
+    }
+}
+
+pub async fn handle_revoke_token(
+    State(pool): State<SqlitePool>,
+    Json(revoke_request): Json<RevokeTokenRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    // Unknown tokens are treated as already revoked
+    if let Err(e) = RefreshToken::consume(&pool, &revoke_request.refresh_token).await {
+        tracing::error!("Database error revoking refresh token: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Token revoked"
+    })))
+}
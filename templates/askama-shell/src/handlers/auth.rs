@@ -1,20 +1,42 @@
-use crate::models::{CreateUserRequest, LoginRequest, User, UserResponse};
-use argon2::password_hash::{SaltString, rand_core::OsRng};
-use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use crate::audit::{AuditAction, AuditLogger};
+use crate::config::{LockoutPolicy, PasswordHashing, SessionCookie, SignupMode};
+use crate::email::{Mailer, send_new_sign_in_email, send_verification_email};
+use crate::handlers::dashboard::{CSRF_TOKEN_KEY, get_or_create_csrf_token};
+use crate::live::{Hub, LiveEvent};
+use crate::models::{
+    CreateUserRequest, FailedLogin, Invitation, KnownDevice, LoginRequest, PolicyAcceptance,
+    PolicyDocument, Role, User, UserResponse, UserSession,
+};
+use crate::negotiate::Negotiate;
+use crate::password::{estimate_strength, hash_password, needs_rehash, verify_password};
+use crate::rbac::{RoleName, UserRole};
+use crate::state::AppState;
+use crate::webhooks::{WebhookEvent, Webhooks};
 use askama::Template;
 use axum::{
-    Json,
-    extract::{Query, State},
-    http::StatusCode,
+    Json, async_trait,
+    extract::{ConnectInfo, FromRef, FromRequestParts, Query, State},
+    http::{StatusCode, header, request::Parts},
     response::{Html, IntoResponse, Redirect, Response},
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::SqlitePool;
 use std::collections::HashMap;
-use tower_sessions::Session;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use tower_sessions::cookie::time::Duration;
+use tower_sessions::{Expiry, Session};
 use validator::Validate;
 
+const LAST_SEEN_KEY: &str = "last_seen";
+// Seconds a non-remembered session may sit idle, fixed when it starts
+const IDLE_TIMEOUT_KEY: &str = "idle_timeout";
+// Links the cookie session to its row in user_sessions
+pub const USER_SESSION_KEY: &str = "user_session_id";
+// Holds the admin's own user id while they are signed in as someone else
+pub const IMPERSONATOR_KEY: &str = "impersonator_id";
+
 #[derive(Template)]
 #[template(path = "login.html")]
 struct LoginTemplate {
@@ -22,6 +44,7 @@ struct LoginTemplate {
     js: String,
     user: Option<UserResponse>,
     flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
 }
 
 #[derive(Template)]
@@ -31,6 +54,9 @@ struct SignupTemplate {
     js: String,
     user: Option<UserResponse>,
     flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+    invite_only: bool,
+    invite_code: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -39,11 +65,47 @@ pub struct FlashMessage {
     pub content: String,
 }
 
+const FLASH_MESSAGES_KEY: &str = "flash_messages";
+
+/// Queues a message for the next page the session views
+pub async fn push_flash_message(
+    session: &Session,
+    level: &str,
+    content: &str,
+) -> Result<(), tower_sessions::session::Error> {
+    let mut messages: Vec<FlashMessage> =
+        session.get(FLASH_MESSAGES_KEY).await?.unwrap_or_default();
+    messages.push(FlashMessage {
+        level: level.to_string(),
+        content: content.to_string(),
+    });
+    session.insert(FLASH_MESSAGES_KEY, messages).await
+}
+
+/// Takes the messages queued with `push_flash_message`, so each shows once
+pub async fn take_flash_messages(session: &Session) -> Vec<FlashMessage> {
+    match session
+        .remove::<Vec<FlashMessage>>(FLASH_MESSAGES_KEY)
+        .await
+    {
+        Ok(messages) => messages.unwrap_or_default(),
+        Err(e) => {
+            tracing::warn!("Failed to load flash messages: {}", e);
+            Vec::new()
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LoginQuery {
     message: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SignupQuery {
+    invite: Option<String>,
+}
+
 // Helper function to get assets
 fn get_assets() -> (String, String) {
     let css = include_str!(concat!(env!("OUT_DIR"), "/output.css"));
@@ -51,31 +113,286 @@ fn get_assets() -> (String, String) {
     (css.to_string(), js.to_string())
 }
 
+// The device a request came from, used to track sessions and known devices
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: String,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        let ip_address = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        Ok(Self {
+            user_agent,
+            ip_address,
+        })
+    }
+}
+
+// Helper function to email the user when they sign in from a device we haven't seen before
+async fn notify_if_new_device(
+    pool: &SqlitePool,
+    mailer: &Mailer,
+    user: &User,
+    client: &ClientInfo,
+) {
+    let user_agent = client.user_agent.as_deref().unwrap_or_default();
+    let ip_address = client.ip_address.as_str();
+
+    // The very first device an account signs in from isn't worth reporting
+    let has_known_devices = match KnownDevice::count_for_user(pool, &user.id).await {
+        Ok(count) => count > 0,
+        Err(e) => {
+            tracing::warn!("Failed to load known devices for user {}: {}", user.id, e);
+            return;
+        }
+    };
+
+    let is_new_device = match KnownDevice::remember(pool, &user.id, user_agent, ip_address).await {
+        Ok(is_new_device) => is_new_device,
+        Err(e) => {
+            tracing::warn!("Failed to record device for user {}: {}", user.id, e);
+            return;
+        }
+    };
+
+    if !is_new_device || !has_known_devices || !user.notify_new_sign_in {
+        return;
+    }
+
+    // Send the notification without holding up the login
+    let mailer = mailer.clone();
+    let user = user.clone();
+    let user_agent = user_agent.to_string();
+    let ip_address = ip_address.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = send_new_sign_in_email(&mailer, &user, &user_agent, &ip_address).await {
+            tracing::error!(
+                "Failed to send new sign-in email to user {}: {}",
+                user.id,
+                e
+            );
+        }
+    });
+}
+
+/// What a successful sign-in needs beyond the user: the configured session
+/// lifetimes, and who to tell about it (the new-device email to the account
+/// owner and a live event for admins' dashboards)
+#[derive(Clone)]
+pub struct SignInNotifier {
+    pub mailer: Mailer,
+    pub hub: Hub,
+    pub session_cookie: SessionCookie,
+}
+
+impl FromRef<AppState> for SignInNotifier {
+    fn from_ref(state: &AppState) -> Self {
+        Self {
+            mailer: state.mailer.clone(),
+            hub: state.hub.clone(),
+            session_cookie: state.session_cookie.clone(),
+        }
+    }
+}
+
+impl SignInNotifier {
+    pub async fn notify(&self, pool: &SqlitePool, user: &User, client: &ClientInfo) {
+        self.hub.publish_to_admins(LiveEvent::UserLoggedIn {
+            username: user.username.clone(),
+            display_name: user.display_name.clone(),
+            at: chrono::Utc::now(),
+        });
+
+        notify_if_new_device(pool, &self.mailer, user, client).await;
+    }
+}
+
+/// Everything told about a new account: the verification email to its owner
+/// and a `user.created` webhook
+#[derive(Clone)]
+pub struct SignUpNotifier {
+    pub mailer: Mailer,
+    pub webhooks: Webhooks,
+}
+
+impl FromRef<AppState> for SignUpNotifier {
+    fn from_ref(state: &AppState) -> Self {
+        Self {
+            mailer: state.mailer.clone(),
+            webhooks: state.webhooks.clone(),
+        }
+    }
+}
+
+impl SignUpNotifier {
+    pub async fn notify(&self, pool: &SqlitePool, user: &User) {
+        // Send the verification email without holding up the response
+        let pool = pool.clone();
+        let mailer = self.mailer.clone();
+        let verification_user = user.clone();
+        tokio::spawn(async move {
+            if let Err(e) = send_verification_email(&pool, &mailer, &verification_user).await {
+                tracing::error!(
+                    "Failed to send verification email to user {}: {}",
+                    verification_user.id,
+                    e
+                );
+            }
+        });
+
+        self.webhooks
+            .emit(
+                WebhookEvent::UserCreated,
+                json!({
+                    "id": user.id,
+                    "username": user.username,
+                    "email": user.email,
+                    "created_at": user.created_at
+                }),
+            )
+            .await;
+    }
+}
+
+// Helper function to log a user into the session with the requested lifetime
+pub async fn start_user_session(
+    session: &Session,
+    cookie: &SessionCookie,
+    user_id: &str,
+    user_session_id: &str,
+    remember: bool,
+) -> Result<(), tower_sessions::session::Error> {
+    // A new session ID and CSRF token on every sign-in, so any value an
+    // attacker planted before login is useless afterwards
+    session.cycle_id().await?;
+    session.remove_value(CSRF_TOKEN_KEY).await?;
+
+    if remember {
+        session.set_expiry(Some(Expiry::OnInactivity(Duration::seconds(
+            cookie.remembered_timeout.num_seconds(),
+        ))));
+        session.remove_value(LAST_SEEN_KEY).await?;
+        session.remove_value(IDLE_TIMEOUT_KEY).await?;
+    } else {
+        session.set_expiry(Some(Expiry::OnSessionEnd));
+        session
+            .insert(LAST_SEEN_KEY, chrono::Utc::now().timestamp())
+            .await?;
+        session
+            .insert(
+                IDLE_TIMEOUT_KEY,
+                cookie.short_inactivity_timeout.num_seconds(),
+            )
+            .await?;
+    }
+
+    session.insert(USER_SESSION_KEY, user_session_id).await?;
+    session.insert("user_id", user_id).await
+}
+
+// Helper function to enforce the inactivity window of non-remembered sessions
+async fn session_is_fresh(session: &Session) -> bool {
+    match session.get::<i64>(LAST_SEEN_KEY).await {
+        Ok(Some(last_seen)) => {
+            let now = chrono::Utc::now().timestamp();
+            let idle_timeout = session
+                .get::<i64>(IDLE_TIMEOUT_KEY)
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or(SessionCookie::DEFAULT_SHORT_INACTIVITY_MINUTES * 60);
+            if now - last_seen > idle_timeout {
+                let _ = session.flush().await;
+                return false;
+            }
+            let _ = session.insert(LAST_SEEN_KEY, now).await;
+            true
+        }
+        // Remembered sessions rely on the cookie expiry alone
+        Ok(None) => true,
+        Err(_) => false,
+    }
+}
+
+/// How long the signed-in session may go without requests before it expires,
+/// for checks made away from the request, such as on open live connections
+pub async fn session_idle_limit(session: &Session, cookie: &SessionCookie) -> chrono::Duration {
+    match session.get::<i64>(LAST_SEEN_KEY).await {
+        // Remembered sessions rely on the cookie expiry alone
+        Ok(None) => cookie.remembered_timeout,
+        _ => chrono::Duration::seconds(
+            session
+                .get::<i64>(IDLE_TIMEOUT_KEY)
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or(SessionCookie::DEFAULT_SHORT_INACTIVITY_MINUTES * 60),
+        ),
+    }
+}
+
 // Helper function to get user from session
 pub async fn get_user_from_session(session: &Session, pool: &SqlitePool) -> Option<UserResponse> {
     if let Ok(Some(user_id)) = session.get::<String>("user_id").await {
+        if !session_is_fresh(session).await {
+            return None;
+        }
+        // Sessions revoked from the settings page no longer have a user_sessions row
+        let user_session_id = session.get::<String>(USER_SESSION_KEY).await.ok()??;
+        // While impersonating, the user_sessions row still belongs to the admin
+        let impersonator_id = session.get::<String>(IMPERSONATOR_KEY).await.ok().flatten();
+        let session_owner = impersonator_id.as_deref().unwrap_or(&user_id);
+        match UserSession::find_by_id(pool, &user_session_id).await {
+            Ok(Some(user_session)) if user_session.user_id == session_owner => {
+                let _ = UserSession::touch(pool, &user_session.id).await;
+            }
+            Ok(_) => {
+                let _ = session.flush().await;
+                return None;
+            }
+            Err(_) => return None,
+        }
+        // Deactivating or deleting an account invalidates every session that belongs to it
         if let Ok(Some(user)) = User::find_by_id(pool, &user_id).await {
-            return Some(user.into());
+            if user.is_active && user.deleted_at.is_none() {
+                let mut user_response: UserResponse = user.into();
+                if let Some(impersonator_id) = impersonator_id {
+                    user_response.impersonated_by = User::find_by_id(pool, &impersonator_id)
+                        .await
+                        .ok()
+                        .flatten()
+                        .map(|admin| admin.username);
+                }
+                return Some(user_response);
+            }
         }
     }
     None
 }
 
-// Helper function to hash password
-fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2.hash_password(password.as_bytes(), &salt)?;
-    Ok(password_hash.to_string())
-}
-
-// Helper function to verify password
-fn verify_password(password: &str, hash: &str) -> Result<bool, argon2::password_hash::Error> {
-    let parsed_hash = PasswordHash::new(hash)?;
-    let argon2 = Argon2::default();
-    Ok(argon2
-        .verify_password(password.as_bytes(), &parsed_hash)
-        .is_ok())
+// Helper function to describe how long a locked account stays locked
+fn lockout_message(locked_until: chrono::DateTime<chrono::Utc>) -> String {
+    let remaining_seconds = (locked_until - chrono::Utc::now()).num_seconds().max(0);
+    let minutes = (remaining_seconds + 59) / 60;
+    format!(
+        "Too many failed login attempts. Your account is locked, try again in {} minute{}.",
+        minutes.max(1),
+        if minutes > 1 { "s" } else { "" }
+    )
 }
 
 pub async fn show_login(
@@ -91,7 +408,7 @@ pub async fn show_login(
         return Err(Redirect::to("/dashboard").into_response());
     }
 
-    let mut flash_messages = Vec::new();
+    let mut flash_messages = take_flash_messages(&session).await;
     if let Some(message) = query.message {
         flash_messages.push(FlashMessage {
             level: "info".to_string(),
@@ -99,11 +416,14 @@ pub async fn show_login(
         });
     }
 
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+
     let template = LoginTemplate {
         css,
         js,
         user,
         flash_messages,
+        csrf_token,
     };
 
     match template.render() {
@@ -115,10 +435,192 @@ pub async fn show_login(
     }
 }
 
+// Result of checking a username/password pair
+pub enum CredentialCheck {
+    Valid(Box<User>),
+    /// The message to show the user
+    Invalid(String),
+}
+
+// Helper function to verify a password login, enforcing deactivation and
+// lockout, recording failures, and upgrading outdated password hashes
+pub async fn check_credentials(
+    pool: &SqlitePool,
+    audit: &AuditLogger,
+    lockout_policy: &LockoutPolicy,
+    password_hashing: &PasswordHashing,
+    identifier: &str,
+    password: &str,
+) -> Result<CredentialCheck, Response> {
+    let invalid = || CredentialCheck::Invalid("Invalid email/username or password".to_string());
+
+    // Find user by email or username
+    let user = match User::find_by_email_or_username(pool, identifier.trim()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            audit
+                .record(
+                    AuditAction::LoginFailed,
+                    None,
+                    None,
+                    json!({ "identifier": identifier.trim(), "reason": "unknown_user" }),
+                )
+                .await;
+            return Ok(invalid());
+        }
+        Err(e) => {
+            tracing::error!("Database error during login: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    // Check if user is active
+    if !user.is_active {
+        audit
+            .record(
+                AuditAction::LoginFailed,
+                None,
+                Some(&user.id),
+                json!({ "reason": "deactivated" }),
+            )
+            .await;
+        return Ok(CredentialCheck::Invalid(
+            "Account is deactivated".to_string(),
+        ));
+    }
+
+    // Check if user is locked out after too many failed attempts
+    match FailedLogin::locked_until(
+        pool,
+        &user.id,
+        lockout_policy.max_attempts,
+        lockout_policy.window,
+    )
+    .await
+    {
+        Ok(Some(locked_until)) => {
+            audit
+                .record(
+                    AuditAction::LoginFailed,
+                    None,
+                    Some(&user.id),
+                    json!({ "reason": "locked" }),
+                )
+                .await;
+            return Ok(CredentialCheck::Invalid(lockout_message(locked_until)));
+        }
+        Ok(None) => {}
+        Err(e) => {
+            tracing::error!("Database error checking lockout: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    }
+
+    // Verify password
+    match verify_password(password, &user.password_hash) {
+        Ok(true) => {
+            if let Err(e) = FailedLogin::clear_for_user(pool, &user.id).await {
+                tracing::warn!("Failed to clear failed logins for user {}: {}", user.id, e);
+            }
+
+            // Upgrade hashes created with weaker parameters while we have the plaintext
+            if needs_rehash(&user.password_hash, password_hashing) {
+                match hash_password(password, password_hashing) {
+                    Ok(password_hash) => {
+                        if let Err(e) =
+                            User::update_password_hash(pool, &user.id, &password_hash).await
+                        {
+                            tracing::warn!("Failed to rehash password for user {}: {}", user.id, e);
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to rehash password for user {}: {}", user.id, e);
+                    }
+                }
+            }
+
+            Ok(CredentialCheck::Valid(Box::new(user)))
+        }
+        Ok(false) => {
+            if let Err(e) = FailedLogin::record(pool, &user.id).await {
+                tracing::error!("Database error recording failed login: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+
+            audit
+                .record(
+                    AuditAction::LoginFailed,
+                    None,
+                    Some(&user.id),
+                    json!({ "reason": "invalid_password" }),
+                )
+                .await;
+
+            // Tell the user right away if this attempt triggered the lock
+            match FailedLogin::locked_until(
+                pool,
+                &user.id,
+                lockout_policy.max_attempts,
+                lockout_policy.window,
+            )
+            .await
+            {
+                Ok(Some(locked_until)) => {
+                    tracing::warn!("Locking account {} after repeated failed logins", user.id);
+                    Ok(CredentialCheck::Invalid(lockout_message(locked_until)))
+                }
+                Ok(None) => Ok(invalid()),
+                Err(e) => {
+                    tracing::error!("Database error checking lockout: {}", e);
+                    Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!("Password verification error: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Password verification error",
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Signs in from the login page's script (JSON) or, without JavaScript, a
+/// plain form post that is redirected to the dashboard or back to `/login`
 pub async fn handle_login(
     session: Session,
+    client: ClientInfo,
     State(pool): State<SqlitePool>,
-    Json(login_request): Json<LoginRequest>,
+    State(sign_in): State<SignInNotifier>,
+    State(lockout_policy): State<LockoutPolicy>,
+    State(password_hashing): State<PasswordHashing>,
+    Negotiate(format, login_request): Negotiate<LoginRequest>,
+) -> Response {
+    let reply = login(
+        session.clone(),
+        client,
+        pool,
+        sign_in,
+        lockout_policy,
+        password_hashing,
+        login_request,
+    )
+    .await;
+    format
+        .respond(&session, reply, "/dashboard", "/login")
+        .await
+}
+
+async fn login(
+    session: Session,
+    client: ClientInfo,
+    pool: SqlitePool,
+    sign_in: SignInNotifier,
+    lockout_policy: LockoutPolicy,
+    password_hashing: PasswordHashing,
+    login_request: LoginRequest,
 ) -> Result<Json<serde_json::Value>, Response> {
     // Validate the request
     if let Err(validation_errors) = login_request.validate() {
@@ -137,67 +639,85 @@ pub async fn handle_login(
         })));
     }
 
-    // Find user by email
-    let user = match User::find_by_email(&pool, &login_request.email).await {
-        Ok(Some(user)) => user,
-        Ok(None) => {
+    let audit = AuditLogger::new(pool.clone(), Some(&client));
+
+    let user = match check_credentials(
+        &pool,
+        &audit,
+        &lockout_policy,
+        &password_hashing,
+        &login_request.identifier,
+        &login_request.password,
+    )
+    .await?
+    {
+        CredentialCheck::Valid(user) => *user,
+        CredentialCheck::Invalid(message) => {
             return Ok(Json(json!({
                 "success": false,
-                "message": "Invalid email or password"
+                "message": message
             })));
         }
+    };
+
+    // Password is correct, track the login so it can be listed and revoked
+    let user_session = match UserSession::create(
+        &pool,
+        &user.id,
+        client.user_agent.as_deref(),
+        Some(&client.ip_address),
+    )
+    .await
+    {
+        Ok(user_session) => user_session,
         Err(e) => {
-            tracing::error!("Database error during login: {}", e);
+            tracing::error!("Database error creating user session: {}", e);
             return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
         }
     };
 
-    // Check if user is active
-    if !user.is_active {
-        return Ok(Json(json!({
-            "success": false,
-            "message": "Account is deactivated"
-        })));
+    // Create session
+    if let Err(e) = start_user_session(
+        &session,
+        &sign_in.session_cookie,
+        &user.id,
+        &user_session.id,
+        login_request.remember,
+    )
+    .await
+    {
+        tracing::error!("Session error: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
     }
 
-    // Verify password
-    match verify_password(&login_request.password, &user.password_hash) {
-        Ok(true) => {
-            // Password is correct, create session
-            if let Err(e) = session.insert("user_id", &user.id).await {
-                tracing::error!("Session error: {}", e);
-                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
-            }
+    sign_in.notify(&pool, &user, &client).await;
 
-            // Update last login
-            if let Err(e) = User::update_last_login(&pool, &user.id).await {
-                tracing::warn!("Failed to update last login for user {}: {}", user.id, e);
-            }
+    audit
+        .record(
+            AuditAction::LoginSucceeded,
+            Some(&user.id),
+            Some(&user.id),
+            json!({ "method": "password" }),
+        )
+        .await;
 
-            Ok(Json(json!({
-                "success": true,
-                "message": "Login successful",
-                "user": UserResponse::from(user)
-            })))
-        }
-        Ok(false) => Ok(Json(json!({
-            "success": false,
-            "message": "Invalid email or password"
-        }))),
-        Err(e) => {
-            tracing::error!("Password verification error: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Password verification error",
-            )
-                .into_response())
-        }
+    // Update last login
+    if let Err(e) = User::update_last_login(&pool, &user.id).await {
+        tracing::warn!("Failed to update last login for user {}: {}", user.id, e);
     }
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Login successful",
+        "user": UserResponse::from(user)
+    })))
 }
 
 pub async fn show_signup(
     session: Session,
     State(pool): State<SqlitePool>,
+    State(signup_mode): State<SignupMode>,
+    Query(query): Query<SignupQuery>,
 ) -> Result<Html<String>, Response> {
     let (css, js) = get_assets();
     let user = get_user_from_session(&session, &pool).await;
@@ -207,11 +727,16 @@ pub async fn show_signup(
         return Err(Redirect::to("/dashboard").into_response());
     }
 
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+
     let template = SignupTemplate {
         css,
         js,
         user,
-        flash_messages: Vec::new(),
+        flash_messages: take_flash_messages(&session).await,
+        csrf_token,
+        invite_only: signup_mode == SignupMode::InviteOnly,
+        invite_code: query.invite.unwrap_or_default(),
     };
 
     match template.render() {
@@ -223,9 +748,47 @@ pub async fn show_signup(
     }
 }
 
+/// Creates an account from the signup page's script (JSON) or a plain form
+/// post, which is redirected to `/login` or back to the signup form
 pub async fn handle_signup(
+    session: Session,
+    client: ClientInfo,
     State(pool): State<SqlitePool>,
-    Json(signup_request): Json<CreateUserRequest>,
+    State(notifier): State<SignUpNotifier>,
+    State(password_hashing): State<PasswordHashing>,
+    State(signup_mode): State<SignupMode>,
+    Negotiate(format, signup_request): Negotiate<CreateUserRequest>,
+) -> Response {
+    // Keep the invitation code in the form when sending the user back to it
+    let on_failure = match signup_request.invite_code.as_deref() {
+        Some(code) if !code.is_empty() => format!(
+            "/signup?{}",
+            url::form_urlencoded::Serializer::new(String::new())
+                .append_pair("invite", code)
+                .finish()
+        ),
+        _ => "/signup".to_string(),
+    };
+
+    let reply = signup(
+        client,
+        pool,
+        notifier,
+        password_hashing,
+        signup_mode,
+        signup_request,
+    )
+    .await;
+    format.respond(&session, reply, "/login", &on_failure).await
+}
+
+async fn signup(
+    client: ClientInfo,
+    pool: SqlitePool,
+    notifier: SignUpNotifier,
+    password_hashing: PasswordHashing,
+    signup_mode: SignupMode,
+    signup_request: CreateUserRequest,
 ) -> Result<Json<serde_json::Value>, Response> {
     // Validate the request
     if let Err(validation_errors) = signup_request.validate() {
@@ -244,6 +807,30 @@ pub async fn handle_signup(
         })));
     }
 
+    if !signup_request.accept_terms {
+        return Ok(Json(json!({
+            "success": false,
+            "errors": {
+                "accept_terms": "You must accept the Terms of Service and Privacy Policy"
+            }
+        })));
+    }
+
+    // Reject easily guessable passwords
+    let password_strength = estimate_strength(
+        &signup_request.password,
+        &[&signup_request.email, &signup_request.username],
+    );
+    if !password_strength.acceptable {
+        return Ok(Json(json!({
+            "success": false,
+            "errors": {
+                "password": password_strength.message(),
+                "password_strength": password_strength
+            }
+        })));
+    }
+
     // Check if user already exists
     match User::find_by_email(&pool, &signup_request.email).await {
         Ok(Some(_)) => {
@@ -279,7 +866,7 @@ pub async fn handle_signup(
     }
 
     // Hash the password
-    let password_hash = match hash_password(&signup_request.password) {
+    let password_hash = match hash_password(&signup_request.password, &password_hashing) {
         Ok(hash) => hash,
         Err(e) => {
             tracing::error!("Password hashing error: {}", e);
@@ -289,28 +876,152 @@ pub async fn handle_signup(
         }
     };
 
+    // Invite-only mode needs a valid invitation, claimed along with creating the account
+    let invitation = if signup_mode == SignupMode::InviteOnly {
+        let invite_code = signup_request
+            .invite_code
+            .as_deref()
+            .unwrap_or_default()
+            .trim();
+        match Invitation::find_usable(&pool, invite_code).await {
+            Ok(Some(invitation)) => Some(invitation),
+            Ok(None) => {
+                return Ok(Json(json!({
+                    "success": false,
+                    "errors": {
+                        "invite_code": "Invitation code is invalid or has expired"
+                    }
+                })));
+            }
+            Err(e) => {
+                tracing::error!("Database error checking invitation: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        }
+    } else {
+        None
+    };
+
+    // The versions the user is agreeing to, looked up before the account exists
+    let policies = match PolicyDocument::find_all_current(&pool).await {
+        Ok(policies) => policies,
+        Err(e) => {
+            tracing::error!("Database error loading policies: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    // Claiming the invitation, creating the account, granting its role, and
+    // recording policy acceptance succeed or fail together, so a failed
+    // signup neither uses up a single-use invite nor leaves a half-made account
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            tracing::error!("Database error starting signup: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    if let Some(invitation) = &invitation {
+        match Invitation::redeem(&mut *tx, &invitation.id).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return Ok(Json(json!({
+                    "success": false,
+                    "errors": {
+                        "invite_code": "Invitation code has already been used"
+                    }
+                })));
+            }
+            Err(e) => {
+                tracing::error!("Database error redeeming invitation: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        }
+    }
+
     // Create the user
-    match User::create(
-        &pool,
+    let user = match User::create(
+        &mut *tx,
         signup_request.email,
         signup_request.username,
         password_hash,
     )
     .await
     {
-        Ok(user) => Ok(Json(json!({
-            "success": true,
-            "message": "Account created successfully",
-            "user": UserResponse::from(user)
-        }))),
+        Ok(user) => user,
         Err(e) => {
             tracing::error!("Database error creating user: {}", e);
-            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    if let Some(invitation) = &invitation {
+        if let Err(e) = User::set_invitation(&mut *tx, &user.id, &invitation.id).await {
+            tracing::error!(
+                "Database error recording invitation {} for user {}: {}",
+                invitation.id,
+                user.id,
+                e
+            );
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    }
+
+    if let Err(e) = Role::grant(&mut *tx, &user.id, UserRole::NAME).await {
+        tracing::error!("Database error granting role to user {}: {}", user.id, e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+    }
+
+    for policy in &policies {
+        if let Err(e) =
+            PolicyAcceptance::record(&mut *tx, &user.id, &policy.id, Some(&client.ip_address)).await
+        {
+            tracing::error!("Database error recording policy acceptance: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
         }
     }
+
+    if let Err(e) = tx.commit().await {
+        tracing::error!("Database error finishing signup: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+    }
+
+    notifier.notify(&pool, &user).await;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Account created successfully. Check your email to verify your address.",
+        "user": UserResponse::from(user)
+    })))
 }
 
-pub async fn handle_logout(session: Session) -> Redirect {
+pub async fn handle_logout(
+    session: Session,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+) -> Redirect {
+    let user_id = session.get::<String>("user_id").await.ok().flatten();
+    // While impersonating, the user_sessions row belongs to the admin, and
+    // signing out also ends the impersonation
+    let impersonator_id = session.get::<String>(IMPERSONATOR_KEY).await.ok().flatten();
+    if let Some(admin_id) = &impersonator_id {
+        audit
+            .record(
+                AuditAction::ImpersonationStopped,
+                Some(admin_id),
+                user_id.as_deref(),
+                json!({ "reason": "logout" }),
+            )
+            .await;
+    }
+
+    let session_owner = impersonator_id.or(user_id);
+    if let (Some(owner_id), Ok(Some(user_session_id))) =
+        (session_owner, session.get::<String>(USER_SESSION_KEY).await)
+    {
+        let _ = UserSession::delete(&pool, &user_session_id, &owner_id).await;
+    }
     let _ = session.delete().await;
     Redirect::to("/")
 }
@@ -1,10 +1,12 @@
-use crate::handlers::auth::{FlashMessage, get_user_from_session};
-use crate::models::UserResponse;
+use crate::handlers::auth::{FlashMessage, take_flash_messages};
+use crate::models::{Organization, Role, UserResponse, WebauthnCredential};
+use crate::rbac::{AdminRole, RequireRole, RoleName, UserRole};
+use crate::tenancy::ACTIVE_ORGANIZATION_KEY;
 use askama::Template;
 use axum::{
     extract::State,
     http::StatusCode,
-    response::{Html, IntoResponse, Redirect, Response},
+    response::{Html, IntoResponse, Response},
 };
 use rand::{Rng, distributions::Alphanumeric};
 use sqlx::SqlitePool;
@@ -17,8 +19,12 @@ struct DashboardTemplate {
     js: String,
     user: Option<UserResponse>,
     dashboard_user: DashboardUser,
+    passkeys: Vec<WebauthnCredential>,
     flash_messages: Vec<FlashMessage>,
     csrf_token: String,
+    is_admin: bool,
+    organizations: Vec<Organization>,
+    active_organization_id: String,
 }
 
 #[derive(Debug)]
@@ -39,6 +45,9 @@ fn get_assets() -> (String, String) {
     (css.to_string(), js.to_string())
 }
 
+/// Session key holding the CSRF token.
+pub const CSRF_TOKEN_KEY: &str = "csrf_token";
+
 // Generate a cryptographically secure CSRF token
 fn generate_csrf_token() -> String {
     rand::thread_rng()
@@ -49,15 +58,15 @@ fn generate_csrf_token() -> String {
 }
 
 // Get or create CSRF token from session
-async fn get_or_create_csrf_token(session: &Session) -> Result<String, Response> {
+pub async fn get_or_create_csrf_token(session: &Session) -> Result<String, Response> {
     // Try to get existing token from session
-    if let Ok(Some(token)) = session.get::<String>("csrf_token").await {
+    if let Ok(Some(token)) = session.get::<String>(CSRF_TOKEN_KEY).await {
         return Ok(token);
     }
 
     // Generate new token and store in session
     let token = generate_csrf_token();
-    if let Err(_) = session.insert("csrf_token", &token).await {
+    if session.insert(CSRF_TOKEN_KEY, &token).await.is_err() {
         tracing::error!("Failed to store CSRF token in session");
         return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
     }
@@ -70,7 +79,7 @@ pub async fn validate_csrf_token(
     session: &Session,
     provided_token: &str,
 ) -> Result<bool, Response> {
-    match session.get::<String>("csrf_token").await {
+    match session.get::<String>(CSRF_TOKEN_KEY).await {
         Ok(Some(session_token)) => Ok(session_token == provided_token),
         Ok(None) => Ok(false), // No token in session
         Err(_) => {
@@ -81,20 +90,15 @@ pub async fn validate_csrf_token(
 }
 
 pub async fn show_dashboard(
+    RequireRole {
+        user: user_response,
+        ..
+    }: RequireRole<UserRole>,
     session: Session,
     State(pool): State<SqlitePool>,
 ) -> Result<Html<String>, Response> {
     let (css, js) = get_assets();
 
-    // Get user from session
-    let user_response = match get_user_from_session(&session, &pool).await {
-        Some(user) => user,
-        None => {
-            // User not logged in, redirect to login
-            return Err(Redirect::to("/login").into_response());
-        }
-    };
-
     // Get or create CSRF token
     let csrf_token = get_or_create_csrf_token(&session).await?;
 
@@ -115,13 +119,59 @@ pub async fn show_dashboard(
             .to_string(),
     };
 
+    let passkeys = match WebauthnCredential::find_by_user(&pool, &user_response.id).await {
+        Ok(passkeys) => passkeys,
+        Err(e) => {
+            tracing::error!("Database error loading passkeys: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let is_admin = match Role::user_has_role(&pool, &user_response.id, AdminRole::NAME).await {
+        Ok(is_admin) => is_admin,
+        Err(e) => {
+            tracing::error!("Database error checking roles: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let organizations = match Organization::find_for_user(&pool, &user_response.id).await {
+        Ok(organizations) => organizations,
+        Err(e) => {
+            tracing::error!("Database error loading organizations: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    // Same fallback as the ActiveOrganization extractor: the oldest membership
+    let active_organization_id = session
+        .get::<String>(ACTIVE_ORGANIZATION_KEY)
+        .await
+        .ok()
+        .flatten()
+        .filter(|id| {
+            organizations
+                .iter()
+                .any(|organization| &organization.id == id)
+        })
+        .or_else(|| {
+            organizations
+                .first()
+                .map(|organization| organization.id.clone())
+        })
+        .unwrap_or_default();
+
     let template = DashboardTemplate {
         css,
         js,
         user: Some(user_response),
         dashboard_user,
-        flash_messages: Vec::new(),
+        passkeys,
+        flash_messages: take_flash_messages(&session).await,
         csrf_token,
+        is_admin,
+        organizations,
+        active_organization_id,
     };
 
     match template.render() {
@@ -0,0 +1,238 @@
+use crate::config::SessionCookie;
+use crate::handlers::auth::{
+    IMPERSONATOR_KEY, USER_SESSION_KEY, get_user_from_session, session_idle_limit,
+};
+use crate::live::{Hub, LiveEvent, Subscription};
+use crate::models::{Role, User, UserSession};
+use crate::rbac::{AdminRole, RoleName};
+use axum::{
+    extract::{
+        State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::{HeaderMap, StatusCode, header},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::stream::{self, Stream};
+use sqlx::SqlitePool;
+use std::time::Duration;
+use tokio::time::{Instant, Interval, interval_at};
+use tower_sessions::Session;
+use url::Url;
+
+// How often an open connection checks its session hasn't been revoked
+const SESSION_RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The session behind an open connection, checked again every
+/// `SESSION_RECHECK_INTERVAL` so revoked or expired sessions and deactivated
+/// accounts stop receiving events. The connection also closes when the
+/// user's admin role changes, so the client reconnects with the right
+/// audience.
+struct Connection {
+    pool: SqlitePool,
+    user_id: String,
+    user_session_id: String,
+    // The admin while impersonating, who owns the user_sessions row
+    session_owner: String,
+    // Requests refresh the user_sessions row's last_seen_at; once it's older
+    // than this the session has expired
+    idle_limit: chrono::Duration,
+    is_admin: bool,
+    recheck: Interval,
+}
+
+impl Connection {
+    async fn open(
+        session: &Session,
+        pool: &SqlitePool,
+        cookie: &SessionCookie,
+        hub: &Hub,
+    ) -> Result<(Self, Subscription), Response> {
+        let Some(user) = get_user_from_session(session, pool).await else {
+            return Err((StatusCode::UNAUTHORIZED, "Authentication required").into_response());
+        };
+        // get_user_from_session only succeeds when this key is set
+        let user_session_id = session
+            .get::<String>(USER_SESSION_KEY)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        let session_owner = session
+            .get::<String>(IMPERSONATOR_KEY)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| user.id.clone());
+
+        let is_admin = match Role::user_has_role(pool, &user.id, AdminRole::NAME).await {
+            Ok(is_admin) => is_admin,
+            Err(e) => {
+                tracing::error!("Database error checking roles: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        };
+
+        let subscription = hub.subscribe(&user.id, is_admin);
+        let connection = Self {
+            pool: pool.clone(),
+            user_id: user.id,
+            user_session_id,
+            session_owner,
+            idle_limit: session_idle_limit(session, cookie).await,
+            is_admin,
+            recheck: interval_at(
+                Instant::now() + SESSION_RECHECK_INTERVAL,
+                SESSION_RECHECK_INTERVAL,
+            ),
+        };
+        Ok((connection, subscription))
+    }
+
+    /// Waits for the next event, or `None` once the hub is gone or the
+    /// session has ended
+    async fn next_event(&mut self, subscription: &mut Subscription) -> Option<LiveEvent> {
+        loop {
+            tokio::select! {
+                event = subscription.next() => return event,
+                _ = self.recheck.tick() => {
+                    if !self.is_unchanged().await {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn is_unchanged(&self) -> bool {
+        match UserSession::find_by_id(&self.pool, &self.user_session_id).await {
+            Ok(Some(user_session))
+                if user_session.user_id == self.session_owner
+                    && chrono::Utc::now() - user_session.last_seen_at <= self.idle_limit => {}
+            Ok(_) => return false,
+            Err(e) => {
+                // Keep the connection through a passing database error
+                tracing::warn!("Failed to recheck live connection session: {}", e);
+                return true;
+            }
+        }
+
+        match User::find_by_id(&self.pool, &self.user_id).await {
+            Ok(Some(user)) if user.is_active && user.deleted_at.is_none() => {}
+            Ok(_) => return false,
+            Err(e) => {
+                tracing::warn!("Failed to recheck live connection user: {}", e);
+                return true;
+            }
+        }
+
+        match Role::user_has_role(&self.pool, &self.user_id, AdminRole::NAME).await {
+            Ok(is_admin) => is_admin == self.is_admin,
+            Err(e) => {
+                tracing::warn!("Failed to recheck live connection roles: {}", e);
+                true
+            }
+        }
+    }
+}
+
+// Helper function to reject cross-site pages opening a socket with the
+// visitor's session cookie; browsers always send Origin on WebSocket requests
+fn is_same_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers
+        .get(header::ORIGIN)
+        .and_then(|value| value.to_str().ok())
+    else {
+        // Non-browser clients
+        return true;
+    };
+    let host = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok());
+
+    match (Url::parse(origin), host) {
+        (Ok(origin), Some(host)) => {
+            let origin_host = match (origin.host_str(), origin.port()) {
+                (Some(origin_host), Some(port)) => format!("{}:{}", origin_host, port),
+                (Some(origin_host), None) => origin_host.to_string(),
+                (None, _) => return false,
+            };
+            origin_host.eq_ignore_ascii_case(host)
+        }
+        _ => false,
+    }
+}
+
+/// Upgrades signed-in browsers to a WebSocket that receives the events
+/// published to the `Hub` for them, closed once their session ends
+pub async fn live_updates(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    session: Session,
+    State(pool): State<SqlitePool>,
+    State(session_cookie): State<SessionCookie>,
+    State(hub): State<Hub>,
+) -> Response {
+    if !is_same_origin(&headers) {
+        return (StatusCode::FORBIDDEN, "Cross-origin WebSocket request").into_response();
+    }
+
+    let (connection, subscription) =
+        match Connection::open(&session, &pool, &session_cookie, &hub).await {
+            Ok(opened) => opened,
+            Err(response) => return response,
+        };
+    ws.on_upgrade(move |socket| forward_events(socket, connection, subscription))
+}
+
+async fn forward_events(
+    mut socket: WebSocket,
+    mut connection: Connection,
+    mut subscription: Subscription,
+) {
+    loop {
+        tokio::select! {
+            event = connection.next_event(&mut subscription) => {
+                let Some(event) = event else { break };
+                let text = match serde_json::to_string(&event) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        tracing::error!("Failed to serialize live event: {}", e);
+                        continue;
+                    }
+                };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                // Pings are answered by axum; clients have nothing else to say
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// Server-sent events for the signed-in user: the same events as `/ws`,
+/// named by their `type`, with keep-alive comments so proxies don't drop
+/// an idle stream
+pub async fn event_stream(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    State(session_cookie): State<SessionCookie>,
+    State(hub): State<Hub>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, Response> {
+    let opened = Connection::open(&session, &pool, &session_cookie, &hub).await?;
+
+    let events = stream::unfold(opened, |(mut connection, mut subscription)| async move {
+        let event = connection.next_event(&mut subscription).await?;
+        let sse_event = Event::default().event(event.name()).json_data(&event);
+        Some((sse_event, (connection, subscription)))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
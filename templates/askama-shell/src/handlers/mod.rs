@@ -1,7 +1,33 @@
+pub mod admin;
+pub mod api;
 pub mod auth;
 pub mod dashboard;
+pub mod live;
+pub mod notes;
+pub mod organizations;
 pub mod pages;
+pub mod password;
+pub mod policies;
+pub mod profile;
+pub mod search;
+pub mod settings;
+pub mod verification;
+pub mod webauthn;
+pub mod webhooks;
 
+pub use admin::*;
+pub use api::*;
 pub use auth::*;
 pub use dashboard::*;
+pub use live::*;
+pub use notes::*;
+pub use organizations::*;
 pub use pages::*;
+pub use password::*;
+pub use policies::*;
+pub use profile::*;
+pub use search::*;
+pub use settings::*;
+pub use verification::*;
+pub use webauthn::*;
+pub use webhooks::*;
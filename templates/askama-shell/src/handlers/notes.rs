@@ -0,0 +1,307 @@
+use crate::api_auth::ApiUser;
+use crate::models::{CreateNoteRequest, Note, NoteSort, UpdateNoteRequest};
+use crate::pagination::{PageInfo, Paginated};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NotesQuery {
+    /// Only notes whose title contains this text
+    pub q: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NoteResponse {
+    pub success: bool,
+    pub note: Note,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NoteListResponse {
+    pub success: bool,
+    pub notes: Vec<Note>,
+    #[serde(flatten)]
+    pub pagination: PageInfo,
+}
+
+/// Body of every failed request. Validation failures list messages by field
+/// in `errors`; everything else sets `message`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub success: bool,
+    pub message: Option<String>,
+    pub errors: Option<HashMap<String, String>>,
+}
+
+// Helper function to build the 404 for notes that don't exist or aren't the caller's
+fn note_not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "success": false,
+            "message": "Note not found"
+        })),
+    )
+        .into_response()
+}
+
+// Helper function to build the 500 for failed queries, in the same envelope
+// as every other error
+fn database_error() -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({
+            "success": false,
+            "message": "Database error"
+        })),
+    )
+        .into_response()
+}
+
+// Helper function to turn validation errors into a 422
+fn validation_failed(validation_errors: validator::ValidationErrors) -> Response {
+    let mut errors = HashMap::new();
+    for (field, field_errors) in validation_errors.field_errors() {
+        let error_message = field_errors[0]
+            .message
+            .as_ref()
+            .map(|m| m.as_ref())
+            .unwrap_or("Invalid input");
+        errors.insert(field, error_message);
+    }
+
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(json!({
+            "success": false,
+            "errors": errors
+        })),
+    )
+        .into_response()
+}
+
+/// List the caller's notes, newest first unless sorted otherwise
+#[utoipa::path(
+    get,
+    path = "/api/v1/notes",
+    tag = "notes",
+    params(
+        ("page" = Option<i64>, Query, description = "Page number, starting at 1"),
+        ("per_page" = Option<i64>, Query, description = "Notes per page, at most 100"),
+        ("sort" = Option<NoteSort>, Query, description = "Column to sort by"),
+        ("dir" = Option<SortDirection>, Query, description = "Sort direction, `desc` by default"),
+        ("after" = Option<String>, Query, description = "ID of the last note seen, for keyset pagination"),
+        NotesQuery
+    ),
+    responses(
+        (status = 200, description = "A page of notes", body = NoteListResponse),
+        (status = 401, description = "Missing or invalid credentials", body = ErrorResponse),
+        (status = 403, description = "Token lacks the notes:read scope", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn list_notes(
+    api_user: ApiUser,
+    State(pool): State<SqlitePool>,
+    page: Paginated<NoteSort>,
+    Query(query): Query<NotesQuery>,
+) -> Result<Json<NoteListResponse>, Response> {
+    api_user.require_scope("notes:read")?;
+
+    let search = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    let (notes, total) = match tokio::try_join!(
+        Note::list_for_user(&pool, &api_user.user.id, search, &page),
+        Note::count_for_user(&pool, &api_user.user.id, search)
+    ) {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::error!("Database error listing notes: {}", e);
+            return Err(database_error());
+        }
+    };
+
+    Ok(Json(NoteListResponse {
+        success: true,
+        pagination: page.page_info(total, &notes, |note| &note.id),
+        notes,
+    }))
+}
+
+/// Create a note
+#[utoipa::path(
+    post,
+    path = "/api/v1/notes",
+    tag = "notes",
+    request_body = CreateNoteRequest,
+    responses(
+        (status = 201, description = "The new note", body = NoteResponse),
+        (status = 401, description = "Missing or invalid credentials", body = ErrorResponse),
+        (status = 403, description = "Token lacks the notes:write scope", body = ErrorResponse),
+        (status = 422, description = "Invalid title or body", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create_note(
+    api_user: ApiUser,
+    State(pool): State<SqlitePool>,
+    Json(mut create_request): Json<CreateNoteRequest>,
+) -> Result<(StatusCode, Json<NoteResponse>), Response> {
+    api_user.require_scope("notes:write")?;
+
+    create_request.title = create_request.title.trim().to_string();
+    if let Err(validation_errors) = create_request.validate() {
+        return Err(validation_failed(validation_errors));
+    }
+
+    match Note::create(
+        &pool,
+        &api_user.user.id,
+        &create_request.title,
+        &create_request.body,
+    )
+    .await
+    {
+        Ok(note) => Ok((
+            StatusCode::CREATED,
+            Json(NoteResponse {
+                success: true,
+                note,
+            }),
+        )),
+        Err(e) => {
+            tracing::error!("Database error creating note: {}", e);
+            Err(database_error())
+        }
+    }
+}
+
+/// Fetch one of the caller's notes
+#[utoipa::path(
+    get,
+    path = "/api/v1/notes/{id}",
+    tag = "notes",
+    params(("id" = String, Path, description = "Note ID")),
+    responses(
+        (status = 200, description = "The note", body = NoteResponse),
+        (status = 401, description = "Missing or invalid credentials", body = ErrorResponse),
+        (status = 403, description = "Token lacks the notes:read scope", body = ErrorResponse),
+        (status = 404, description = "No such note", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_note(
+    api_user: ApiUser,
+    State(pool): State<SqlitePool>,
+    Path(id): Path<String>,
+) -> Result<Json<NoteResponse>, Response> {
+    api_user.require_scope("notes:read")?;
+
+    match Note::find_for_user(&pool, &id, &api_user.user.id).await {
+        Ok(Some(note)) => Ok(Json(NoteResponse {
+            success: true,
+            note,
+        })),
+        Ok(None) => Err(note_not_found()),
+        Err(e) => {
+            tracing::error!("Database error loading note: {}", e);
+            Err(database_error())
+        }
+    }
+}
+
+/// Update a note's title and/or body
+#[utoipa::path(
+    patch,
+    path = "/api/v1/notes/{id}",
+    tag = "notes",
+    params(("id" = String, Path, description = "Note ID")),
+    request_body = UpdateNoteRequest,
+    responses(
+        (status = 200, description = "The updated note", body = NoteResponse),
+        (status = 401, description = "Missing or invalid credentials", body = ErrorResponse),
+        (status = 403, description = "Token lacks the notes:write scope", body = ErrorResponse),
+        (status = 404, description = "No such note", body = ErrorResponse),
+        (status = 422, description = "Invalid title or body", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update_note(
+    api_user: ApiUser,
+    State(pool): State<SqlitePool>,
+    Path(id): Path<String>,
+    Json(mut update_request): Json<UpdateNoteRequest>,
+) -> Result<Json<NoteResponse>, Response> {
+    api_user.require_scope("notes:write")?;
+
+    update_request.title = update_request.title.map(|title| title.trim().to_string());
+    if let Err(validation_errors) = update_request.validate() {
+        return Err(validation_failed(validation_errors));
+    }
+
+    match Note::update(
+        &pool,
+        &id,
+        &api_user.user.id,
+        update_request.title.as_deref(),
+        update_request.body.as_deref(),
+    )
+    .await
+    {
+        Ok(Some(note)) => Ok(Json(NoteResponse {
+            success: true,
+            note,
+        })),
+        Ok(None) => Err(note_not_found()),
+        Err(e) => {
+            tracing::error!("Database error updating note: {}", e);
+            Err(database_error())
+        }
+    }
+}
+
+/// Delete a note
+#[utoipa::path(
+    delete,
+    path = "/api/v1/notes/{id}",
+    tag = "notes",
+    params(("id" = String, Path, description = "Note ID")),
+    responses(
+        (status = 204, description = "The note was deleted"),
+        (status = 401, description = "Missing or invalid credentials", body = ErrorResponse),
+        (status = 403, description = "Token lacks the notes:write scope", body = ErrorResponse),
+        (status = 404, description = "No such note", body = ErrorResponse),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_note(
+    api_user: ApiUser,
+    State(pool): State<SqlitePool>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, Response> {
+    api_user.require_scope("notes:write")?;
+
+    match Note::delete(&pool, &id, &api_user.user.id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(note_not_found()),
+        Err(e) => {
+            tracing::error!("Database error deleting note: {}", e);
+            Err(database_error())
+        }
+    }
+}
@@ -0,0 +1,493 @@
+use crate::audit::{AuditAction, AuditLogger};
+use crate::email::{Mailer, send_organization_invitation_email};
+use crate::handlers::auth::{FlashMessage, get_user_from_session};
+use crate::handlers::dashboard::get_or_create_csrf_token;
+use crate::models::{
+    CreateOrganizationRequest, InviteMemberRequest, MemberRow, Membership, MembershipRole,
+    Organization, OrganizationInvitation, UserResponse,
+};
+use crate::tenancy::{ACTIVE_ORGANIZATION_KEY, ActiveOrganization};
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use tower_sessions::Session;
+use validator::Validate;
+
+#[derive(Template)]
+#[template(path = "organizations/new.html")]
+struct NewOrganizationTemplate {
+    css: String,
+    js: String,
+    user: Option<UserResponse>,
+    flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+}
+
+#[derive(Template)]
+#[template(path = "organizations/show.html")]
+struct OrganizationTemplate {
+    css: String,
+    js: String,
+    user: Option<UserResponse>,
+    flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+    organization: Organization,
+    membership: Membership,
+    members: Vec<MemberRow>,
+    invitations: Vec<OrganizationInvitation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrganizationQuery {
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SwitchOrganizationRequest {
+    pub organization_id: String,
+}
+
+// Organization invitations stay valid for a week
+const ORGANIZATION_INVITATION_TTL_DAYS: i64 = 7;
+
+// Helper function to get assets
+fn get_assets() -> (String, String) {
+    let css = include_str!(concat!(env!("OUT_DIR"), "/output.css"));
+    let js = include_str!(concat!(env!("OUT_DIR"), "/index.js"));
+    (css.to_string(), js.to_string())
+}
+
+pub async fn show_new_organization(
+    session: Session,
+    State(pool): State<SqlitePool>,
+) -> Result<Html<String>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err(Redirect::to("/login").into_response()),
+    };
+
+    let (css, js) = get_assets();
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+
+    let template = NewOrganizationTemplate {
+        css,
+        js,
+        user: Some(user),
+        flash_messages: Vec::new(),
+        csrf_token,
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Template error").into_response())
+        }
+    }
+}
+
+pub async fn handle_create_organization(
+    session: Session,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    Json(organization_request): Json<CreateOrganizationRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    // Validate the request
+    if let Err(validation_errors) = organization_request.validate() {
+        let mut errors = HashMap::new();
+        for (field, field_errors) in validation_errors.field_errors() {
+            let error_message = field_errors[0]
+                .message
+                .as_ref()
+                .map(|m| m.as_ref())
+                .unwrap_or("Invalid input");
+            errors.insert(field, error_message);
+        }
+        return Ok(Json(json!({
+            "success": false,
+            "errors": errors
+        })));
+    }
+
+    let organization =
+        match Organization::create(&pool, organization_request.name.trim(), &user.id).await {
+            Ok(organization) => organization,
+            Err(e) => {
+                tracing::error!("Database error creating organization: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        };
+
+    audit
+        .record(
+            AuditAction::OrganizationCreated,
+            Some(&user.id),
+            None,
+            json!({ "organization_id": organization.id, "name": organization.name }),
+        )
+        .await;
+
+    // Start working in the new organization straight away
+    if session
+        .insert(ACTIVE_ORGANIZATION_KEY, &organization.id)
+        .await
+        .is_err()
+    {
+        tracing::error!("Failed to store active organization in session");
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "organization": organization,
+        "redirect": "/organization"
+    })))
+}
+
+pub async fn handle_switch_organization(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    Json(switch_request): Json<SwitchOrganizationRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    match Membership::find(&pool, &switch_request.organization_id, &user.id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "You're not a member of that organization"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Database error loading membership: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    }
+
+    if session
+        .insert(ACTIVE_ORGANIZATION_KEY, &switch_request.organization_id)
+        .await
+        .is_err()
+    {
+        tracing::error!("Failed to store active organization in session");
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
+    }
+
+    Ok(Json(json!({ "success": true })))
+}
+
+pub async fn show_organization(
+    active: ActiveOrganization,
+    session: Session,
+    State(pool): State<SqlitePool>,
+    Query(query): Query<OrganizationQuery>,
+) -> Result<Html<String>, Response> {
+    let (css, js) = get_assets();
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+
+    let members = match Membership::find_members(&pool, &active.organization.id).await {
+        Ok(members) => members,
+        Err(e) => {
+            tracing::error!("Database error loading members: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    // Pending invitations include addresses, so only members who can manage see them
+    let invitations = if active.membership.can_manage_members() {
+        match OrganizationInvitation::find_pending(&pool, &active.organization.id).await {
+            Ok(invitations) => invitations,
+            Err(e) => {
+                tracing::error!("Database error loading organization invitations: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        }
+    } else {
+        Vec::new()
+    };
+
+    let mut flash_messages = Vec::new();
+    if let Some(message) = query.message {
+        flash_messages.push(FlashMessage {
+            level: "info".to_string(),
+            content: message,
+        });
+    }
+
+    let template = OrganizationTemplate {
+        css,
+        js,
+        user: Some(active.user),
+        flash_messages,
+        csrf_token,
+        organization: active.organization,
+        membership: active.membership,
+        members,
+        invitations,
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Template error").into_response())
+        }
+    }
+}
+
+pub async fn handle_invite_member(
+    active: ActiveOrganization,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    State(mailer): State<Mailer>,
+    Json(invite_request): Json<InviteMemberRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    if !active.membership.can_manage_members() {
+        return Err((StatusCode::FORBIDDEN, "Forbidden").into_response());
+    }
+
+    // Validate the request
+    if let Err(validation_errors) = invite_request.validate() {
+        let mut errors = HashMap::new();
+        for (field, field_errors) in validation_errors.field_errors() {
+            let error_message = field_errors[0]
+                .message
+                .as_ref()
+                .map(|m| m.as_ref())
+                .unwrap_or("Invalid input");
+            errors.insert(field, error_message);
+        }
+        return Ok(Json(json!({
+            "success": false,
+            "errors": errors
+        })));
+    }
+
+    if invite_request.role == MembershipRole::Owner {
+        return Ok(Json(json!({
+            "success": false,
+            "errors": { "role": "Invite as a member or admin" }
+        })));
+    }
+
+    let invitation = match OrganizationInvitation::create(
+        &pool,
+        &active.organization.id,
+        invite_request.email.trim(),
+        invite_request.role,
+        &active.user.id,
+        chrono::Duration::days(ORGANIZATION_INVITATION_TTL_DAYS),
+    )
+    .await
+    {
+        Ok(invitation) => invitation,
+        Err(e) => {
+            tracing::error!("Database error creating organization invitation: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    audit
+        .record(
+            AuditAction::OrganizationMemberInvited,
+            Some(&active.user.id),
+            None,
+            json!({
+                "organization_id": active.organization.id,
+                "email": invitation.email,
+                "role": invitation.role
+            }),
+        )
+        .await;
+
+    if let Err(e) = send_organization_invitation_email(
+        &mailer,
+        &invitation,
+        &active.organization.name,
+        &active.user.username,
+    )
+    .await
+    {
+        tracing::error!("Failed to send organization invitation email: {}", e);
+        return Ok(Json(json!({
+            "success": false,
+            "message": "The invitation was created but the email couldn't be sent"
+        })));
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "message": format!("Invitation sent to {}", invitation.email),
+        "invitation": invitation
+    })))
+}
+
+pub async fn handle_accept_organization_invitation(
+    session: Session,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    Path(token): Path<String>,
+) -> Result<Redirect, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => {
+            return Ok(Redirect::to(
+                "/login?message=Sign+in+with+the+invited+email+address,+then+open+the+invitation+link+again.",
+            ));
+        }
+    };
+
+    let invitation = match OrganizationInvitation::find_valid(&pool, &token).await {
+        Ok(Some(invitation)) => invitation,
+        Ok(None) => {
+            return Ok(Redirect::to(
+                "/organization?message=This+invitation+is+invalid+or+has+expired.",
+            ));
+        }
+        Err(e) => {
+            tracing::error!("Database error looking up organization invitation: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    // Invitations can only be used by the address they were sent to
+    if !invitation.email.eq_ignore_ascii_case(&user.email) {
+        return Ok(Redirect::to(
+            "/organization?message=This+invitation+was+sent+to+a+different+email+address.",
+        ));
+    }
+
+    if let Err(e) = Membership::add(
+        &pool,
+        &invitation.organization_id,
+        &user.id,
+        invitation.role(),
+    )
+    .await
+    {
+        tracing::error!("Database error adding member: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+    }
+
+    if let Err(e) = OrganizationInvitation::delete(&pool, &invitation.token).await {
+        tracing::warn!(
+            "Failed to delete accepted organization invitation for {}: {}",
+            invitation.email,
+            e
+        );
+    }
+
+    audit
+        .record(
+            AuditAction::OrganizationMemberJoined,
+            Some(&user.id),
+            Some(&user.id),
+            json!({ "organization_id": invitation.organization_id, "role": invitation.role }),
+        )
+        .await;
+
+    if let Err(e) = session
+        .insert(ACTIVE_ORGANIZATION_KEY, &invitation.organization_id)
+        .await
+    {
+        tracing::warn!("Failed to store active organization in session: {}", e);
+    }
+
+    Ok(Redirect::to(
+        "/organization?message=You've+joined+the+organization.",
+    ))
+}
+
+pub async fn handle_remove_member(
+    active: ActiveOrganization,
+    session: Session,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    Path(user_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Response> {
+    // Anyone can leave; removing someone else takes an owner or admin
+    let leaving = user_id == active.user.id;
+    if !leaving && !active.membership.can_manage_members() {
+        return Err((StatusCode::FORBIDDEN, "Forbidden").into_response());
+    }
+
+    let target = match Membership::find(&pool, &active.organization.id, &user_id).await {
+        Ok(Some(target)) => target,
+        Ok(None) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "Member not found"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Database error loading membership: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    if target.role() == MembershipRole::Owner {
+        if !leaving && active.membership.role() != MembershipRole::Owner {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "Only owners can remove other owners"
+            })));
+        }
+
+        match Membership::count_owners(&pool, &active.organization.id).await {
+            Ok(owners) if owners <= 1 => {
+                return Ok(Json(json!({
+                    "success": false,
+                    "message": "An organization needs at least one owner"
+                })));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!("Database error counting owners: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        }
+    }
+
+    if let Err(e) = Membership::remove(&pool, &active.organization.id, &user_id).await {
+        tracing::error!("Database error removing member: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+    }
+
+    audit
+        .record(
+            AuditAction::OrganizationMemberRemoved,
+            Some(&active.user.id),
+            Some(&user_id),
+            json!({ "organization_id": active.organization.id, "role": target.role }),
+        )
+        .await;
+
+    if leaving {
+        // The next request falls back to another membership, if there is one
+        if let Err(e) = session.remove::<String>(ACTIVE_ORGANIZATION_KEY).await {
+            tracing::warn!("Failed to clear active organization from session: {}", e);
+        }
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "redirect": if leaving { "/dashboard" } else { "/organization" }
+    })))
+}
@@ -1,4 +1,5 @@
 use crate::handlers::auth::{FlashMessage, get_user_from_session};
+use crate::handlers::dashboard::get_or_create_csrf_token;
 use crate::models::UserResponse;
 use askama::Template;
 use axum::{
@@ -16,6 +17,7 @@ struct IndexTemplate {
     js: String,
     user: Option<UserResponse>,
     flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
 }
 
 // Helper function to get assets
@@ -31,12 +33,14 @@ pub async fn show_index(
 ) -> Result<Html<String>, Response> {
     let (css, js) = get_assets();
     let user = get_user_from_session(&session, &pool).await;
+    let csrf_token = get_or_create_csrf_token(&session).await?;
 
     let template = IndexTemplate {
         css,
         js,
         user,
         flash_messages: Vec::new(),
+        csrf_token,
     };
 
     match template.render() {
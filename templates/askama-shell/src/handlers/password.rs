@@ -0,0 +1,41 @@
+use crate::password::{PasswordStrength, estimate_strength};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+
+// Same limit as CreateUserRequest; zxcvbn gets slow on long inputs and this
+// endpoint is open to anyone
+const MAX_PASSWORD_CHARS: usize = 128;
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordStrengthRequest {
+    pub password: String,
+    #[serde(default)]
+    pub email: String,
+    #[serde(default)]
+    pub username: String,
+}
+
+pub async fn check_password_strength(
+    Json(request): Json<PasswordStrengthRequest>,
+) -> Result<Json<PasswordStrength>, Response> {
+    if request.password.chars().count() > MAX_PASSWORD_CHARS {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+                "success": false,
+                "message": format!("Password must be at most {} characters", MAX_PASSWORD_CHARS)
+            })),
+        )
+            .into_response());
+    }
+
+    Ok(Json(estimate_strength(
+        &request.password,
+        &[&request.email, &request.username],
+    )))
+}
@@ -0,0 +1,204 @@
+use crate::audit::{AuditAction, AuditLogger};
+use crate::handlers::auth::{ClientInfo, FlashMessage, get_user_from_session};
+use crate::handlers::dashboard::get_or_create_csrf_token;
+use crate::models::{PolicyAcceptance, PolicyDocument, PolicyKind, UserResponse};
+use askama::Template;
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use tower_sessions::Session;
+
+#[derive(Template)]
+#[template(path = "policies/show.html")]
+struct PolicyTemplate {
+    css: String,
+    js: String,
+    user: Option<UserResponse>,
+    flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+    document: PolicyDocument,
+}
+
+#[derive(Template)]
+#[template(path = "policies/accept.html")]
+struct AcceptPoliciesTemplate {
+    css: String,
+    js: String,
+    user: Option<UserResponse>,
+    flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+    documents: Vec<PolicyDocument>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptPoliciesRequest {
+    /// IDs of the versions the user was shown
+    pub policies: Vec<String>,
+}
+
+// Helper function to get assets
+fn get_assets() -> (String, String) {
+    let css = include_str!(concat!(env!("OUT_DIR"), "/output.css"));
+    let js = include_str!(concat!(env!("OUT_DIR"), "/index.js"));
+    (css.to_string(), js.to_string())
+}
+
+pub async fn show_terms(
+    session: Session,
+    State(pool): State<SqlitePool>,
+) -> Result<Html<String>, Response> {
+    show_policy(&session, &pool, PolicyKind::Terms).await
+}
+
+pub async fn show_privacy(
+    session: Session,
+    State(pool): State<SqlitePool>,
+) -> Result<Html<String>, Response> {
+    show_policy(&session, &pool, PolicyKind::Privacy).await
+}
+
+async fn show_policy(
+    session: &Session,
+    pool: &SqlitePool,
+    kind: PolicyKind,
+) -> Result<Html<String>, Response> {
+    let document = match PolicyDocument::find_current(pool, kind).await {
+        Ok(Some(document)) => document,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Not Found").into_response()),
+        Err(e) => {
+            tracing::error!("Database error loading policy: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let (css, js) = get_assets();
+    let user = get_user_from_session(session, pool).await;
+    let csrf_token = get_or_create_csrf_token(session).await?;
+
+    let template = PolicyTemplate {
+        css,
+        js,
+        user,
+        flash_messages: Vec::new(),
+        csrf_token,
+        document,
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Template error").into_response())
+        }
+    }
+}
+
+pub async fn show_accept_policies(
+    session: Session,
+    State(pool): State<SqlitePool>,
+) -> Result<Html<String>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err(Redirect::to("/login").into_response()),
+    };
+
+    let documents = match PolicyDocument::find_unaccepted(&pool, &user.id).await {
+        Ok(documents) => documents,
+        Err(e) => {
+            tracing::error!("Database error loading unaccepted policies: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+    if documents.is_empty() {
+        return Err(Redirect::to("/dashboard").into_response());
+    }
+
+    let (css, js) = get_assets();
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+
+    let template = AcceptPoliciesTemplate {
+        css,
+        js,
+        user: Some(user),
+        flash_messages: Vec::new(),
+        csrf_token,
+        documents,
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Template error").into_response())
+        }
+    }
+}
+
+pub async fn handle_accept_policies(
+    session: Session,
+    client: ClientInfo,
+    State(pool): State<SqlitePool>,
+    Json(accept_request): Json<AcceptPoliciesRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    // Acceptance has to come from the account holder, not an admin signed in as them
+    if user.impersonated_by.is_some() {
+        return Ok(Json(json!({
+            "success": false,
+            "message": "Policies can't be accepted on another user's behalf"
+        })));
+    }
+
+    let pending = match PolicyDocument::find_unaccepted(&pool, &user.id).await {
+        Ok(pending) => pending,
+        Err(e) => {
+            tracing::error!("Database error loading unaccepted policies: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+    // Anything published after the page loaded hasn't been shown yet
+    if pending
+        .iter()
+        .any(|document| !accept_request.policies.contains(&document.id))
+    {
+        return Ok(Json(json!({
+            "success": false,
+            "message": "The policies were updated while you were reading. Please review them again."
+        })));
+    }
+
+    let audit = AuditLogger::new(pool.clone(), Some(&client));
+    for document in &pending {
+        if let Err(e) =
+            PolicyAcceptance::record(&pool, &user.id, &document.id, Some(&client.ip_address)).await
+        {
+            tracing::error!("Database error recording policy acceptance: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+
+        audit
+            .record(
+                AuditAction::PolicyAccepted,
+                Some(&user.id),
+                Some(&user.id),
+                json!({ "policy": document.id }),
+            )
+            .await;
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Thanks for accepting the updated policies",
+        "redirect": "/dashboard"
+    })))
+}
@@ -0,0 +1,126 @@
+use crate::config::AvatarStorage;
+use crate::handlers::auth::{FlashMessage, get_user_from_session};
+use crate::handlers::dashboard::get_or_create_csrf_token;
+use crate::models::{User, UserResponse};
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{Html, IntoResponse, Response},
+};
+use sqlx::SqlitePool;
+use tower_sessions::Session;
+
+#[derive(Template)]
+#[template(path = "profile.html")]
+struct ProfileTemplate {
+    css: String,
+    js: String,
+    user: Option<UserResponse>,
+    flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+    profile: User,
+}
+
+// Avatar URLs carry a version parameter, so a day is a safe browser cache lifetime
+const AVATAR_CACHE_CONTROL: &str = "public, max-age=86400";
+
+// Helper function to get assets
+fn get_assets() -> (String, String) {
+    let css = include_str!(concat!(env!("OUT_DIR"), "/output.css"));
+    let js = include_str!(concat!(env!("OUT_DIR"), "/index.js"));
+    (css.to_string(), js.to_string())
+}
+
+pub async fn show_profile(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    Path(username): Path<String>,
+) -> Result<Html<String>, Response> {
+    // Deactivated and deleted accounts don't have public profiles
+    let profile = match User::find_by_username(&pool, &username).await {
+        Ok(Some(profile)) if profile.is_active && profile.deleted_at.is_none() => profile,
+        Ok(_) => return Err((StatusCode::NOT_FOUND, "Not Found").into_response()),
+        Err(e) => {
+            tracing::error!("Database error loading profile: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let (css, js) = get_assets();
+    let user = get_user_from_session(&session, &pool).await;
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+
+    let template = ProfileTemplate {
+        css,
+        js,
+        user,
+        flash_messages: Vec::new(),
+        csrf_token,
+        profile,
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Template error").into_response())
+        }
+    }
+}
+
+pub async fn serve_avatar(
+    headers: HeaderMap,
+    State(pool): State<SqlitePool>,
+    State(avatars): State<AvatarStorage>,
+    Path(user_id): Path<String>,
+) -> Result<Response, Response> {
+    let updated_at = match User::find_by_id(&pool, &user_id).await {
+        Ok(Some(user)) if user.is_active && user.deleted_at.is_none() => {
+            match user.avatar_updated_at {
+                Some(updated_at) => updated_at,
+                None => return Err((StatusCode::NOT_FOUND, "Not Found").into_response()),
+            }
+        }
+        Ok(_) => return Err((StatusCode::NOT_FOUND, "Not Found").into_response()),
+        Err(e) => {
+            tracing::error!("Database error loading avatar owner: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    // The upload time identifies the stored image, so it doubles as the ETag
+    let etag = format!("\"{}-{}\"", user_id, updated_at.timestamp());
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if not_modified {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [
+                (header::ETAG, etag),
+                (header::CACHE_CONTROL, AVATAR_CACHE_CONTROL.to_string()),
+            ],
+        )
+            .into_response());
+    }
+
+    let png = match avatars.load(&user_id).await {
+        Ok(png) => png,
+        Err(e) => {
+            tracing::error!("Failed to read avatar for user {}: {}", user_id, e);
+            return Err((StatusCode::NOT_FOUND, "Not Found").into_response());
+        }
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/png".to_string()),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, AVATAR_CACHE_CONTROL.to_string()),
+        ],
+        png,
+    )
+        .into_response())
+}
@@ -0,0 +1,100 @@
+use crate::handlers::auth::{FlashMessage, take_flash_messages};
+use crate::handlers::dashboard::get_or_create_csrf_token;
+use crate::models::{NoteMatch, Search, UserMatch, UserResponse};
+use crate::negotiate::ResponseFormat;
+use crate::rbac::{RequireRole, UserRole};
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use tower_sessions::Session;
+
+// Per kind of result; search is for finding things, not browsing them
+const MAX_SEARCH_RESULTS: i64 = 20;
+
+#[derive(Template)]
+#[template(path = "search.html")]
+struct SearchTemplate {
+    css: String,
+    js: String,
+    user: Option<UserResponse>,
+    flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+    query: String,
+    notes: Vec<NoteMatch>,
+    users: Vec<UserMatch>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    q: Option<String>,
+}
+
+// Helper function to get assets
+fn get_assets() -> (String, String) {
+    let css = include_str!(concat!(env!("OUT_DIR"), "/output.css"));
+    let js = include_str!(concat!(env!("OUT_DIR"), "/index.js"));
+    (css.to_string(), js.to_string())
+}
+
+/// Searches the caller's notes and everyone's public profiles. Answers with
+/// the search page, or JSON when asked for it.
+pub async fn show_search(
+    RequireRole { user, .. }: RequireRole<UserRole>,
+    session: Session,
+    State(pool): State<SqlitePool>,
+    format: ResponseFormat,
+    Query(query): Query<SearchQuery>,
+) -> Result<Response, Response> {
+    let query = query.q.unwrap_or_default().trim().to_string();
+
+    let (notes, users) = match tokio::try_join!(
+        Search::notes(&pool, &user.id, &query, MAX_SEARCH_RESULTS),
+        Search::users(&pool, &query, MAX_SEARCH_RESULTS)
+    ) {
+        Ok(results) => results,
+        Err(e) => {
+            tracing::error!("Database error searching: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    if format == ResponseFormat::Json {
+        return Ok(Json(json!({
+            "success": true,
+            "query": query,
+            "notes": notes,
+            "users": users
+        }))
+        .into_response());
+    }
+
+    let (css, js) = get_assets();
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+    let flash_messages = take_flash_messages(&session).await;
+
+    let template = SearchTemplate {
+        css,
+        js,
+        user: Some(user),
+        flash_messages,
+        csrf_token,
+        query,
+        notes,
+        users,
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html).into_response()),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Template error").into_response())
+        }
+    }
+}
@@ -0,0 +1,920 @@
+use crate::audit::{AuditAction, AuditLogger};
+use crate::avatar::process_avatar;
+use crate::config::{AvatarStorage, PasswordHashing};
+use crate::email::{Mailer, send_email_change_emails};
+use crate::export::spawn_data_export;
+use crate::handlers::auth::{FlashMessage, USER_SESSION_KEY, get_user_from_session};
+use crate::handlers::dashboard::get_or_create_csrf_token;
+use crate::live::Hub;
+use crate::models::{
+    API_SCOPES, ApiToken, ChangePasswordRequest, CreateApiTokenRequest, DataExport,
+    DeleteAccountRequest, RefreshToken, UpdateNotificationsRequest, UpdateProfileRequest, User,
+    UserResponse, UserSession,
+};
+use crate::password::{estimate_strength, hash_password, verify_password};
+use crate::webhooks::{WebhookEvent, Webhooks};
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Multipart, Path, State},
+    http::{StatusCode, header},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use tower_sessions::Session;
+use validator::Validate;
+
+#[derive(Template)]
+#[template(path = "settings.html")]
+struct SettingsTemplate {
+    css: String,
+    js: String,
+    user: Option<UserResponse>,
+    account: UserResponse,
+    flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+    sessions: Vec<UserSession>,
+    current_session_id: String,
+    api_tokens: Vec<ApiToken>,
+    api_scopes: Vec<String>,
+    data_exports: Vec<DataExport>,
+}
+
+// Users can request one data export per day
+const DATA_EXPORT_COOLDOWN_HOURS: i64 = 24;
+
+// Helper function to get assets
+fn get_assets() -> (String, String) {
+    let css = include_str!(concat!(env!("OUT_DIR"), "/output.css"));
+    let js = include_str!(concat!(env!("OUT_DIR"), "/index.js"));
+    (css.to_string(), js.to_string())
+}
+
+pub async fn show_settings(
+    session: Session,
+    State(pool): State<SqlitePool>,
+) -> Result<Html<String>, Response> {
+    let (css, js) = get_assets();
+
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err(Redirect::to("/login").into_response()),
+    };
+
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+
+    let sessions = match UserSession::find_by_user(&pool, &user.id).await {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            tracing::error!("Database error loading sessions: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+    let current_session_id = session
+        .get::<String>(USER_SESSION_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+
+    let api_tokens = match ApiToken::find_by_user(&pool, &user.id).await {
+        Ok(api_tokens) => api_tokens,
+        Err(e) => {
+            tracing::error!("Database error loading API tokens: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let data_exports = match DataExport::find_by_user(&pool, &user.id).await {
+        Ok(data_exports) => data_exports,
+        Err(e) => {
+            tracing::error!("Database error loading data exports: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let template = SettingsTemplate {
+        css,
+        js,
+        user: Some(user.clone()),
+        account: user,
+        flash_messages: Vec::new(),
+        csrf_token,
+        sessions,
+        current_session_id,
+        api_tokens,
+        api_scopes: API_SCOPES.iter().map(|scope| scope.to_string()).collect(),
+        data_exports,
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Template error").into_response())
+        }
+    }
+}
+
+pub async fn handle_update_profile(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    State(mailer): State<Mailer>,
+    Json(profile_request): Json<UpdateProfileRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    // Validate the request
+    if let Err(validation_errors) = profile_request.validate() {
+        let mut errors = HashMap::new();
+        for (field, field_errors) in validation_errors.field_errors() {
+            let error_message = field_errors[0]
+                .message
+                .as_ref()
+                .map(|m| m.as_ref())
+                .unwrap_or("Invalid input");
+            errors.insert(field, error_message);
+        }
+        return Ok(Json(json!({
+            "success": false,
+            "errors": errors
+        })));
+    }
+
+    let username_changed = profile_request.username != user.username;
+    let email_changed = profile_request.email != user.email;
+
+    // Moving the address would hand the account to whoever reads the new inbox
+    if email_changed && user.impersonated_by.is_some() {
+        return Ok(Json(json!({
+            "success": false,
+            "errors": {
+                "email": "Email can't be changed while signed in as another user"
+            }
+        })));
+    }
+
+    // Check if username is taken by someone else
+    if username_changed {
+        match User::find_by_username(&pool, &profile_request.username).await {
+            Ok(Some(_)) => {
+                return Ok(Json(json!({
+                    "success": false,
+                    "errors": {
+                        "username": "Username already exists"
+                    }
+                })));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::error!("Database error checking existing username: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        }
+    }
+
+    // Check if email is taken by someone else
+    if email_changed {
+        match User::find_by_email(&pool, &profile_request.email).await {
+            Ok(Some(_)) => {
+                return Ok(Json(json!({
+                    "success": false,
+                    "errors": {
+                        "email": "Email already exists"
+                    }
+                })));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::error!("Database error checking existing user: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        }
+    }
+
+    if username_changed {
+        if let Err(e) = User::update_username(&pool, &user.id, &profile_request.username).await {
+            tracing::error!("Database error updating username: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    }
+
+    // Blank profile fields are stored as NULL so the username shows instead
+    let display_name = profile_request
+        .display_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    let bio = profile_request
+        .bio
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    if let Err(e) = User::update_profile_details(&pool, &user.id, display_name, bio).await {
+        tracing::error!("Database error updating profile details: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+    }
+
+    // The new address only replaces the current one once it's been confirmed
+    if email_changed {
+        if let Err(e) = User::set_pending_email(&pool, &user.id, Some(&profile_request.email)).await
+        {
+            tracing::error!("Database error updating pending email: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    }
+
+    let updated_user = match User::find_by_id(&pool, &user.id).await {
+        Ok(Some(updated_user)) => updated_user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+        Err(e) => {
+            tracing::error!("Database error loading user: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    if email_changed {
+        // Send the confirmation and notice emails without holding up the response
+        let change_user = updated_user.clone();
+        let new_email = profile_request.email.clone();
+        tokio::spawn(async move {
+            if let Err(e) = send_email_change_emails(&pool, &mailer, &change_user, &new_email).await
+            {
+                tracing::error!(
+                    "Failed to send email change confirmation for user {}: {}",
+                    change_user.id,
+                    e
+                );
+            }
+        });
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "message": if email_changed {
+            "Profile updated. Check your new inbox to confirm the email change."
+        } else {
+            "Profile updated"
+        },
+        "user": UserResponse::from(updated_user)
+    })))
+}
+
+pub async fn handle_change_password(
+    session: Session,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    State(password_hashing): State<PasswordHashing>,
+    Json(password_request): Json<ChangePasswordRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user_id = match get_user_from_session(&session, &pool).await {
+        Some(user) => user.id,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    // Validate the request
+    if let Err(validation_errors) = password_request.validate() {
+        let mut errors = HashMap::new();
+        for (field, field_errors) in validation_errors.field_errors() {
+            let error_message = field_errors[0]
+                .message
+                .as_ref()
+                .map(|m| m.as_ref())
+                .unwrap_or("Invalid input");
+            errors.insert(field, error_message);
+        }
+        return Ok(Json(json!({
+            "success": false,
+            "errors": errors
+        })));
+    }
+
+    let user = match User::find_by_id(&pool, &user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+        Err(e) => {
+            tracing::error!("Database error loading user: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    // Verify current password
+    match verify_password(&password_request.current_password, &user.password_hash) {
+        Ok(true) => {}
+        Ok(false) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "Current password is incorrect"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Password verification error: {}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Password verification error",
+            )
+                .into_response());
+        }
+    }
+
+    // Reject easily guessable passwords
+    let password_strength = estimate_strength(
+        &password_request.new_password,
+        &[&user.email, &user.username],
+    );
+    if !password_strength.acceptable {
+        return Ok(Json(json!({
+            "success": false,
+            "message": password_strength.message(),
+            "errors": {
+                "new_password": password_strength.message(),
+                "password_strength": password_strength
+            }
+        })));
+    }
+
+    let password_hash = match hash_password(&password_request.new_password, &password_hashing) {
+        Ok(hash) => hash,
+        Err(e) => {
+            tracing::error!("Password hashing error: {}", e);
+            return Err(
+                (StatusCode::INTERNAL_SERVER_ERROR, "Password hashing error").into_response(),
+            );
+        }
+    };
+
+    if let Err(e) = User::update_password_hash(&pool, &user.id, &password_hash).await {
+        tracing::error!("Database error updating password: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+    }
+
+    // Sign out everywhere else so the old password can't keep a session alive
+    let current_session_id = session
+        .get::<String>(USER_SESSION_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    if let Err(e) = UserSession::delete_others_for_user(&pool, &user.id, &current_session_id).await
+    {
+        tracing::warn!("Failed to revoke sessions for user {}: {}", user.id, e);
+    }
+    if let Err(e) = RefreshToken::delete_for_user(&pool, &user.id).await {
+        tracing::warn!(
+            "Failed to revoke refresh tokens for user {}: {}",
+            user.id,
+            e
+        );
+    }
+
+    audit
+        .record(
+            AuditAction::PasswordChanged,
+            Some(&user.id),
+            Some(&user.id),
+            json!({}),
+        )
+        .await;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Password changed successfully"
+    })))
+}
+
+pub async fn handle_update_notifications(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    Json(notifications_request): Json<UpdateNotificationsRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    match User::update_notify_new_sign_in(&pool, &user.id, notifications_request.notify_new_sign_in)
+        .await
+    {
+        Ok(()) => Ok(Json(json!({
+            "success": true,
+            "message": "Notification preferences saved"
+        }))),
+        Err(e) => {
+            tracing::error!("Database error updating notification preferences: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn handle_delete_account(
+    session: Session,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    State(webhooks): State<Webhooks>,
+    Json(delete_request): Json<DeleteAccountRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user_id = match get_user_from_session(&session, &pool).await {
+        Some(user) if user.impersonated_by.is_some() => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "An account can't be deleted while signed in as its owner"
+            })));
+        }
+        Some(user) => user.id,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    // Validate the request
+    if let Err(validation_errors) = delete_request.validate() {
+        let mut errors = HashMap::new();
+        for (field, field_errors) in validation_errors.field_errors() {
+            let error_message = field_errors[0]
+                .message
+                .as_ref()
+                .map(|m| m.as_ref())
+                .unwrap_or("Invalid input");
+            errors.insert(field, error_message);
+        }
+        return Ok(Json(json!({
+            "success": false,
+            "errors": errors
+        })));
+    }
+
+    let user = match User::find_by_id(&pool, &user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+        Err(e) => {
+            tracing::error!("Database error loading user: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    // Deleting an account always requires the password, even with a valid session
+    match verify_password(&delete_request.password, &user.password_hash) {
+        Ok(true) => {}
+        Ok(false) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "Password is incorrect"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Password verification error: {}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Password verification error",
+            )
+                .into_response());
+        }
+    }
+
+    if let Err(e) = User::soft_delete(&pool, &user.id).await {
+        tracing::error!("Database error deleting account: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+    }
+
+    if let Err(e) = UserSession::delete_for_user(&pool, &user.id).await {
+        tracing::warn!("Failed to revoke sessions for user {}: {}", user.id, e);
+    }
+    if let Err(e) = RefreshToken::delete_for_user(&pool, &user.id).await {
+        tracing::warn!(
+            "Failed to revoke refresh tokens for user {}: {}",
+            user.id,
+            e
+        );
+    }
+
+    if let Err(e) = session.flush().await {
+        tracing::error!("Session error: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
+    }
+
+    audit
+        .record(
+            AuditAction::AccountDeleted,
+            Some(&user.id),
+            Some(&user.id),
+            json!({}),
+        )
+        .await;
+    webhooks
+        .emit(
+            WebhookEvent::UserDeleted,
+            json!({
+                "id": user.id,
+                "username": user.username,
+                "email": user.email
+            }),
+        )
+        .await;
+
+    tracing::info!("Account {} scheduled for deletion", user.id);
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Your account has been deleted"
+    })))
+}
+
+pub async fn handle_revoke_session(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    Path(user_session_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    // While impersonating, the user_sessions rows listed are the user's own
+    // devices, and this session belongs to the admin
+    if user.impersonated_by.is_some() {
+        return Ok(Json(json!({
+            "success": false,
+            "message": "Sessions can't be managed while signed in as another user"
+        })));
+    }
+
+    match UserSession::delete(&pool, &user_session_id, &user.id).await {
+        Ok(true) => Ok(Json(json!({
+            "success": true,
+            "message": "Session revoked"
+        }))),
+        Ok(false) => Ok(Json(json!({
+            "success": false,
+            "message": "Session not found"
+        }))),
+        Err(e) => {
+            tracing::error!("Database error revoking session: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn handle_revoke_other_sessions(
+    session: Session,
+    State(pool): State<SqlitePool>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    // Every "other" session is the real user's while an admin is signed in as them
+    if user.impersonated_by.is_some() {
+        return Ok(Json(json!({
+            "success": false,
+            "message": "Sessions can't be managed while signed in as another user"
+        })));
+    }
+
+    // get_user_from_session only succeeds when this key is set
+    let current_session_id = session
+        .get::<String>(USER_SESSION_KEY)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+
+    match UserSession::delete_others_for_user(&pool, &user.id, &current_session_id).await {
+        Ok(revoked) => Ok(Json(json!({
+            "success": true,
+            "message": format!("Signed out of {} other session(s)", revoked)
+        }))),
+        Err(e) => {
+            tracing::error!("Database error revoking sessions: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn handle_create_api_token(
+    session: Session,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    Json(token_request): Json<CreateApiTokenRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    // A token would keep working after the impersonation ends
+    if user.impersonated_by.is_some() {
+        return Ok(Json(json!({
+            "success": false,
+            "message": "API tokens can't be created while signed in as another user"
+        })));
+    }
+
+    // Validate the request
+    if let Err(validation_errors) = token_request.validate() {
+        let mut errors = HashMap::new();
+        for (field, field_errors) in validation_errors.field_errors() {
+            let error_message = field_errors[0]
+                .message
+                .as_ref()
+                .map(|m| m.as_ref())
+                .unwrap_or("Invalid input");
+            errors.insert(field, error_message);
+        }
+        return Ok(Json(json!({
+            "success": false,
+            "errors": errors
+        })));
+    }
+
+    if let Some(scope) = token_request
+        .scopes
+        .iter()
+        .find(|scope| !API_SCOPES.contains(&scope.as_str()))
+    {
+        return Ok(Json(json!({
+            "success": false,
+            "message": format!("Unknown scope: {}", scope)
+        })));
+    }
+
+    match ApiToken::create(
+        &pool,
+        &user.id,
+        token_request.name.trim(),
+        &token_request.scopes,
+        token_request.expires_in_days.map(chrono::Duration::days),
+    )
+    .await
+    {
+        Ok((api_token, secret)) => {
+            audit
+                .record(
+                    AuditAction::ApiTokenCreated,
+                    Some(&user.id),
+                    Some(&user.id),
+                    json!({ "token_id": api_token.id, "scopes": api_token.scopes }),
+                )
+                .await;
+
+            // The secret is only ever shown in this response
+            Ok(Json(json!({
+                "success": true,
+                "message": "Token created",
+                "token": api_token,
+                "secret": secret
+            })))
+        }
+        Err(e) => {
+            tracing::error!("Database error creating API token: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn handle_revoke_api_token(
+    session: Session,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    Path(token_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    match ApiToken::delete(&pool, &token_id, &user.id).await {
+        Ok(true) => {
+            audit
+                .record(
+                    AuditAction::ApiTokenRevoked,
+                    Some(&user.id),
+                    Some(&user.id),
+                    json!({ "token_id": token_id }),
+                )
+                .await;
+
+            Ok(Json(json!({
+                "success": true,
+                "message": "Token revoked"
+            })))
+        }
+        Ok(false) => Ok(Json(json!({
+            "success": false,
+            "message": "Token not found"
+        }))),
+        Err(e) => {
+            tracing::error!("Database error revoking API token: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn handle_request_data_export(
+    session: Session,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    State(mailer): State<Mailer>,
+    State(hub): State<Hub>,
+    State(avatars): State<AvatarStorage>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user_id = match get_user_from_session(&session, &pool).await {
+        Some(user) => user.id,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    let user = match User::find_by_id(&pool, &user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+        Err(e) => {
+            tracing::error!("Database error loading user: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    // Assembling an export is expensive, so limit how often it can be done
+    match DataExport::find_by_user(&pool, &user.id).await {
+        Ok(exports) => {
+            let cooldown_ends = exports.first().map(|latest| {
+                latest.created_at + chrono::Duration::hours(DATA_EXPORT_COOLDOWN_HOURS)
+            });
+            if let Some(cooldown_ends) = cooldown_ends.filter(|ends| *ends > chrono::Utc::now()) {
+                let hours = ((cooldown_ends - chrono::Utc::now()).num_minutes() + 59) / 60;
+                return Ok(Json(json!({
+                    "success": false,
+                    "message": format!(
+                        "You can request another export in {} hour{}",
+                        hours,
+                        if hours == 1 { "" } else { "s" }
+                    )
+                })));
+            }
+        }
+        Err(e) => {
+            tracing::error!("Database error loading data exports: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    }
+
+    let export = match DataExport::create(&pool, &user.id).await {
+        Ok(export) => export,
+        Err(e) => {
+            tracing::error!("Database error creating data export: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    audit
+        .record(
+            AuditAction::DataExportRequested,
+            Some(&user.id),
+            Some(&user.id),
+            json!({ "export_id": export.id }),
+        )
+        .await;
+
+    spawn_data_export(pool, mailer, hub, avatars, user, export.id.clone());
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "We're preparing your export and will email you when it's ready",
+        "export": export
+    })))
+}
+
+pub async fn handle_download_data_export(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    Path(export_id): Path<String>,
+) -> Result<Response, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err(Redirect::to("/login").into_response()),
+    };
+
+    let export = match DataExport::find_ready(&pool, &export_id, &user.id).await {
+        Ok(Some(export)) => export,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Export not found").into_response()),
+        Err(e) => {
+            tracing::error!("Database error loading data export: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let filename = format!(
+        "attachment; filename=\"data-export-{}.json\"",
+        export.created_at.format("%Y-%m-%d")
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (header::CONTENT_DISPOSITION, filename),
+        ],
+        export.data.unwrap_or_default(),
+    )
+        .into_response())
+}
+
+pub async fn handle_upload_avatar(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    State(avatars): State<AvatarStorage>,
+    mut multipart: Multipart,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    let mut upload = None;
+    loop {
+        match multipart.next_field().await {
+            Ok(Some(field)) if field.name() == Some("avatar") => match field.bytes().await {
+                Ok(bytes) => {
+                    upload = Some(bytes);
+                    break;
+                }
+                Err(e) => return Err(e.into_response()),
+            },
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(e) => return Err(e.into_response()),
+        }
+    }
+
+    let Some(bytes) = upload.filter(|bytes| !bytes.is_empty()) else {
+        return Ok(Json(json!({
+            "success": false,
+            "message": "Choose an image to upload"
+        })));
+    };
+
+    // Decoding and resizing are CPU bound, keep them off the async workers
+    let png = match tokio::task::spawn_blocking(move || process_avatar(&bytes)).await {
+        Ok(Ok(png)) => png,
+        Ok(Err(e)) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": e.to_string()
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Avatar processing task failed: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Processing error").into_response());
+        }
+    };
+
+    if let Err(e) = avatars.save(&user.id, &png).await {
+        tracing::error!("Failed to store avatar for user {}: {}", user.id, e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Storage error").into_response());
+    }
+
+    if let Err(e) = User::set_avatar_updated_at(&pool, &user.id, Some(chrono::Utc::now())).await {
+        tracing::error!("Database error updating avatar: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+    }
+
+    let avatar_url = match User::find_by_id(&pool, &user.id).await {
+        Ok(Some(updated_user)) => updated_user.avatar_url(),
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+        Err(e) => {
+            tracing::error!("Database error loading user: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Avatar updated",
+        "avatar_url": avatar_url
+    })))
+}
+
+pub async fn handle_delete_avatar(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    State(avatars): State<AvatarStorage>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    if let Err(e) = User::set_avatar_updated_at(&pool, &user.id, None).await {
+        tracing::error!("Database error removing avatar: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+    }
+
+    if let Err(e) = avatars.delete(&user.id).await {
+        tracing::error!("Failed to delete avatar for user {}: {}", user.id, e);
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Avatar removed"
+    })))
+}
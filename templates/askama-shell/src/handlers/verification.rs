@@ -0,0 +1,105 @@
+use crate::models::{EmailChangeToken, EmailVerificationToken, User};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+};
+use sqlx::SqlitePool;
+
+pub async fn handle_verify_email(
+    State(pool): State<SqlitePool>,
+    Path(token): Path<String>,
+) -> Result<Redirect, Response> {
+    let verification_token = match EmailVerificationToken::find_valid(&pool, &token).await {
+        Ok(Some(verification_token)) => verification_token,
+        Ok(None) => {
+            return Ok(Redirect::to(
+                "/login?message=This+verification+link+is+invalid+or+has+expired.",
+            ));
+        }
+        Err(e) => {
+            tracing::error!("Database error looking up verification token: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    if let Err(e) = User::verify_email(&pool, &verification_token.user_id).await {
+        tracing::error!("Database error verifying email: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+    }
+
+    if let Err(e) =
+        EmailVerificationToken::delete_for_user(&pool, &verification_token.user_id).await
+    {
+        tracing::warn!(
+            "Failed to clean up verification tokens for user {}: {}",
+            verification_token.user_id,
+            e
+        );
+    }
+
+    Ok(Redirect::to(
+        "/login?message=Your+email+address+has+been+verified.",
+    ))
+}
+
+pub async fn handle_confirm_email_change(
+    State(pool): State<SqlitePool>,
+    Path(token): Path<String>,
+) -> Result<Redirect, Response> {
+    let change_token = match EmailChangeToken::find_valid(&pool, &token).await {
+        Ok(Some(change_token)) => change_token,
+        Ok(None) => {
+            return Ok(Redirect::to(
+                "/login?message=This+confirmation+link+is+invalid+or+has+expired.",
+            ));
+        }
+        Err(e) => {
+            tracing::error!("Database error looking up email change token: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    // Someone else may have claimed the address since the change was requested
+    match User::find_by_email(&pool, &change_token.new_email).await {
+        Ok(Some(_)) => {
+            return Ok(Redirect::to(
+                "/login?message=This+email+address+is+already+in+use.",
+            ));
+        }
+        Ok(None) => {}
+        Err(e) => {
+            tracing::error!("Database error checking existing user: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    }
+
+    let confirmed =
+        match User::confirm_pending_email(&pool, &change_token.user_id, &change_token.new_email)
+            .await
+        {
+            Ok(confirmed) => confirmed,
+            Err(e) => {
+                tracing::error!("Database error confirming email change: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        };
+
+    if let Err(e) = EmailChangeToken::delete_for_user(&pool, &change_token.user_id).await {
+        tracing::warn!(
+            "Failed to clean up email change tokens for user {}: {}",
+            change_token.user_id,
+            e
+        );
+    }
+
+    if !confirmed {
+        return Ok(Redirect::to(
+            "/login?message=This+confirmation+link+is+invalid+or+has+expired.",
+        ));
+    }
+
+    Ok(Redirect::to(
+        "/login?message=Your+email+address+has+been+changed.",
+    ))
+}
@@ -0,0 +1,352 @@
+use crate::audit::{AuditAction, AuditLogger};
+use crate::handlers::auth::{
+    ClientInfo, SignInNotifier, get_user_from_session, start_user_session,
+};
+use crate::models::{User, UserResponse, UserSession, WebauthnCredential};
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tower_sessions::Session;
+use webauthn_rs::prelude::{
+    PasskeyAuthentication, PasskeyRegistration, PublicKeyCredential, RegisterPublicKeyCredential,
+    Uuid, Webauthn,
+};
+
+const REGISTRATION_STATE_KEY: &str = "webauthn_registration";
+const AUTHENTICATION_STATE_KEY: &str = "webauthn_authentication";
+
+#[derive(Debug, Deserialize)]
+pub struct FinishPasskeyRegistrationRequest {
+    pub name: Option<String>,
+    pub credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartPasskeyLoginRequest {
+    // Either an email address or a username
+    #[serde(alias = "email")]
+    pub identifier: String,
+}
+
+pub async fn start_passkey_registration(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    State(webauthn): State<Arc<Webauthn>>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    // A passkey would keep working after the impersonation ends
+    if user.impersonated_by.is_some() {
+        return Ok(Json(json!({
+            "success": false,
+            "message": "Passkeys can't be registered while signed in as another user"
+        })));
+    }
+
+    let user_unique_id = match Uuid::parse_str(&user.id) {
+        Ok(id) => id,
+        Err(e) => {
+            tracing::error!("Invalid user id {}: {}", user.id, e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Invalid user id").into_response());
+        }
+    };
+
+    // Don't let the same authenticator be registered twice
+    let exclude_credentials = match WebauthnCredential::find_by_user(&pool, &user.id).await {
+        Ok(credentials) => credentials
+            .iter()
+            .map(|credential| credential.passkey.cred_id().clone())
+            .collect(),
+        Err(e) => {
+            tracing::error!("Database error loading passkeys: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let (options, registration_state) = match webauthn.start_passkey_registration(
+        user_unique_id,
+        &user.email,
+        &user.username,
+        Some(exclude_credentials),
+    ) {
+        Ok(challenge) => challenge,
+        Err(e) => {
+            tracing::error!("Failed to start passkey registration: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "WebAuthn error").into_response());
+        }
+    };
+
+    if let Err(e) = session
+        .insert(REGISTRATION_STATE_KEY, &registration_state)
+        .await
+    {
+        tracing::error!("Session error: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "options": options
+    })))
+}
+
+pub async fn finish_passkey_registration(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    State(webauthn): State<Arc<Webauthn>>,
+    Json(request): Json<FinishPasskeyRegistrationRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match get_user_from_session(&session, &pool).await {
+        Some(user) => user,
+        None => return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response()),
+    };
+
+    if user.impersonated_by.is_some() {
+        return Ok(Json(json!({
+            "success": false,
+            "message": "Passkeys can't be registered while signed in as another user"
+        })));
+    }
+
+    let registration_state = match session
+        .remove::<PasskeyRegistration>(REGISTRATION_STATE_KEY)
+        .await
+    {
+        Ok(Some(registration_state)) => registration_state,
+        Ok(None) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "No passkey registration in progress"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Session error: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
+        }
+    };
+
+    let passkey =
+        match webauthn.finish_passkey_registration(&request.credential, &registration_state) {
+            Ok(passkey) => passkey,
+            Err(e) => {
+                tracing::warn!("Passkey registration failed for user {}: {}", user.id, e);
+                return Ok(Json(json!({
+                    "success": false,
+                    "message": "Passkey registration failed"
+                })));
+            }
+        };
+
+    let name = request
+        .name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "Passkey".to_string());
+
+    match WebauthnCredential::create(&pool, &user.id, name, passkey).await {
+        Ok(_) => Ok(Json(json!({
+            "success": true,
+            "message": "Passkey registered successfully"
+        }))),
+        Err(e) => {
+            tracing::error!("Database error saving passkey: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn start_passkey_login(
+    session: Session,
+    State(pool): State<SqlitePool>,
+    State(webauthn): State<Arc<Webauthn>>,
+    Json(request): Json<StartPasskeyLoginRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let user = match User::find_by_email_or_username(&pool, request.identifier.trim()).await {
+        Ok(Some(user)) if user.is_active => user,
+        Ok(_) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "No passkeys are registered for this account"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Database error during passkey login: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let passkeys: Vec<_> = match WebauthnCredential::find_by_user(&pool, &user.id).await {
+        Ok(credentials) => credentials
+            .into_iter()
+            .map(|credential| credential.passkey.0)
+            .collect(),
+        Err(e) => {
+            tracing::error!("Database error loading passkeys: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    if passkeys.is_empty() {
+        return Ok(Json(json!({
+            "success": false,
+            "message": "No passkeys are registered for this account"
+        })));
+    }
+
+    let (options, authentication_state) = match webauthn.start_passkey_authentication(&passkeys) {
+        Ok(challenge) => challenge,
+        Err(e) => {
+            tracing::error!("Failed to start passkey authentication: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "WebAuthn error").into_response());
+        }
+    };
+
+    if let Err(e) = session
+        .insert(AUTHENTICATION_STATE_KEY, (&user.id, &authentication_state))
+        .await
+    {
+        tracing::error!("Session error: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "options": options
+    })))
+}
+
+pub async fn finish_passkey_login(
+    session: Session,
+    client: ClientInfo,
+    State(pool): State<SqlitePool>,
+    State(sign_in): State<SignInNotifier>,
+    State(webauthn): State<Arc<Webauthn>>,
+    Json(credential): Json<PublicKeyCredential>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let (user_id, authentication_state) = match session
+        .remove::<(String, PasskeyAuthentication)>(AUTHENTICATION_STATE_KEY)
+        .await
+    {
+        Ok(Some(state)) => state,
+        Ok(None) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "No passkey login in progress"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Session error: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
+        }
+    };
+
+    let authentication_result =
+        match webauthn.finish_passkey_authentication(&credential, &authentication_state) {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!("Passkey authentication failed for user {}: {}", user_id, e);
+                return Ok(Json(json!({
+                    "success": false,
+                    "message": "Passkey authentication failed"
+                })));
+            }
+        };
+
+    // Persist the updated signature counter on the credential that was used
+    match WebauthnCredential::find_by_user(&pool, &user_id).await {
+        Ok(credentials) => {
+            for mut credential in credentials {
+                if credential
+                    .passkey
+                    .update_credential(&authentication_result)
+                    .is_some()
+                {
+                    if let Err(e) =
+                        WebauthnCredential::record_use(&pool, &credential.id, &credential.passkey)
+                            .await
+                    {
+                        tracing::warn!("Failed to update passkey {}: {}", credential.id, e);
+                    }
+                    break;
+                }
+            }
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load passkeys for user {}: {}", user_id, e);
+        }
+    }
+
+    let user = match User::find_by_id(&pool, &user_id).await {
+        Ok(Some(user)) if user.is_active => user,
+        Ok(_) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "Account is deactivated"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Database error during passkey login: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    // Track the login so it can be listed and revoked
+    let user_session = match UserSession::create(
+        &pool,
+        &user.id,
+        client.user_agent.as_deref(),
+        Some(&client.ip_address),
+    )
+    .await
+    {
+        Ok(user_session) => user_session,
+        Err(e) => {
+            tracing::error!("Database error creating user session: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    if let Err(e) = start_user_session(
+        &session,
+        &sign_in.session_cookie,
+        &user.id,
+        &user_session.id,
+        false,
+    )
+    .await
+    {
+        tracing::error!("Session error: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Session error").into_response());
+    }
+
+    sign_in.notify(&pool, &user, &client).await;
+
+    AuditLogger::new(pool.clone(), Some(&client))
+        .record(
+            AuditAction::LoginSucceeded,
+            Some(&user.id),
+            Some(&user.id),
+            json!({ "method": "passkey" }),
+        )
+        .await;
+
+    if let Err(e) = User::update_last_login(&pool, &user.id).await {
+        tracing::warn!("Failed to update last login for user {}: {}", user.id, e);
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Login successful",
+        "user": UserResponse::from(user)
+    })))
+}
@@ -0,0 +1,298 @@
+use crate::audit::{AuditAction, AuditLogger};
+use crate::handlers::auth::FlashMessage;
+use crate::handlers::dashboard::get_or_create_csrf_token;
+use crate::models::{
+    CreateWebhookRequest, UserResponse, Webhook, WebhookDelivery, WebhookDeliveryRow,
+};
+use crate::rbac::{AdminRole, RequireRole};
+use crate::webhooks::{WebhookEvent, Webhooks, check_endpoint};
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use tower_sessions::Session;
+use validator::Validate;
+
+const RECENT_DELIVERIES: i64 = 50;
+
+#[derive(Template)]
+#[template(path = "admin/webhooks.html")]
+struct AdminWebhooksTemplate {
+    css: String,
+    js: String,
+    user: Option<UserResponse>,
+    flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+    webhooks: Vec<Webhook>,
+    events: Vec<WebhookEvent>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/webhook.html")]
+struct AdminWebhookTemplate {
+    css: String,
+    js: String,
+    user: Option<UserResponse>,
+    flash_messages: Vec<FlashMessage>,
+    csrf_token: String,
+    webhook: Webhook,
+    deliveries: Vec<WebhookDeliveryRow>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookAction {
+    Enable,
+    Disable,
+    Ping,
+    Delete,
+}
+
+// Helper function to get assets
+fn get_assets() -> (String, String) {
+    let css = include_str!(concat!(env!("OUT_DIR"), "/output.css"));
+    let js = include_str!(concat!(env!("OUT_DIR"), "/index.js"));
+    (css.to_string(), js.to_string())
+}
+
+pub async fn show_admin_webhooks(
+    admin: RequireRole<AdminRole>,
+    session: Session,
+    State(pool): State<SqlitePool>,
+) -> Result<Html<String>, Response> {
+    let (css, js) = get_assets();
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+
+    let webhooks = match Webhook::find_all(&pool).await {
+        Ok(webhooks) => webhooks,
+        Err(e) => {
+            tracing::error!("Database error loading webhooks: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let template = AdminWebhooksTemplate {
+        css,
+        js,
+        user: Some(admin.user),
+        flash_messages: Vec::new(),
+        csrf_token,
+        webhooks,
+        events: WebhookEvent::ALL.to_vec(),
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Template error").into_response())
+        }
+    }
+}
+
+pub async fn handle_create_webhook(
+    admin: RequireRole<AdminRole>,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    Json(webhook_request): Json<CreateWebhookRequest>,
+) -> Result<Json<serde_json::Value>, Response> {
+    // Validate the request
+    if let Err(validation_errors) = webhook_request.validate() {
+        let mut errors = HashMap::new();
+        for (field, field_errors) in validation_errors.field_errors() {
+            let error_message = field_errors[0]
+                .message
+                .as_ref()
+                .map(|m| m.as_ref())
+                .unwrap_or("Invalid input");
+            errors.insert(field, error_message);
+        }
+        return Ok(Json(json!({
+            "success": false,
+            "errors": errors
+        })));
+    }
+
+    if let Err(error) = check_endpoint(&webhook_request.url).await {
+        return Ok(Json(json!({
+            "success": false,
+            "errors": { "url": error }
+        })));
+    }
+    if let Some(unknown) = webhook_request.events.iter().find(|event| {
+        !WebhookEvent::ALL
+            .iter()
+            .any(|known| known.as_str() == *event)
+    }) {
+        return Ok(Json(json!({
+            "success": false,
+            "errors": { "events": format!("Unknown event: {}", unknown) }
+        })));
+    }
+
+    match Webhook::create(
+        &pool,
+        &webhook_request.url,
+        &webhook_request.events,
+        Some(&admin.user.id),
+    )
+    .await
+    {
+        Ok(webhook) => {
+            audit
+                .record(
+                    AuditAction::WebhookCreated,
+                    Some(&admin.user.id),
+                    None,
+                    json!({ "webhook_id": webhook.id, "url": webhook.url }),
+                )
+                .await;
+
+            Ok(Json(json!({
+                "success": true,
+                "message": "Webhook created",
+                "redirect": format!("/admin/webhooks/{}", webhook.id)
+            })))
+        }
+        Err(e) => {
+            tracing::error!("Database error creating webhook: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn show_admin_webhook(
+    admin: RequireRole<AdminRole>,
+    session: Session,
+    State(pool): State<SqlitePool>,
+    Path(webhook_id): Path<String>,
+) -> Result<Html<String>, Response> {
+    let (css, js) = get_assets();
+    let csrf_token = get_or_create_csrf_token(&session).await?;
+
+    let webhook = match Webhook::find_by_id(&pool, &webhook_id).await {
+        Ok(Some(webhook)) => webhook,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Not Found").into_response()),
+        Err(e) => {
+            tracing::error!("Database error loading webhook: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let deliveries =
+        match WebhookDelivery::find_recent_for_webhook(&pool, &webhook.id, RECENT_DELIVERIES).await
+        {
+            Ok(deliveries) => deliveries,
+            Err(e) => {
+                tracing::error!("Database error loading webhook deliveries: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        };
+
+    let template = AdminWebhookTemplate {
+        css,
+        js,
+        user: Some(admin.user),
+        flash_messages: Vec::new(),
+        csrf_token,
+        webhook,
+        deliveries,
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Template error").into_response())
+        }
+    }
+}
+
+pub async fn handle_webhook_action(
+    admin: RequireRole<AdminRole>,
+    audit: AuditLogger,
+    State(pool): State<SqlitePool>,
+    State(webhooks): State<Webhooks>,
+    Path((webhook_id, action)): Path<(String, WebhookAction)>,
+) -> Result<Json<serde_json::Value>, Response> {
+    let webhook = match Webhook::find_by_id(&pool, &webhook_id).await {
+        Ok(Some(webhook)) => webhook,
+        Ok(None) => {
+            return Ok(Json(json!({
+                "success": false,
+                "message": "Webhook not found"
+            })));
+        }
+        Err(e) => {
+            tracing::error!("Database error loading webhook: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+        }
+    };
+
+    let result = match action {
+        WebhookAction::Enable => Webhook::set_active(&pool, &webhook.id, true)
+            .await
+            .map(|_| "Webhook enabled"),
+        WebhookAction::Disable => Webhook::set_active(&pool, &webhook.id, false)
+            .await
+            .map(|_| "Webhook disabled"),
+        WebhookAction::Ping => webhooks.ping(&webhook).await.map(|_| "Test event queued"),
+        WebhookAction::Delete => {
+            let result = Webhook::delete(&pool, &webhook.id).await;
+            if result.is_ok() {
+                audit
+                    .record(
+                        AuditAction::WebhookDeleted,
+                        Some(&admin.user.id),
+                        None,
+                        json!({ "webhook_id": webhook.id, "url": webhook.url }),
+                    )
+                    .await;
+            }
+            result.map(|_| "Webhook deleted")
+        }
+    };
+
+    match result {
+        Ok(message) => Ok(Json(json!({
+            "success": true,
+            "message": message
+        }))),
+        Err(e) => {
+            tracing::error!("Database error updating webhook {}: {}", webhook.id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+pub async fn handle_retry_webhook_delivery(
+    _admin: RequireRole<AdminRole>,
+    State(pool): State<SqlitePool>,
+    State(webhooks): State<Webhooks>,
+    Path((webhook_id, delivery_id)): Path<(String, String)>,
+) -> Result<Json<serde_json::Value>, Response> {
+    match WebhookDelivery::retry(&pool, &delivery_id, &webhook_id).await {
+        Ok(true) => {
+            webhooks.wake();
+            Ok(Json(json!({
+                "success": true,
+                "message": "Delivery queued again"
+            })))
+        }
+        Ok(false) => Ok(Json(json!({
+            "success": false,
+            "message": "Only failed deliveries can be retried"
+        }))),
+        Err(e) => {
+            tracing::error!("Database error retrying delivery {}: {}", delivery_id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
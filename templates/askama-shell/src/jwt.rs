@@ -0,0 +1,89 @@
+use crate::api_auth::unauthorized;
+use crate::config::JwtConfig;
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts},
+    response::Response,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+/// Claims carried by access tokens issued from `/api/auth/token`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// The user id
+    pub sub: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// Signs an access token for the user with the current signing key
+pub fn issue_access_token(
+    config: &JwtConfig,
+    user_id: &str,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let signing_key = &config.signing_keys[0];
+    let now = chrono::Utc::now();
+    let claims = Claims {
+        sub: user_id.to_string(),
+        iat: now.timestamp(),
+        exp: (now + config.access_ttl).timestamp(),
+    };
+
+    let mut header = Header::new(Algorithm::HS256);
+    header.kid = Some(signing_key.kid.clone());
+
+    jsonwebtoken::encode(
+        &header,
+        &claims,
+        &EncodingKey::from_secret(&signing_key.secret),
+    )
+}
+
+/// Verifies an access token against whichever configured key signed it
+pub fn decode_access_token(
+    config: &JwtConfig,
+    token: &str,
+) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let header = jsonwebtoken::decode_header(token)?;
+    let signing_key = header
+        .kid
+        .and_then(|kid| config.signing_keys.iter().find(|key| key.kid == kid))
+        .ok_or(jsonwebtoken::errors::ErrorKind::InvalidToken)?;
+
+    let token_data = jsonwebtoken::decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(&signing_key.secret),
+        &Validation::new(Algorithm::HS256),
+    )?;
+
+    Ok(token_data.claims)
+}
+
+/// Extracts the claims of a valid `Authorization: Bearer` access token.
+/// Validation is stateless, so a token keeps working until it expires.
+#[async_trait]
+impl<S> FromRequestParts<S> for Claims
+where
+    JwtConfig: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = JwtConfig::from_ref(state);
+
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match token {
+            Some(token) => decode_access_token(&config, token.trim())
+                .map_err(|_| unauthorized("Invalid or expired token")),
+            None => Err(unauthorized("Authentication required")),
+        }
+    }
+}
@@ -1,40 +1,367 @@
+pub mod abuse;
+pub mod api_auth;
+pub mod api_version;
+pub mod audit;
+pub mod avatar;
+pub mod config;
+pub mod consent;
+pub mod csrf;
+pub mod email;
+pub mod export;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod handlers;
+pub mod jwt;
+pub mod live;
 pub mod models;
+pub mod negotiate;
+pub mod openapi;
+pub mod pagination;
+pub mod password;
+pub mod purge;
+pub mod rate_limit;
+pub mod rbac;
+pub mod security_headers;
+pub mod state;
+pub mod tenancy;
+pub mod webauthn;
+pub mod webhooks;
 
+use abuse::ProtectedRoute;
+use api_version::ApiVersion;
 use axum::{
     Router,
-    http::StatusCode,
+    extract::DefaultBodyLimit,
+    http::{HeaderName, StatusCode, header},
+    middleware,
     routing::{get, post},
 };
+use config::{CorsConfig, SessionBackend, SessionCookie};
+use openapi::ApiDoc;
 use sqlx::SqlitePool;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use state::AppState;
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    trace::TraceLayer,
+};
 use tower_sessions::cookie::time::Duration;
-use tower_sessions::{Expiry, MemoryStore, SessionManagerLayer};
+use tower_sessions::{ExpiredDeletion, Expiry, MemoryStore, SessionManagerLayer, SessionStore};
+#[cfg(feature = "redis")]
+use tower_sessions_redis_store::{RedisStore, fred::prelude::*};
+use tower_sessions_sqlx_store::SqliteStore;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
-pub async fn create_app(pool: SqlitePool) -> Router {
-    // Create session store
-    let session_store = MemoryStore::default();
-    let session_layer = SessionManagerLayer::new(session_store)
-        .with_secure(false)
-        .with_expiry(Expiry::OnInactivity(Duration::weeks(1))); // 7 days
+// How often expired sessions are cleared out of the SQLite store
+const EXPIRED_SESSION_CLEANUP_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(60 * 60);
+#[cfg(feature = "redis")]
+const REDIS_POOL_SIZE: usize = 6;
 
-    Router::new()
+pub async fn create_app(state: AppState) -> Router {
+    // Throttles credential guessing on the sign-in and sign-up endpoints
+    let auth_rate_limit = middleware::from_fn_with_state(
+        state.auth_rate_limiter.clone(),
+        rate_limit::limit_auth_requests,
+    );
+    // Asks for a CAPTCHA or proof of work once an IP's failed sign-ins pile up
+    let login_challenge = middleware::from_fn_with_state(
+        state
+            .abuse_protection
+            .gate(state.pool.clone(), ProtectedRoute::Login),
+        abuse::require_challenge,
+    );
+    let signup_challenge = middleware::from_fn_with_state(
+        state
+            .abuse_protection
+            .gate(state.pool.clone(), ProtectedRoute::Signup),
+        abuse::require_challenge,
+    );
+
+    let router = Router::new()
         // Pages
         .route("/", get(handlers::show_index))
         .route("/login", get(handlers::show_login))
         .route("/signup", get(handlers::show_signup))
         .route("/dashboard", get(handlers::show_dashboard))
+        .route("/settings", get(handlers::show_settings))
+        .route("/search", get(handlers::show_search))
+        .route("/u/:username", get(handlers::show_profile))
+        .route("/avatars/:id", get(handlers::serve_avatar))
+        .route("/terms", get(handlers::show_terms))
+        .route("/privacy", get(handlers::show_privacy))
+        .route(
+            "/policies/accept",
+            get(handlers::show_accept_policies).post(handlers::handle_accept_policies),
+        )
         // Auth endpoints
-        .route("/login", post(handlers::handle_login))
-        .route("/signup", post(handlers::handle_signup))
+        .route(
+            "/login",
+            post(handlers::handle_login)
+                .layer(login_challenge.clone())
+                .layer(auth_rate_limit.clone()),
+        )
+        .route(
+            "/signup",
+            post(handlers::handle_signup)
+                .layer(signup_challenge)
+                .layer(auth_rate_limit.clone()),
+        )
         .route("/logout", post(handlers::handle_logout))
+        .route("/verify-email/:token", get(handlers::handle_verify_email))
+        .route(
+            "/confirm-email/:token",
+            get(handlers::handle_confirm_email_change),
+        )
+        // Settings endpoints
+        .route("/settings/profile", post(handlers::handle_update_profile))
+        .route("/settings/password", post(handlers::handle_change_password))
+        .route(
+            "/settings/notifications",
+            post(handlers::handle_update_notifications),
+        )
+        .route("/settings/delete", post(handlers::handle_delete_account))
+        .route(
+            "/settings/sessions/:id/revoke",
+            post(handlers::handle_revoke_session),
+        )
+        .route(
+            "/settings/sessions/revoke-others",
+            post(handlers::handle_revoke_other_sessions),
+        )
+        .route("/settings/tokens", post(handlers::handle_create_api_token))
+        .route(
+            "/settings/tokens/:id/revoke",
+            post(handlers::handle_revoke_api_token),
+        )
+        .route(
+            "/settings/avatar",
+            // Leave room for the multipart framing around the image itself
+            post(handlers::handle_upload_avatar)
+                .layer(DefaultBodyLimit::max(avatar::AVATAR_MAX_BYTES + 64 * 1024)),
+        )
+        .route(
+            "/settings/avatar/delete",
+            post(handlers::handle_delete_avatar),
+        )
+        .route(
+            "/settings/export",
+            post(handlers::handle_request_data_export),
+        )
+        .route(
+            "/settings/export/:id",
+            get(handlers::handle_download_data_export),
+        )
+        // Organization endpoints
+        .route("/organizations/new", get(handlers::show_new_organization))
+        .route("/organizations", post(handlers::handle_create_organization))
+        .route(
+            "/organizations/switch",
+            post(handlers::handle_switch_organization),
+        )
+        .route(
+            "/organizations/invitations/:token",
+            get(handlers::handle_accept_organization_invitation),
+        )
+        .route("/organization", get(handlers::show_organization))
+        .route(
+            "/organization/invitations",
+            post(handlers::handle_invite_member),
+        )
+        .route(
+            "/organization/members/:user_id/remove",
+            post(handlers::handle_remove_member),
+        )
+        // Live updates for signed-in pages
+        .route("/ws", get(handlers::live_updates))
+        .route("/events", get(handlers::event_stream))
+        // API endpoints, authenticated by session or personal access token
+        .route("/api/me", get(handlers::api_current_user))
+        .route(
+            "/api/password-strength",
+            post(handlers::check_password_strength),
+        )
+        .route(
+            "/api/auth/token",
+            post(handlers::handle_issue_token)
+                .layer(login_challenge)
+                .layer(auth_rate_limit.clone()),
+        )
+        .route("/api/auth/revoke", post(handlers::handle_revoke_token))
+        // To add a version, mount its routes here and mark the old one with
+        // `.deprecated(Deprecation::since(...).sunset(...))` once clients can move
+        .merge(api_version::routes(
+            &state,
+            [ApiVersion::new("v1", api_v1_routes())],
+        ))
+        .merge(SwaggerUi::new("/api/docs").url("/api/openapi.json", ApiDoc::openapi()))
+        // Admin endpoints
+        .route("/admin", get(handlers::show_admin_users))
+        .route(
+            "/admin/users/bulk",
+            post(handlers::handle_admin_bulk_action),
+        )
+        .route("/admin/users/:id", get(handlers::show_admin_user))
+        .route(
+            "/admin/users/:id/reset-password",
+            post(handlers::handle_admin_reset_password),
+        )
+        .route(
+            "/admin/users/:id/roles",
+            post(handlers::handle_admin_update_role),
+        )
+        .route(
+            "/admin/users/:id/impersonate",
+            post(handlers::handle_start_impersonation),
+        )
+        .route(
+            "/admin/users/:id/:action",
+            post(handlers::handle_admin_user_action),
+        )
+        .route(
+            "/admin/invitations",
+            get(handlers::list_invitations).post(handlers::handle_create_invitation),
+        )
+        .route("/admin/audit", get(handlers::show_admin_audit))
+        .route("/admin/audit/events", get(handlers::list_audit_events))
+        .route(
+            "/admin/webhooks",
+            get(handlers::show_admin_webhooks).post(handlers::handle_create_webhook),
+        )
+        .route("/admin/webhooks/:id", get(handlers::show_admin_webhook))
+        .route(
+            "/admin/webhooks/:id/deliveries/:delivery_id/retry",
+            post(handlers::handle_retry_webhook_delivery),
+        )
+        .route(
+            "/admin/webhooks/:id/:action",
+            post(handlers::handle_webhook_action),
+        )
+        .route(
+            "/admin/impersonation/stop",
+            post(handlers::handle_stop_impersonation),
+        )
+        // Passkey endpoints
+        .route(
+            "/webauthn/register/start",
+            post(handlers::start_passkey_registration),
+        )
+        .route(
+            "/webauthn/register/finish",
+            post(handlers::finish_passkey_registration),
+        )
+        .route(
+            "/webauthn/login/start",
+            post(handlers::start_passkey_login).layer(auth_rate_limit),
+        )
+        .route(
+            "/webauthn/login/finish",
+            post(handlers::finish_passkey_login),
+        );
+
+    // GraphQL endpoint, authenticated like the /api routes
+    #[cfg(feature = "graphql")]
+    let router = router.merge(graphql::routes(&state));
+
+    let router = router
         // Fallback for 404
         .fallback(fallback_handler)
         // Middleware
+        .layer(middleware::from_fn_with_state(
+            state.pool.clone(),
+            consent::require_current_policies,
+        ))
+        .layer(middleware::from_fn(csrf::csrf_protection))
+        .layer(middleware::from_fn_with_state(
+            state.security_headers.clone(),
+            security_headers::security_headers,
+        ))
         .layer(TraceLayer::new_for_http())
-        .layer(CorsLayer::permissive())
-        .layer(session_layer)
-        .with_state(pool)
+        .layer(cors_layer(&state.cors));
+
+    // Create session store
+    let router = match &state.session_backend {
+        SessionBackend::Sqlite => {
+            // The sessions table is created by the regular migrations
+            let session_store = SqliteStore::new(state.pool.clone());
+            tokio::spawn(
+                session_store
+                    .clone()
+                    .continuously_delete_expired(EXPIRED_SESSION_CLEANUP_INTERVAL),
+            );
+            with_sessions(router, session_store, &state.session_cookie)
+        }
+        SessionBackend::Memory => {
+            with_sessions(router, MemoryStore::default(), &state.session_cookie)
+        }
+        // Redis expires sessions on its own
+        #[cfg(feature = "redis")]
+        SessionBackend::Redis(redis_config) => {
+            let redis_pool =
+                RedisPool::new(redis_config.clone(), None, None, None, REDIS_POOL_SIZE)
+                    .expect("REDIS_POOL_SIZE must be non-zero");
+            redis_pool.connect();
+            if let Err(e) = redis_pool.wait_for_connect().await {
+                tracing::error!("Failed to connect to Redis: {}", e);
+            }
+            with_sessions(router, RedisStore::new(redis_pool), &state.session_cookie)
+        }
+    };
+
+    router.with_state(state)
+}
+
+// Version 1 of the REST API, mounted by `api_version::routes`; document new
+// routes in `openapi::ApiDoc`
+fn api_v1_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/notes",
+            get(handlers::list_notes).post(handlers::create_note),
+        )
+        .route(
+            "/notes/:id",
+            get(handlers::get_note)
+                .patch(handlers::update_note)
+                .delete(handlers::delete_note),
+        )
+}
+
+fn with_sessions<S: SessionStore + Clone>(
+    router: Router<AppState>,
+    store: S,
+    cookie: &SessionCookie,
+) -> Router<AppState> {
+    let session_layer = SessionManagerLayer::new(store)
+        .with_name(cookie.name.clone())
+        .with_secure(cookie.secure)
+        .with_same_site(cookie.same_site)
+        .with_expiry(Expiry::OnInactivity(Duration::seconds(
+            cookie.inactivity_timeout.num_seconds(),
+        )));
+    let session_layer = match &cookie.domain {
+        Some(domain) => session_layer.with_domain(domain.clone()),
+        None => session_layer,
+    };
+
+    router.layer(session_layer)
+}
+
+// With no allowed origins the layer answers preflights without any
+// Access-Control-Allow-Origin, so browsers keep requests same-origin
+fn cors_layer(config: &CorsConfig) -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(config.allowed_origins.clone()))
+        .allow_methods(config.allowed_methods.clone())
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            HeaderName::from_static(csrf::CSRF_HEADER),
+        ])
+        .allow_credentials(config.allow_credentials)
+        .max_age(config.max_age)
 }
 
 async fn fallback_handler() -> (StatusCode, &'static str) {
@@ -0,0 +1,129 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+// Events kept for subscribers that fall behind; slower ones skip ahead
+const HUB_CAPACITY: usize = 256;
+
+/// Events pushed to browsers over `/ws` and `/events`, serialized as JSON
+/// tagged by `type`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    UserLoggedIn {
+        username: String,
+        display_name: Option<String>,
+        at: DateTime<Utc>,
+    },
+    DataExportReady {
+        export_id: String,
+    },
+    DataExportFailed {
+        export_id: String,
+    },
+}
+
+impl LiveEvent {
+    /// Same as the `type` tag, used as the SSE event name
+    pub fn name(&self) -> &'static str {
+        match self {
+            LiveEvent::UserLoggedIn { .. } => "user_logged_in",
+            LiveEvent::DataExportReady { .. } => "data_export_ready",
+            LiveEvent::DataExportFailed { .. } => "data_export_failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Audience {
+    Everyone,
+    Admins,
+    User(String),
+}
+
+#[derive(Debug, Clone)]
+struct Delivery {
+    audience: Audience,
+    event: LiveEvent,
+}
+
+/// Fans events out to every open `/ws` and `/events` connection. Handlers
+/// publish with `State<Hub>`; each connection subscribes as its user and
+/// only sees events meant for everyone, for that user, or for admins if
+/// they are one.
+#[derive(Clone)]
+pub struct Hub {
+    sender: broadcast::Sender<Delivery>,
+}
+
+impl Default for Hub {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(HUB_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl Hub {
+    /// Sends an event to every connected user
+    pub fn publish(&self, event: LiveEvent) {
+        self.send(Audience::Everyone, event);
+    }
+
+    /// Sends an event only to admins' connections, for events about other users
+    pub fn publish_to_admins(&self, event: LiveEvent) {
+        self.send(Audience::Admins, event);
+    }
+
+    /// Sends an event only to the given user's connections
+    pub fn publish_to(&self, user_id: &str, event: LiveEvent) {
+        self.send(Audience::User(user_id.to_string()), event);
+    }
+
+    pub fn subscribe(&self, user_id: &str, is_admin: bool) -> Subscription {
+        Subscription {
+            receiver: self.sender.subscribe(),
+            user_id: user_id.to_string(),
+            is_admin,
+        }
+    }
+
+    fn send(&self, audience: Audience, event: LiveEvent) {
+        // Sending only fails when nobody is connected, which is fine
+        let _ = self.sender.send(Delivery { audience, event });
+    }
+}
+
+/// One connection's view of the hub
+pub struct Subscription {
+    receiver: broadcast::Receiver<Delivery>,
+    user_id: String,
+    is_admin: bool,
+}
+
+impl Subscription {
+    /// Waits for the next event for this user, or `None` once the hub is gone
+    pub async fn next(&mut self) -> Option<LiveEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(Delivery {
+                    audience: Audience::User(user_id),
+                    ..
+                }) if user_id != self.user_id => continue,
+                Ok(Delivery {
+                    audience: Audience::Admins,
+                    ..
+                }) if !self.is_admin => continue,
+                Ok(delivery) => return Some(delivery.event),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::debug!(
+                        "Live subscriber for {} skipped {} events",
+                        self.user_id,
+                        skipped
+                    );
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
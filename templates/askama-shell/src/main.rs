@@ -1,5 +1,27 @@
-use rust_web_shell::{create_app, setup_database};
+#[cfg(feature = "grpc")]
+use rust_web_shell::grpc::{GrpcConfig, spawn_grpc_server};
+use rust_web_shell::{
+    abuse::AbuseProtection,
+    audit::{AuditAction, AuditLogger},
+    config::{
+        AccountRetention, AvatarStorage, CorsConfig, JwtConfig, LockoutPolicy, PasswordHashing,
+        SecurityHeaders, SessionBackend, SessionCookie, SignupMode,
+    },
+    create_app,
+    email::Mailer,
+    live::Hub,
+    models::{FailedLogin, Invitation, PolicyDocument, PolicyKind, Role, User},
+    purge::spawn_account_purge,
+    rate_limit::{ApiRateLimiter, AuthRateLimiter},
+    setup_database,
+    state::AppState,
+    webauthn,
+    webhooks::{Webhooks, spawn_webhook_delivery},
+};
+use serde_json::json;
 use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -32,8 +54,161 @@ async fn main() -> anyhow::Result<()> {
     let pool = setup_database(&database_url).await?;
     tracing::info!("Database connected and migrations applied");
 
+    // Administrative commands, e.g. `rust-web-shell unlock-user alice`
+    if let Some(command) = env::args().nth(1) {
+        let audit = AuditLogger::new(pool.clone(), None);
+        return match command.as_str() {
+            "unlock-user" => {
+                let identifier = env::args()
+                    .nth(2)
+                    .ok_or_else(|| anyhow::anyhow!("Usage: unlock-user <email-or-username>"))?;
+                let user = User::find_by_email_or_username(&pool, &identifier)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("No user found for {}", identifier))?;
+                FailedLogin::clear_for_user(&pool, &user.id).await?;
+                audit
+                    .record(
+                        AuditAction::UserUnlocked,
+                        None,
+                        Some(&user.id),
+                        json!({ "source": "cli" }),
+                    )
+                    .await;
+                println!("Unlocked account for {}", user.username);
+                Ok(())
+            }
+            "create-invite" => {
+                let max_uses = match env::args().nth(2) {
+                    Some(value) => value.parse()?,
+                    None => 1,
+                };
+                let expires_in_days = match env::args().nth(3) {
+                    Some(value) => value.parse()?,
+                    None => 7,
+                };
+                let invitation = Invitation::create(
+                    &pool,
+                    None,
+                    max_uses,
+                    Some(chrono::Duration::days(expires_in_days)),
+                )
+                .await?;
+                audit
+                    .record(
+                        AuditAction::InvitationCreated,
+                        None,
+                        None,
+                        json!({ "invitation_id": invitation.id, "source": "cli" }),
+                    )
+                    .await;
+                println!("Invitation code: {}", invitation.code);
+                println!("Signup link: /signup?invite={}", invitation.code);
+                Ok(())
+            }
+            "grant-role" | "revoke-role" => {
+                let usage = || anyhow::anyhow!("Usage: {} <email-or-username> <role>", command);
+                let identifier = env::args().nth(2).ok_or_else(usage)?;
+                let role = env::args().nth(3).ok_or_else(usage)?;
+                let user = User::find_by_email_or_username(&pool, &identifier)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("No user found for {}", identifier))?;
+                if !Role::find_all(&pool).await?.iter().any(|r| r.name == role) {
+                    return Err(anyhow::anyhow!("Unknown role: {}", role));
+                }
+                let action = if command == "grant-role" {
+                    Role::grant(&pool, &user.id, &role).await?;
+                    println!("Granted {} to {}", role, user.username);
+                    AuditAction::RoleGranted
+                } else {
+                    Role::revoke(&pool, &user.id, &role).await?;
+                    println!("Revoked {} from {}", role, user.username);
+                    AuditAction::RoleRevoked
+                };
+                audit
+                    .record(
+                        action,
+                        None,
+                        Some(&user.id),
+                        json!({ "role": role, "source": "cli" }),
+                    )
+                    .await;
+                Ok(())
+            }
+            "publish-policy" => {
+                let usage = || anyhow::anyhow!("Usage: publish-policy <terms|privacy> <html-file>");
+                let kind = env::args()
+                    .nth(2)
+                    .and_then(|kind| PolicyKind::parse(&kind))
+                    .ok_or_else(usage)?;
+                let path = env::args().nth(3).ok_or_else(usage)?;
+                let body = std::fs::read_to_string(&path)?;
+                let document = PolicyDocument::publish(&pool, kind, &body).await?;
+                audit
+                    .record(
+                        AuditAction::PolicyPublished,
+                        None,
+                        None,
+                        json!({ "policy": document.id, "source": "cli" }),
+                    )
+                    .await;
+                println!(
+                    "Published {} version {}; users will be asked to accept it",
+                    kind.title(),
+                    document.version
+                );
+                Ok(())
+            }
+            _ => Err(anyhow::anyhow!("Unknown command: {}", command)),
+        };
+    }
+
+    // Purge deleted accounts once their retention window has passed
+    let avatars = AvatarStorage::from_env();
+    let session_cookie = SessionCookie::from_env()?;
+    spawn_account_purge(
+        pool.clone(),
+        AccountRetention::from_env(),
+        avatars.clone(),
+        session_cookie.clone(),
+    );
+
+    // Set up outgoing email
+    let mailer = Mailer::from_env()?;
+
+    // Set up passkey support
+    let webauthn = Arc::new(webauthn::from_env()?);
+
+    let jwt = JwtConfig::from_env()?;
+
+    // Send queued webhook deliveries, including ones left from before a restart
+    let webhooks = Webhooks::new(pool.clone())?;
+    spawn_webhook_delivery(webhooks.clone());
+
+    // Serve the gRPC API next to the HTTP server
+    #[cfg(feature = "grpc")]
+    spawn_grpc_server(GrpcConfig::from_env()?, pool.clone(), jwt.clone());
+
     // Create the application
-    let app = create_app(pool).await;
+    let app = create_app(AppState {
+        pool,
+        mailer,
+        webauthn,
+        lockout_policy: LockoutPolicy::from_env(),
+        auth_rate_limiter: AuthRateLimiter::from_env(),
+        api_rate_limiter: ApiRateLimiter::from_env(),
+        abuse_protection: AbuseProtection::from_env()?,
+        password_hashing: PasswordHashing::from_env()?,
+        session_backend: SessionBackend::from_env()?,
+        session_cookie,
+        signup_mode: SignupMode::from_env()?,
+        jwt,
+        avatars,
+        security_headers: SecurityHeaders::from_env()?,
+        cors: CorsConfig::from_env()?,
+        hub: Hub::default(),
+        webhooks,
+    })
+    .await;
 
     // Create the listener
     let listener = tokio::net::TcpListener::bind(format!("{}:{}", host, port)).await?;
@@ -48,7 +223,12 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("📝 Signup: http://{}:{}/signup", host, port);
 
     // Start the server
-    axum::serve(listener, app).await?;
+    // Client addresses are recorded against each login session
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,150 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::{Rng, distributions::Alphanumeric};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+use validator::Validate;
+
+// Secrets look like `rws_<40 random characters>`
+pub const API_TOKEN_PREFIX: &str = "rws_";
+const TOKEN_SECRET_LENGTH: usize = 40;
+// Characters of the secret kept in plain text so tokens can be told apart
+const VISIBLE_PREFIX_LENGTH: usize = 8;
+
+// Scopes a token can be granted
+pub const API_SCOPES: [&str; 3] = ["profile:read", "notes:read", "notes:write"];
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ApiToken {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub token_prefix: String,
+    /// Space-separated list of scopes
+    pub scopes: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateApiTokenRequest {
+    #[validate(length(
+        min = 1,
+        max = 64,
+        message = "Name must be between 1 and 64 characters"
+    ))]
+    pub name: String,
+
+    #[validate(length(min = 1, message = "Select at least one scope"))]
+    pub scopes: Vec<String>,
+
+    // Leave unset for a token that never expires
+    #[validate(range(min = 1, max = 365))]
+    pub expires_in_days: Option<i64>,
+}
+
+// Helper function to hash a token secret for storage and lookup
+pub(crate) fn hash_token(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+impl ApiToken {
+    /// Creates a token and returns it with its secret, which is never stored
+    pub async fn create(
+        pool: &SqlitePool,
+        user_id: &str,
+        name: &str,
+        scopes: &[String],
+        ttl: Option<Duration>,
+    ) -> Result<(ApiToken, String), sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+        let random: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_SECRET_LENGTH)
+            .map(char::from)
+            .collect();
+        let secret = format!("{}{}", API_TOKEN_PREFIX, random);
+        let now = Utc::now();
+
+        let token = sqlx::query_as::<_, ApiToken>(
+            r#"
+            INSERT INTO api_tokens (id, user_id, name, token_hash, token_prefix, scopes, expires_at, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(name)
+        .bind(hash_token(&secret))
+        .bind(&secret[..API_TOKEN_PREFIX.len() + VISIBLE_PREFIX_LENGTH])
+        .bind(scopes.join(" "))
+        .bind(ttl.map(|ttl| now + ttl))
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok((token, secret))
+    }
+
+    pub async fn find_by_user(
+        pool: &SqlitePool,
+        user_id: &str,
+    ) -> Result<Vec<ApiToken>, sqlx::Error> {
+        let tokens = sqlx::query_as::<_, ApiToken>(
+            "SELECT * FROM api_tokens WHERE user_id = ?1 ORDER BY created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(tokens)
+    }
+
+    pub async fn find_valid(
+        pool: &SqlitePool,
+        secret: &str,
+    ) -> Result<Option<ApiToken>, sqlx::Error> {
+        let token = sqlx::query_as::<_, ApiToken>(
+            r#"
+            SELECT * FROM api_tokens
+            WHERE token_hash = ?1 AND (expires_at IS NULL OR expires_at > ?2)
+            "#,
+        )
+        .bind(hash_token(secret))
+        .bind(Utc::now())
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    pub async fn touch(pool: &SqlitePool, id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE api_tokens SET last_used_at = ?1 WHERE id = ?2")
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Deletes a token owned by the given user, returning whether one was found
+    pub async fn delete(pool: &SqlitePool, id: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM api_tokens WHERE id = ?1 AND user_id = ?2")
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.split(' ').any(|granted| granted == scope)
+    }
+}
@@ -0,0 +1,168 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AuditEvent {
+    pub id: String,
+    pub actor_id: Option<String>,
+    pub action: String,
+    pub target_user_id: Option<String>,
+    pub ip_address: Option<String>,
+    /// JSON object with action-specific context
+    pub details: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEvent {
+    pub async fn record(
+        pool: &SqlitePool,
+        actor_id: Option<&str>,
+        action: &str,
+        target_user_id: Option<&str>,
+        ip_address: Option<&str>,
+        details: serde_json::Value,
+    ) -> Result<AuditEvent, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+
+        let event = sqlx::query_as::<_, AuditEvent>(
+            r#"
+            INSERT INTO audit_events (id, actor_id, action, target_user_id, ip_address, details, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(actor_id)
+        .bind(action)
+        .bind(target_user_id)
+        .bind(ip_address)
+        .bind(details.to_string())
+        .bind(Utc::now())
+        .fetch_one(pool)
+        .await?;
+
+        Ok(event)
+    }
+
+    /// How many `action` events came from `ip_address` since `since`
+    pub async fn count_from_ip(
+        pool: &SqlitePool,
+        action: &str,
+        ip_address: &str,
+        since: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM audit_events
+            WHERE ip_address = ?1 AND action = ?2 AND created_at >= ?3
+            "#,
+        )
+        .bind(ip_address)
+        .bind(action)
+        .bind(since)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Events where the user is either the actor or the target
+    pub async fn find_for_user(
+        pool: &SqlitePool,
+        user_id: &str,
+    ) -> Result<Vec<AuditEvent>, sqlx::Error> {
+        let events = sqlx::query_as::<_, AuditEvent>(
+            r#"
+            SELECT * FROM audit_events
+            WHERE actor_id = ?1 OR target_user_id = ?1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(events)
+    }
+}
+
+// Audit event joined with the usernames of the accounts involved
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AuditEventRow {
+    pub id: String,
+    pub action: String,
+    pub actor_id: Option<String>,
+    pub actor_username: Option<String>,
+    pub target_user_id: Option<String>,
+    pub target_username: Option<String>,
+    pub ip_address: Option<String>,
+    pub details: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+pub struct AuditEventFilter {
+    pub action: Option<String>,
+    /// Matches events where the user is either the actor or the target
+    pub user_id: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl AuditEventRow {
+    pub async fn search(
+        pool: &SqlitePool,
+        filter: &AuditEventFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuditEventRow>, sqlx::Error> {
+        let events = sqlx::query_as::<_, AuditEventRow>(
+            r#"
+            SELECT e.id, e.action, e.actor_id, actor.username AS actor_username,
+                   e.target_user_id, target.username AS target_username,
+                   e.ip_address, e.details, e.created_at
+            FROM audit_events e
+            LEFT JOIN users actor ON actor.id = e.actor_id
+            LEFT JOIN users target ON target.id = e.target_user_id
+            WHERE (?1 IS NULL OR e.action = ?1)
+              AND (?2 IS NULL OR e.actor_id = ?2 OR e.target_user_id = ?2)
+              AND (?3 IS NULL OR e.created_at >= ?3)
+              AND (?4 IS NULL OR e.created_at < ?4)
+            ORDER BY e.created_at DESC
+            LIMIT ?5 OFFSET ?6
+            "#,
+        )
+        .bind(&filter.action)
+        .bind(&filter.user_id)
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    pub async fn count(pool: &SqlitePool, filter: &AuditEventFilter) -> Result<i64, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM audit_events
+            WHERE (?1 IS NULL OR action = ?1)
+              AND (?2 IS NULL OR actor_id = ?2 OR target_user_id = ?2)
+              AND (?3 IS NULL OR created_at >= ?3)
+              AND (?4 IS NULL OR created_at < ?4)
+            "#,
+        )
+        .bind(&filter.action)
+        .bind(&filter.user_id)
+        .bind(filter.since)
+        .bind(filter.until)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+}
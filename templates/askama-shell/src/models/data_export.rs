@@ -0,0 +1,155 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+
+pub const EXPORT_PENDING: &str = "pending";
+pub const EXPORT_READY: &str = "ready";
+pub const EXPORT_FAILED: &str = "failed";
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DataExport {
+    pub id: String,
+    pub user_id: String,
+    pub status: String,
+    /// The JSON bundle, only loaded when downloading
+    #[serde(skip_serializing)]
+    pub data: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl DataExport {
+    pub async fn create(pool: &SqlitePool, user_id: &str) -> Result<DataExport, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+
+        let export = sqlx::query_as::<_, DataExport>(
+            r#"
+            INSERT INTO data_exports (id, user_id, status, created_at)
+            VALUES (?1, ?2, ?3, ?4)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(EXPORT_PENDING)
+        .bind(Utc::now())
+        .fetch_one(pool)
+        .await?;
+
+        Ok(export)
+    }
+
+    /// Lists a user's exports, newest first, without their data
+    pub async fn find_by_user(
+        pool: &SqlitePool,
+        user_id: &str,
+    ) -> Result<Vec<DataExport>, sqlx::Error> {
+        let exports = sqlx::query_as::<_, DataExport>(
+            r#"
+            SELECT id, user_id, status, NULL AS data, created_at, completed_at, expires_at
+            FROM data_exports
+            WHERE user_id = ?1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(exports)
+    }
+
+    /// Loads a finished, unexpired export owned by the given user
+    pub async fn find_ready(
+        pool: &SqlitePool,
+        id: &str,
+        user_id: &str,
+    ) -> Result<Option<DataExport>, sqlx::Error> {
+        let export = sqlx::query_as::<_, DataExport>(
+            r#"
+            SELECT * FROM data_exports
+            WHERE id = ?1 AND user_id = ?2 AND status = ?3 AND expires_at > ?4
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(EXPORT_READY)
+        .bind(Utc::now())
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(export)
+    }
+
+    pub async fn complete(
+        pool: &SqlitePool,
+        id: &str,
+        data: &str,
+        ttl: Duration,
+    ) -> Result<DataExport, sqlx::Error> {
+        let now = Utc::now();
+
+        let export = sqlx::query_as::<_, DataExport>(
+            r#"
+            UPDATE data_exports
+            SET status = ?1, data = ?2, completed_at = ?3, expires_at = ?4
+            WHERE id = ?5
+            RETURNING *
+            "#,
+        )
+        .bind(EXPORT_READY)
+        .bind(data)
+        .bind(now)
+        .bind(now + ttl)
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(export)
+    }
+
+    pub async fn fail(pool: &SqlitePool, id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE data_exports SET status = ?1, completed_at = ?2 WHERE id = ?3")
+            .bind(EXPORT_FAILED)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Marks exports still pending from before `cutoff` failed, returning how
+    /// many there were
+    pub async fn fail_stale(pool: &SqlitePool, cutoff: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE data_exports SET status = ?1, completed_at = ?2 WHERE status = ?3 AND created_at < ?4",
+        )
+        .bind(EXPORT_FAILED)
+        .bind(Utc::now())
+        .bind(EXPORT_PENDING)
+        .bind(cutoff)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub fn is_downloadable(&self) -> bool {
+        self.status == EXPORT_READY
+            && self
+                .expires_at
+                .is_some_and(|expires_at| expires_at > Utc::now())
+    }
+
+    pub async fn delete_expired(pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM data_exports WHERE expires_at <= ?1")
+            .bind(Utc::now())
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,70 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::{Rng, distributions::Alphanumeric};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct EmailChangeToken {
+    pub token: String,
+    pub user_id: String,
+    pub new_email: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EmailChangeToken {
+    pub async fn create(
+        pool: &SqlitePool,
+        user_id: &str,
+        new_email: &str,
+        ttl: Duration,
+    ) -> Result<EmailChangeToken, sqlx::Error> {
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(48)
+            .map(char::from)
+            .collect();
+        let now = Utc::now();
+
+        let change_token = sqlx::query_as::<_, EmailChangeToken>(
+            r#"
+            INSERT INTO email_change_tokens (token, user_id, new_email, expires_at, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            RETURNING *
+            "#,
+        )
+        .bind(&token)
+        .bind(user_id)
+        .bind(new_email)
+        .bind(now + ttl)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(change_token)
+    }
+
+    pub async fn find_valid(
+        pool: &SqlitePool,
+        token: &str,
+    ) -> Result<Option<EmailChangeToken>, sqlx::Error> {
+        let change_token = sqlx::query_as::<_, EmailChangeToken>(
+            "SELECT * FROM email_change_tokens WHERE token = ?1 AND expires_at > ?2",
+        )
+        .bind(token)
+        .bind(Utc::now())
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(change_token)
+    }
+
+    pub async fn delete_for_user(pool: &SqlitePool, user_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM email_change_tokens WHERE user_id = ?1")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
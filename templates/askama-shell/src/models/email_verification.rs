@@ -0,0 +1,67 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::{Rng, distributions::Alphanumeric};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct EmailVerificationToken {
+    pub token: String,
+    pub user_id: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EmailVerificationToken {
+    pub async fn create(
+        pool: &SqlitePool,
+        user_id: &str,
+        ttl: Duration,
+    ) -> Result<EmailVerificationToken, sqlx::Error> {
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(48)
+            .map(char::from)
+            .collect();
+        let now = Utc::now();
+
+        let verification_token = sqlx::query_as::<_, EmailVerificationToken>(
+            r#"
+            INSERT INTO email_verification_tokens (token, user_id, expires_at, created_at)
+            VALUES (?1, ?2, ?3, ?4)
+            RETURNING *
+            "#,
+        )
+        .bind(&token)
+        .bind(user_id)
+        .bind(now + ttl)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(verification_token)
+    }
+
+    pub async fn find_valid(
+        pool: &SqlitePool,
+        token: &str,
+    ) -> Result<Option<EmailVerificationToken>, sqlx::Error> {
+        let verification_token = sqlx::query_as::<_, EmailVerificationToken>(
+            "SELECT * FROM email_verification_tokens WHERE token = ?1 AND expires_at > ?2",
+        )
+        .bind(token)
+        .bind(Utc::now())
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(verification_token)
+    }
+
+    pub async fn delete_for_user(pool: &SqlitePool, user_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM email_verification_tokens WHERE user_id = ?1")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
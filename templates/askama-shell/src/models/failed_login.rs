@@ -0,0 +1,61 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FailedLogin {
+    pub id: i64,
+    pub user_id: String,
+    pub attempted_at: DateTime<Utc>,
+}
+
+impl FailedLogin {
+    pub async fn record(pool: &SqlitePool, user_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO failed_login_attempts (user_id, attempted_at) VALUES (?1, ?2)")
+            .bind(user_id)
+            .bind(Utc::now())
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns when the account unlocks if at least `max_attempts` failures
+    /// happened within the last `window`.
+    pub async fn locked_until(
+        pool: &SqlitePool,
+        user_id: &str,
+        max_attempts: u32,
+        window: Duration,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let attempts = sqlx::query_as::<_, FailedLogin>(
+            r#"
+            SELECT * FROM failed_login_attempts
+            WHERE user_id = ?1 AND attempted_at > ?2
+            ORDER BY attempted_at DESC
+            LIMIT ?3
+            "#,
+        )
+        .bind(user_id)
+        .bind(Utc::now() - window)
+        .bind(max_attempts)
+        .fetch_all(pool)
+        .await?;
+
+        if max_attempts == 0 || attempts.len() < max_attempts as usize {
+            return Ok(None);
+        }
+
+        // The lock lifts once the oldest of the counted failures leaves the window
+        Ok(attempts.last().map(|attempt| attempt.attempted_at + window))
+    }
+
+    pub async fn clear_for_user(pool: &SqlitePool, user_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM failed_login_attempts WHERE user_id = ?1")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::{Rng, distributions::Alphanumeric};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteExecutor, SqlitePool};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Invitation {
+    pub id: String,
+    pub code: String,
+    pub created_by: Option<String>,
+    pub max_uses: i64,
+    pub use_count: i64,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateInvitationRequest {
+    #[serde(default = "default_max_uses")]
+    #[validate(range(min = 1, max = 1000))]
+    pub max_uses: i64,
+
+    // Leave unset for an invitation that never expires
+    #[serde(default = "default_expires_in_days")]
+    #[validate(range(min = 1, max = 365))]
+    pub expires_in_days: Option<i64>,
+}
+
+fn default_max_uses() -> i64 {
+    1
+}
+
+fn default_expires_in_days() -> Option<i64> {
+    Some(7)
+}
+
+impl Invitation {
+    pub async fn create(
+        pool: &SqlitePool,
+        created_by: Option<&str>,
+        max_uses: i64,
+        ttl: Option<Duration>,
+    ) -> Result<Invitation, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+        let code: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(16)
+            .map(char::from)
+            .collect();
+        let now = Utc::now();
+
+        let invitation = sqlx::query_as::<_, Invitation>(
+            r#"
+            INSERT INTO invitations (id, code, created_by, max_uses, use_count, expires_at, created_at)
+            VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(&code)
+        .bind(created_by)
+        .bind(max_uses)
+        .bind(ttl.map(|ttl| now + ttl))
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(invitation)
+    }
+
+    pub async fn find_all(pool: &SqlitePool) -> Result<Vec<Invitation>, sqlx::Error> {
+        let invitations =
+            sqlx::query_as::<_, Invitation>("SELECT * FROM invitations ORDER BY created_at DESC")
+                .fetch_all(pool)
+                .await?;
+
+        Ok(invitations)
+    }
+
+    pub async fn find_usable(
+        pool: &SqlitePool,
+        code: &str,
+    ) -> Result<Option<Invitation>, sqlx::Error> {
+        let invitation = sqlx::query_as::<_, Invitation>(
+            r#"
+            SELECT * FROM invitations
+            WHERE code = ?1 AND use_count < max_uses AND (expires_at IS NULL OR expires_at > ?2)
+            "#,
+        )
+        .bind(code)
+        .bind(Utc::now())
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(invitation)
+    }
+
+    // Claims one use of the invitation, failing if it was used up in the meantime
+    pub async fn redeem<'e>(
+        executor: impl SqliteExecutor<'e>,
+        id: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE invitations SET use_count = use_count + 1 WHERE id = ?1 AND use_count < max_uses",
+        )
+        .bind(id)
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
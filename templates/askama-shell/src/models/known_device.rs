@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct KnownDevice {
+    pub id: String,
+    pub user_id: String,
+    pub user_agent: String,
+    pub ip_address: String,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+impl KnownDevice {
+    // Returns true when the device hadn't been seen for this user before
+    pub async fn remember(
+        pool: &SqlitePool,
+        user_id: &str,
+        user_agent: &str,
+        ip_address: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let now = Utc::now();
+
+        let updated = sqlx::query(
+            r#"
+            UPDATE known_devices SET last_seen_at = ?1
+            WHERE user_id = ?2 AND user_agent = ?3 AND ip_address = ?4
+            "#,
+        )
+        .bind(now)
+        .bind(user_id)
+        .bind(user_agent)
+        .bind(ip_address)
+        .execute(pool)
+        .await?;
+
+        if updated.rows_affected() > 0 {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO known_devices (id, user_id, user_agent, ip_address, first_seen_at, last_seen_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(user_agent)
+        .bind(ip_address)
+        .bind(now)
+        .bind(now)
+        .execute(pool)
+        .await?;
+
+        Ok(true)
+    }
+
+    pub async fn find_by_user(
+        pool: &SqlitePool,
+        user_id: &str,
+    ) -> Result<Vec<KnownDevice>, sqlx::Error> {
+        let devices = sqlx::query_as::<_, KnownDevice>(
+            "SELECT * FROM known_devices WHERE user_id = ?1 ORDER BY first_seen_at",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(devices)
+    }
+
+    pub async fn count_for_user(pool: &SqlitePool, user_id: &str) -> Result<i64, sqlx::Error> {
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM known_devices WHERE user_id = ?1")
+                .bind(user_id)
+                .fetch_one(pool)
+                .await?;
+
+        Ok(count)
+    }
+}
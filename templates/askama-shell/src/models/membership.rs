@@ -0,0 +1,192 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+
+// A member's role within one organization, separate from the site-wide roles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MembershipRole {
+    Owner,
+    Admin,
+    Member,
+}
+
+impl MembershipRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MembershipRole::Owner => "owner",
+            MembershipRole::Admin => "admin",
+            MembershipRole::Member => "member",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<MembershipRole> {
+        match role {
+            "owner" => Some(MembershipRole::Owner),
+            "admin" => Some(MembershipRole::Admin),
+            "member" => Some(MembershipRole::Member),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Membership {
+    pub organization_id: String,
+    pub user_id: String,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// A membership joined with the member's account, for listing an organization's members
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct MemberRow {
+    pub user_id: String,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// A membership joined with its organization, for the user's data export
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct UserMembershipRow {
+    pub organization_id: String,
+    pub organization_name: String,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Membership {
+    pub fn role(&self) -> MembershipRole {
+        MembershipRole::parse(&self.role).unwrap_or(MembershipRole::Member)
+    }
+
+    // Owners and admins can invite and remove members
+    pub fn can_manage_members(&self) -> bool {
+        matches!(self.role(), MembershipRole::Owner | MembershipRole::Admin)
+    }
+
+    pub async fn find(
+        pool: &SqlitePool,
+        organization_id: &str,
+        user_id: &str,
+    ) -> Result<Option<Membership>, sqlx::Error> {
+        let membership = sqlx::query_as::<_, Membership>(
+            "SELECT * FROM memberships WHERE organization_id = ?1 AND user_id = ?2",
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(membership)
+    }
+
+    // The user's oldest membership, used when no organization has been picked yet
+    pub async fn find_first_for_user(
+        pool: &SqlitePool,
+        user_id: &str,
+    ) -> Result<Option<Membership>, sqlx::Error> {
+        let membership = sqlx::query_as::<_, Membership>(
+            "SELECT * FROM memberships WHERE user_id = ?1 ORDER BY created_at LIMIT 1",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(membership)
+    }
+
+    // Every organization the user belongs to, oldest membership first
+    pub async fn find_for_user(
+        pool: &SqlitePool,
+        user_id: &str,
+    ) -> Result<Vec<UserMembershipRow>, sqlx::Error> {
+        let memberships = sqlx::query_as::<_, UserMembershipRow>(
+            r#"
+            SELECT memberships.organization_id, organizations.name AS organization_name,
+                   memberships.role, memberships.created_at
+            FROM memberships
+            JOIN organizations ON organizations.id = memberships.organization_id
+            WHERE memberships.user_id = ?1
+            ORDER BY memberships.created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(memberships)
+    }
+
+    pub async fn find_members(
+        pool: &SqlitePool,
+        organization_id: &str,
+    ) -> Result<Vec<MemberRow>, sqlx::Error> {
+        let members = sqlx::query_as::<_, MemberRow>(
+            r#"
+            SELECT memberships.user_id, users.username, users.email, memberships.role,
+                   memberships.created_at
+            FROM memberships
+            JOIN users ON users.id = memberships.user_id
+            WHERE memberships.organization_id = ?1
+            ORDER BY memberships.created_at
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(members)
+    }
+
+    // Adds the user to the organization, keeping their existing role if they're already a member
+    pub async fn add(
+        pool: &SqlitePool,
+        organization_id: &str,
+        user_id: &str,
+        role: MembershipRole,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO memberships (organization_id, user_id, role, created_at) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .bind(role.as_str())
+        .bind(Utc::now())
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove(
+        pool: &SqlitePool,
+        organization_id: &str,
+        user_id: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM memberships WHERE organization_id = ?1 AND user_id = ?2")
+                .bind(organization_id)
+                .bind(user_id)
+                .execute(pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn count_owners(
+        pool: &SqlitePool,
+        organization_id: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM memberships WHERE organization_id = ?1 AND role = 'owner'",
+        )
+        .bind(organization_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+}
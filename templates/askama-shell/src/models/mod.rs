@@ -1,3 +1,41 @@
+pub mod api_token;
+pub mod audit_event;
+pub mod data_export;
+pub mod email_change;
+pub mod email_verification;
+pub mod failed_login;
+pub mod invitation;
+pub mod known_device;
+pub mod membership;
+pub mod note;
+pub mod organization;
+pub mod organization_invitation;
+pub mod policy;
+pub mod refresh_token;
+pub mod role;
+pub mod search;
 pub mod user;
+pub mod user_session;
+pub mod webauthn_credential;
+pub mod webhook;
 
+pub use api_token::*;
+pub use audit_event::*;
+pub use data_export::*;
+pub use email_change::*;
+pub use email_verification::*;
+pub use failed_login::*;
+pub use invitation::*;
+pub use known_device::*;
+pub use membership::*;
+pub use note::*;
+pub use organization::*;
+pub use organization_invitation::*;
+pub use policy::*;
+pub use refresh_token::*;
+pub use role::*;
+pub use search::*;
 pub use user::*;
+pub use user_session::*;
+pub use webauthn_credential::*;
+pub use webhook::*;
@@ -0,0 +1,203 @@
+use super::search::contains_pattern;
+use crate::pagination::{Paginated, SortKey};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+/// A note owned by a single user
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Note {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub user_id: String,
+    pub title: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateNoteRequest {
+    #[validate(length(min = 1, max = 200, message = "Title must be 1 to 200 characters"))]
+    pub title: String,
+
+    #[serde(default)]
+    #[validate(length(max = 10000, message = "Body must be at most 10,000 characters"))]
+    pub body: String,
+}
+
+/// Columns notes can be sorted by
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteSort {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Title,
+}
+
+impl SortKey for NoteSort {
+    const TABLE: &'static str = "notes";
+
+    fn column(self) -> &'static str {
+        match self {
+            NoteSort::CreatedAt => "created_at",
+            NoteSort::UpdatedAt => "updated_at",
+            NoteSort::Title => "title",
+        }
+    }
+}
+
+/// Fields left out are kept as they are
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateNoteRequest {
+    #[validate(length(min = 1, max = 200, message = "Title must be 1 to 200 characters"))]
+    pub title: Option<String>,
+
+    #[validate(length(max = 10000, message = "Body must be at most 10,000 characters"))]
+    pub body: Option<String>,
+}
+
+impl Note {
+    pub async fn create(
+        pool: &SqlitePool,
+        user_id: &str,
+        title: &str,
+        body: &str,
+    ) -> Result<Note, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        let note = sqlx::query_as::<_, Note>(
+            r#"
+            INSERT INTO notes (id, user_id, title, body, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(title)
+        .bind(body)
+        .bind(now)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(note)
+    }
+
+    /// Looks a note up by ID, only if `user_id` owns it
+    pub async fn find_for_user(
+        pool: &SqlitePool,
+        id: &str,
+        user_id: &str,
+    ) -> Result<Option<Note>, sqlx::Error> {
+        let note = sqlx::query_as::<_, Note>("SELECT * FROM notes WHERE id = ?1 AND user_id = ?2")
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(note)
+    }
+
+    /// Every note the user owns, oldest first, for their data export
+    pub async fn find_all_for_user(
+        pool: &SqlitePool,
+        user_id: &str,
+    ) -> Result<Vec<Note>, sqlx::Error> {
+        let notes = sqlx::query_as::<_, Note>(
+            "SELECT * FROM notes WHERE user_id = ?1 ORDER BY created_at, id",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(notes)
+    }
+
+    /// One page of the user's notes, optionally only those whose title
+    /// contains `search`
+    pub async fn list_for_user(
+        pool: &SqlitePool,
+        user_id: &str,
+        search: Option<&str>,
+        page: &Paginated<NoteSort>,
+    ) -> Result<Vec<Note>, sqlx::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM notes WHERE user_id = ");
+        query.push_bind(user_id);
+        if let Some(search) = search {
+            query
+                .push(" AND title LIKE ")
+                .push_bind(contains_pattern(search))
+                .push(" ESCAPE '\\'");
+        }
+        page.push_keyset_condition(&mut query);
+        page.push_order_and_limit(&mut query);
+
+        let notes = query.build_query_as::<Note>().fetch_all(pool).await?;
+
+        Ok(notes)
+    }
+
+    pub async fn count_for_user(
+        pool: &SqlitePool,
+        user_id: &str,
+        search: Option<&str>,
+    ) -> Result<i64, sqlx::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM notes WHERE user_id = ");
+        query.push_bind(user_id);
+        if let Some(search) = search {
+            query
+                .push(" AND title LIKE ")
+                .push_bind(contains_pattern(search))
+                .push(" ESCAPE '\\'");
+        }
+
+        let count: i64 = query.build_query_scalar().fetch_one(pool).await?;
+
+        Ok(count)
+    }
+
+    /// Returns the updated note, or `None` if the user doesn't own it
+    pub async fn update(
+        pool: &SqlitePool,
+        id: &str,
+        user_id: &str,
+        title: Option<&str>,
+        body: Option<&str>,
+    ) -> Result<Option<Note>, sqlx::Error> {
+        let note = sqlx::query_as::<_, Note>(
+            r#"
+            UPDATE notes
+            SET title = COALESCE(?3, title), body = COALESCE(?4, body), updated_at = ?5
+            WHERE id = ?1 AND user_id = ?2
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(title)
+        .bind(body)
+        .bind(Utc::now())
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(note)
+    }
+
+    /// Returns whether a note the user owns was deleted
+    pub async fn delete(pool: &SqlitePool, id: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM notes WHERE id = ?1 AND user_id = ?2")
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,98 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::MembershipRole;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Organization {
+    pub id: String,
+    pub name: String,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateOrganizationRequest {
+    #[validate(length(min = 2, max = 80, message = "Name must be 2 to 80 characters"))]
+    pub name: String,
+}
+
+impl Organization {
+    /// Creates an organization with `owner_id` as its first owner.
+    pub async fn create(
+        pool: &SqlitePool,
+        name: &str,
+        owner_id: &str,
+    ) -> Result<Organization, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        let mut tx = pool.begin().await?;
+
+        let organization = sqlx::query_as::<_, Organization>(
+            r#"
+            INSERT INTO organizations (id, name, created_by, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(name)
+        .bind(owner_id)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO memberships (organization_id, user_id, role, created_at) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(&id)
+        .bind(owner_id)
+        .bind(MembershipRole::Owner.as_str())
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(organization)
+    }
+
+    pub async fn find_by_id(
+        pool: &SqlitePool,
+        id: &str,
+    ) -> Result<Option<Organization>, sqlx::Error> {
+        let organization =
+            sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE id = ?1")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?;
+
+        Ok(organization)
+    }
+
+    // Organizations the user belongs to, oldest membership first
+    pub async fn find_for_user(
+        pool: &SqlitePool,
+        user_id: &str,
+    ) -> Result<Vec<Organization>, sqlx::Error> {
+        let organizations = sqlx::query_as::<_, Organization>(
+            r#"
+            SELECT organizations.* FROM organizations
+            JOIN memberships ON memberships.organization_id = organizations.id
+            WHERE memberships.user_id = ?1
+            ORDER BY memberships.created_at, organizations.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(organizations)
+    }
+}
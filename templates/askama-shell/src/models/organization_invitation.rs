@@ -0,0 +1,127 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::{Rng, distributions::Alphanumeric};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use validator::Validate;
+
+use super::MembershipRole;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct OrganizationInvitation {
+    #[serde(skip_serializing)]
+    pub token: String,
+    pub organization_id: String,
+    pub email: String,
+    pub role: String,
+    pub invited_by: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct InviteMemberRequest {
+    #[validate(email(message = "Please enter a valid email address"))]
+    pub email: String,
+
+    // Owners can only be made by the owners themselves, not through an invitation
+    #[serde(default = "default_invite_role")]
+    pub role: MembershipRole,
+}
+
+fn default_invite_role() -> MembershipRole {
+    MembershipRole::Member
+}
+
+impl OrganizationInvitation {
+    pub fn role(&self) -> MembershipRole {
+        MembershipRole::parse(&self.role).unwrap_or(MembershipRole::Member)
+    }
+
+    /// Invites `email` to the organization, replacing any earlier invitation
+    /// for the same address so only the newest link works.
+    pub async fn create(
+        pool: &SqlitePool,
+        organization_id: &str,
+        email: &str,
+        role: MembershipRole,
+        invited_by: &str,
+        ttl: Duration,
+    ) -> Result<OrganizationInvitation, sqlx::Error> {
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(48)
+            .map(char::from)
+            .collect();
+        let now = Utc::now();
+
+        sqlx::query(
+            "DELETE FROM organization_invitations WHERE organization_id = ?1 AND email = ?2 COLLATE NOCASE",
+        )
+        .bind(organization_id)
+        .bind(email)
+        .execute(pool)
+        .await?;
+
+        let invitation = sqlx::query_as::<_, OrganizationInvitation>(
+            r#"
+            INSERT INTO organization_invitations (token, organization_id, email, role, invited_by, expires_at, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            RETURNING *
+            "#,
+        )
+        .bind(&token)
+        .bind(organization_id)
+        .bind(email)
+        .bind(role.as_str())
+        .bind(invited_by)
+        .bind(now + ttl)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(invitation)
+    }
+
+    pub async fn find_valid(
+        pool: &SqlitePool,
+        token: &str,
+    ) -> Result<Option<OrganizationInvitation>, sqlx::Error> {
+        let invitation = sqlx::query_as::<_, OrganizationInvitation>(
+            "SELECT * FROM organization_invitations WHERE token = ?1 AND expires_at > ?2",
+        )
+        .bind(token)
+        .bind(Utc::now())
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(invitation)
+    }
+
+    pub async fn find_pending(
+        pool: &SqlitePool,
+        organization_id: &str,
+    ) -> Result<Vec<OrganizationInvitation>, sqlx::Error> {
+        let invitations = sqlx::query_as::<_, OrganizationInvitation>(
+            r#"
+            SELECT * FROM organization_invitations
+            WHERE organization_id = ?1 AND expires_at > ?2
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(organization_id)
+        .bind(Utc::now())
+        .fetch_all(pool)
+        .await?;
+
+        Ok(invitations)
+    }
+
+    pub async fn delete(pool: &SqlitePool, token: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM organization_invitations WHERE token = ?1")
+            .bind(token)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteExecutor, SqlitePool};
+
+// Documents users must agree to; each kind is versioned independently
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyKind {
+    Terms,
+    Privacy,
+}
+
+impl PolicyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PolicyKind::Terms => "terms",
+            PolicyKind::Privacy => "privacy",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<PolicyKind> {
+        match kind {
+            "terms" => Some(PolicyKind::Terms),
+            "privacy" => Some(PolicyKind::Privacy),
+            _ => None,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            PolicyKind::Terms => "Terms of Service",
+            PolicyKind::Privacy => "Privacy Policy",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PolicyDocument {
+    /// `<kind>-v<version>`
+    pub id: String,
+    pub kind: String,
+    pub version: i64,
+    pub body: String,
+    pub published_at: DateTime<Utc>,
+}
+
+// An acceptance joined with the document, for the admin panel and data exports
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PolicyAcceptanceRow {
+    pub kind: String,
+    pub version: i64,
+    pub ip_address: Option<String>,
+    pub accepted_at: DateTime<Utc>,
+}
+
+impl PolicyDocument {
+    pub fn title(&self) -> &'static str {
+        PolicyKind::parse(&self.kind)
+            .map(|kind| kind.title())
+            .unwrap_or("Policy")
+    }
+
+    /// The latest version of `kind`
+    pub async fn find_current(
+        pool: &SqlitePool,
+        kind: PolicyKind,
+    ) -> Result<Option<PolicyDocument>, sqlx::Error> {
+        let document = sqlx::query_as::<_, PolicyDocument>(
+            "SELECT * FROM policy_documents WHERE kind = ?1 ORDER BY version DESC LIMIT 1",
+        )
+        .bind(kind.as_str())
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(document)
+    }
+
+    /// The latest version of each kind
+    pub async fn find_all_current(pool: &SqlitePool) -> Result<Vec<PolicyDocument>, sqlx::Error> {
+        let documents = sqlx::query_as::<_, PolicyDocument>(
+            r#"
+            SELECT * FROM policy_documents d
+            WHERE version = (SELECT MAX(version) FROM policy_documents WHERE kind = d.kind)
+            ORDER BY kind
+            "#,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(documents)
+    }
+
+    /// Latest versions the user hasn't accepted yet
+    pub async fn find_unaccepted(
+        pool: &SqlitePool,
+        user_id: &str,
+    ) -> Result<Vec<PolicyDocument>, sqlx::Error> {
+        let documents = sqlx::query_as::<_, PolicyDocument>(
+            r#"
+            SELECT * FROM policy_documents d
+            WHERE version = (SELECT MAX(version) FROM policy_documents WHERE kind = d.kind)
+              AND NOT EXISTS (
+                  SELECT 1 FROM policy_acceptances a
+                  WHERE a.policy_document_id = d.id AND a.user_id = ?1
+              )
+            ORDER BY kind
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(documents)
+    }
+
+    /// Adds the next version of `kind`. Every user has to accept it before
+    /// they can keep using the app.
+    pub async fn publish(
+        pool: &SqlitePool,
+        kind: PolicyKind,
+        body: &str,
+    ) -> Result<PolicyDocument, sqlx::Error> {
+        let document = sqlx::query_as::<_, PolicyDocument>(
+            r#"
+            INSERT INTO policy_documents (id, kind, version, body, published_at)
+            SELECT ?1 || '-v' || (COALESCE(MAX(version), 0) + 1), ?1, COALESCE(MAX(version), 0) + 1, ?2, ?3
+            FROM policy_documents WHERE kind = ?1
+            RETURNING *
+            "#,
+        )
+        .bind(kind.as_str())
+        .bind(body)
+        .bind(Utc::now())
+        .fetch_one(pool)
+        .await?;
+
+        Ok(document)
+    }
+}
+
+pub struct PolicyAcceptance;
+
+impl PolicyAcceptance {
+    pub async fn record<'e>(
+        executor: impl SqliteExecutor<'e>,
+        user_id: &str,
+        policy_document_id: &str,
+        ip_address: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO policy_acceptances (user_id, policy_document_id, ip_address, accepted_at)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+        )
+        .bind(user_id)
+        .bind(policy_document_id)
+        .bind(ip_address)
+        .bind(Utc::now())
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Every version the user accepted, newest first
+    pub async fn find_for_user(
+        pool: &SqlitePool,
+        user_id: &str,
+    ) -> Result<Vec<PolicyAcceptanceRow>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, PolicyAcceptanceRow>(
+            r#"
+            SELECT d.kind, d.version, a.ip_address, a.accepted_at
+            FROM policy_acceptances a
+            JOIN policy_documents d ON d.id = a.policy_document_id
+            WHERE a.user_id = ?1
+            ORDER BY a.accepted_at DESC, d.kind
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows)
+    }
+}
+
+impl PolicyAcceptanceRow {
+    pub fn title(&self) -> &'static str {
+        PolicyKind::parse(&self.kind)
+            .map(|kind| kind.title())
+            .unwrap_or("Policy")
+    }
+}
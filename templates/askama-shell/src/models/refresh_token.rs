@@ -0,0 +1,84 @@
+use super::api_token::hash_token;
+use chrono::{DateTime, Duration, Utc};
+use rand::{Rng, distributions::Alphanumeric};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RefreshToken {
+    pub id: String,
+    pub user_id: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RefreshToken {
+    /// Creates a refresh token and returns it with its secret, which is never stored
+    pub async fn create(
+        pool: &SqlitePool,
+        user_id: &str,
+        ttl: Duration,
+    ) -> Result<(RefreshToken, String), sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+        let secret: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(64)
+            .map(char::from)
+            .collect();
+        let now = Utc::now();
+
+        let refresh_token = sqlx::query_as::<_, RefreshToken>(
+            r#"
+            INSERT INTO refresh_tokens (id, user_id, token_hash, expires_at, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(hash_token(&secret))
+        .bind(now + ttl)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok((refresh_token, secret))
+    }
+
+    /// Deletes and returns a valid refresh token, so each one can only be used once
+    pub async fn consume(
+        pool: &SqlitePool,
+        secret: &str,
+    ) -> Result<Option<RefreshToken>, sqlx::Error> {
+        let refresh_token = sqlx::query_as::<_, RefreshToken>(
+            "DELETE FROM refresh_tokens WHERE token_hash = ?1 AND expires_at > ?2 RETURNING *",
+        )
+        .bind(hash_token(secret))
+        .bind(Utc::now())
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(refresh_token)
+    }
+
+    pub async fn delete_for_user(pool: &SqlitePool, user_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM refresh_tokens WHERE user_id = ?1")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_expired(pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM refresh_tokens WHERE expires_at <= ?1")
+            .bind(Utc::now())
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteExecutor, SqlitePool};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Role {
+    pub name: String,
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Role {
+    pub async fn find_all(pool: &SqlitePool) -> Result<Vec<Role>, sqlx::Error> {
+        let roles = sqlx::query_as::<_, Role>("SELECT * FROM roles ORDER BY name")
+            .fetch_all(pool)
+            .await?;
+
+        Ok(roles)
+    }
+
+    pub async fn find_for_user(pool: &SqlitePool, user_id: &str) -> Result<Vec<Role>, sqlx::Error> {
+        let roles = sqlx::query_as::<_, Role>(
+            r#"
+            SELECT roles.* FROM roles
+            JOIN user_roles ON user_roles.role = roles.name
+            WHERE user_roles.user_id = ?1
+            ORDER BY roles.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(roles)
+    }
+
+    pub async fn user_has_role(
+        pool: &SqlitePool,
+        user_id: &str,
+        role: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM user_roles WHERE user_id = ?1 AND role = ?2")
+                .bind(user_id)
+                .bind(role)
+                .fetch_one(pool)
+                .await?;
+
+        Ok(count > 0)
+    }
+
+    pub async fn grant<'e>(
+        executor: impl SqliteExecutor<'e>,
+        user_id: &str,
+        role: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO user_roles (user_id, role, granted_at) VALUES (?1, ?2, ?3)",
+        )
+        .bind(user_id)
+        .bind(role)
+        .bind(Utc::now())
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn revoke(pool: &SqlitePool, user_id: &str, role: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM user_roles WHERE user_id = ?1 AND role = ?2")
+            .bind(user_id)
+            .bind(role)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
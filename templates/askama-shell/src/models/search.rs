@@ -0,0 +1,198 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, SqlitePool};
+
+// Wrapped around matches by the FTS5 highlight functions. Control characters
+// can't be typed into a form, so they never come from the text itself.
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+// Longer queries are cut off rather than rejected
+const MAX_QUERY_TERMS: usize = 8;
+
+/// A run of text that is or isn't part of a match, so templates can mark up
+/// matches while still escaping what users wrote
+#[derive(Debug, Clone, Serialize)]
+pub struct Fragment {
+    pub text: String,
+    pub matched: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NoteMatch {
+    pub id: String,
+    pub title: Vec<Fragment>,
+    /// Part of the body around the matches, empty if only the title matched
+    pub snippet: Vec<Fragment>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserMatch {
+    /// Unhighlighted, for building profile links
+    pub username: String,
+    pub highlighted_username: Vec<Fragment>,
+    /// Empty when the user hasn't set one
+    pub display_name: Vec<Fragment>,
+}
+
+#[derive(FromRow)]
+struct NoteMatchRow {
+    id: String,
+    title: String,
+    snippet: String,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(FromRow)]
+struct UserMatchRow {
+    username: String,
+    highlighted_username: String,
+    display_name: String,
+}
+
+/// Full-text search over the FTS5 tables the migrations keep in sync with
+/// notes and users
+pub struct Search;
+
+impl Search {
+    /// Best matches first, among notes owned by `user_id`
+    pub async fn notes(
+        pool: &SqlitePool,
+        user_id: &str,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<NoteMatch>, sqlx::Error> {
+        let Some(query) = match_expression(query) else {
+            return Ok(Vec::new());
+        };
+
+        // Title matches weigh more than body matches
+        let rows = sqlx::query_as::<_, NoteMatchRow>(
+            r#"
+            SELECT notes.id,
+                   highlight(notes_fts, 1, char(2), char(3)) AS title,
+                   snippet(notes_fts, 2, char(2), char(3), '…', 24) AS snippet,
+                   notes.updated_at
+            FROM notes_fts
+            JOIN notes ON notes.id = notes_fts.note_id
+            WHERE notes_fts MATCH ?1 AND notes.user_id = ?2
+            ORDER BY bm25(notes_fts, 0.0, 10.0, 1.0)
+            LIMIT ?3
+            "#,
+        )
+        .bind(query)
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let snippet = if row.snippet.contains(MATCH_START) {
+                    fragments(&row.snippet)
+                } else {
+                    Vec::new()
+                };
+                NoteMatch {
+                    id: row.id,
+                    title: fragments(&row.title),
+                    snippet,
+                    updated_at: row.updated_at,
+                }
+            })
+            .collect())
+    }
+
+    /// Best matches first, by username or display name, among active accounts
+    pub async fn users(
+        pool: &SqlitePool,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<UserMatch>, sqlx::Error> {
+        let Some(query) = match_expression(query) else {
+            return Ok(Vec::new());
+        };
+
+        let rows = sqlx::query_as::<_, UserMatchRow>(
+            r#"
+            SELECT users.username,
+                   highlight(users_fts, 1, char(2), char(3)) AS highlighted_username,
+                   highlight(users_fts, 2, char(2), char(3)) AS display_name
+            FROM users_fts
+            JOIN users ON users.id = users_fts.user_id
+            WHERE users_fts MATCH ?1 AND users.is_active = 1 AND users.deleted_at IS NULL
+            ORDER BY rank
+            LIMIT ?2
+            "#,
+        )
+        .bind(query)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| UserMatch {
+                username: row.username,
+                highlighted_username: fragments(&row.highlighted_username),
+                display_name: fragments(&row.display_name),
+            })
+            .collect())
+    }
+}
+
+/// Turns what the user typed into an FTS5 query matching rows that contain
+/// every word, each as a prefix. Quoting each word keeps FTS5 operators and
+/// punctuation from being parsed as query syntax.
+fn match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .filter(|term| term.chars().any(char::is_alphanumeric))
+        .take(MAX_QUERY_TERMS)
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Turns what the user typed into a `LIKE ... ESCAPE '\'` pattern matching
+/// values that contain it, with `%`, `_` and `\` matching themselves
+pub(crate) fn contains_pattern(query: &str) -> String {
+    let escaped = query
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+fn fragments(highlighted: &str) -> Vec<Fragment> {
+    let mut fragments = Vec::new();
+    let mut rest = highlighted;
+    while let Some(start) = rest.find(MATCH_START) {
+        if start > 0 {
+            fragments.push(Fragment {
+                text: rest[..start].to_string(),
+                matched: false,
+            });
+        }
+        rest = &rest[start + MATCH_START.len_utf8()..];
+        let end = rest.find(MATCH_END).unwrap_or(rest.len());
+        fragments.push(Fragment {
+            text: rest[..end].to_string(),
+            matched: true,
+        });
+        rest = rest.get(end + MATCH_END.len_utf8()..).unwrap_or_default();
+    }
+    if !rest.is_empty() {
+        fragments.push(Fragment {
+            text: rest.to_string(),
+            matched: false,
+        });
+    }
+    fragments
+}
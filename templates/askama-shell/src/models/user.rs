@@ -1,6 +1,8 @@
+use super::search::contains_pattern;
+use crate::pagination::{Paginated, SortKey};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, SqlitePool};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqliteExecutor, SqlitePool};
 use uuid::Uuid;
 use validator::Validate;
 
@@ -14,6 +16,13 @@ pub struct User {
     pub updated_at: DateTime<Utc>,
     pub email_verified: bool,
     pub is_active: bool,
+    pub pending_email: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub notify_new_sign_in: bool,
+    pub invitation_id: Option<String>,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_updated_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate)]
@@ -29,18 +38,68 @@ pub struct CreateUserRequest {
 
     #[validate(must_match(other = "password"))]
     pub confirm_password: String,
+
+    // Required when SIGNUP_MODE=invite_only
+    #[serde(default)]
+    pub invite_code: Option<String>,
+
+    // Agreement to the current Terms of Service and Privacy Policy
+    #[serde(default)]
+    pub accept_terms: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct LoginRequest {
+    // Either an email address or a username
+    #[serde(alias = "email")]
+    #[validate(length(min = 1, max = 254, message = "Email or username is required"))]
+    pub identifier: String,
+
+    #[validate(length(min = 1))]
+    pub password: String,
+
+    #[serde(default)]
+    pub remember: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct UpdateProfileRequest {
     #[validate(email)]
     pub email: String,
 
+    #[validate(length(min = 3, max = 50))]
+    pub username: String,
+
+    #[serde(default)]
+    #[validate(length(max = 50, message = "Display name must be at most 50 characters"))]
+    pub display_name: Option<String>,
+
+    #[serde(default)]
+    #[validate(length(max = 500, message = "Bio must be at most 500 characters"))]
+    pub bio: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1))]
+    pub current_password: String,
+
+    #[validate(length(min = 8, max = 128))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct DeleteAccountRequest {
     #[validate(length(min = 1))]
     pub password: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Deserialize)]
+pub struct UpdateNotificationsRequest {
+    pub notify_new_sign_in: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserResponse {
     pub id: String,
     pub email: String,
@@ -48,10 +107,42 @@ pub struct UserResponse {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub email_verified: bool,
+    pub pending_email: Option<String>,
+    pub notify_new_sign_in: bool,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    /// Username of the admin currently signed in as this user, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impersonated_by: Option<String>,
+}
+
+/// Columns the admin user list can be sorted by
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSort {
+    #[default]
+    CreatedAt,
+    Username,
+    Email,
+}
+
+impl SortKey for UserSort {
+    const TABLE: &'static str = "users";
+    const DEFAULT_PER_PAGE: i64 = 25;
+
+    fn column(self) -> &'static str {
+        match self {
+            UserSort::CreatedAt => "created_at",
+            UserSort::Username => "username",
+            UserSort::Email => "email",
+        }
+    }
 }
 
 impl From<User> for UserResponse {
     fn from(user: User) -> Self {
+        let avatar_url = user.avatar_url();
         Self {
             id: user.id,
             email: user.email,
@@ -59,13 +150,21 @@ impl From<User> for UserResponse {
             created_at: user.created_at,
             updated_at: user.updated_at,
             email_verified: user.email_verified,
+            pending_email: user.pending_email,
+            notify_new_sign_in: user.notify_new_sign_in,
+            display_name: user.display_name,
+            bio: user.bio,
+            avatar_url,
+            impersonated_by: None,
         }
     }
 }
 
 impl User {
-    pub async fn create(
-        pool: &SqlitePool,
+    /// Takes the pool or, to create the account along with other rows, a
+    /// transaction
+    pub async fn create<'e>(
+        executor: impl SqliteExecutor<'e>,
         email: String,
         username: String,
         password_hash: String,
@@ -88,7 +187,7 @@ impl User {
         .bind(now)
         .bind(false)
         .bind(true)
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await?;
 
         Ok(user)
@@ -115,6 +214,24 @@ impl User {
         Ok(user)
     }
 
+    /// Looks up several users in one query; missing IDs are skipped
+    pub async fn find_by_ids(pool: &SqlitePool, ids: &[String]) -> Result<Vec<User>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM users WHERE id IN (");
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(id);
+        }
+        separated.push_unseparated(")");
+
+        let users = query.build_query_as::<User>().fetch_all(pool).await?;
+
+        Ok(users)
+    }
+
     pub async fn find_by_username(
         pool: &SqlitePool,
         username: &str,
@@ -127,6 +244,55 @@ impl User {
         Ok(user)
     }
 
+    pub async fn find_by_email_or_username(
+        pool: &SqlitePool,
+        identifier: &str,
+    ) -> Result<Option<User>, sqlx::Error> {
+        // Prefer an email match in case someone's username looks like another user's email
+        let user = sqlx::query_as::<_, User>(
+            "SELECT * FROM users WHERE email = ?1 OR username = ?1 ORDER BY email = ?1 DESC LIMIT 1",
+        )
+        .bind(identifier)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    // Case-insensitive match on email or username, excluding deleted accounts
+    pub async fn search(
+        pool: &SqlitePool,
+        query: &str,
+        page: &Paginated<UserSort>,
+    ) -> Result<Vec<User>, sqlx::Error> {
+        let pattern = contains_pattern(query);
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            "SELECT * FROM users WHERE deleted_at IS NULL AND (email LIKE ",
+        );
+        builder
+            .push_bind(pattern.clone())
+            .push(" ESCAPE '\\' OR username LIKE ")
+            .push_bind(pattern)
+            .push(" ESCAPE '\\')");
+        page.push_keyset_condition(&mut builder);
+        page.push_order_and_limit(&mut builder);
+
+        let users = builder.build_query_as::<User>().fetch_all(pool).await?;
+
+        Ok(users)
+    }
+
+    pub async fn count_search(pool: &SqlitePool, query: &str) -> Result<i64, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM users WHERE deleted_at IS NULL AND (email LIKE ?1 ESCAPE '\\' OR username LIKE ?1 ESCAPE '\\')",
+        )
+        .bind(contains_pattern(query))
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+
     pub async fn update_last_login(pool: &SqlitePool, id: &str) -> Result<(), sqlx::Error> {
         sqlx::query("UPDATE users SET updated_at = ?1 WHERE id = ?2")
             .bind(Utc::now())
@@ -137,6 +303,145 @@ impl User {
         Ok(())
     }
 
+    pub async fn update_password_hash(
+        pool: &SqlitePool,
+        id: &str,
+        password_hash: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE users SET password_hash = ?1, updated_at = ?2 WHERE id = ?3")
+            .bind(password_hash)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_username(
+        pool: &SqlitePool,
+        id: &str,
+        username: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE users SET username = ?1, updated_at = ?2 WHERE id = ?3")
+            .bind(username)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_profile_details(
+        pool: &SqlitePool,
+        id: &str,
+        display_name: Option<&str>,
+        bio: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE users SET display_name = ?1, bio = ?2, updated_at = ?3 WHERE id = ?4")
+            .bind(display_name)
+            .bind(bio)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_avatar_updated_at(
+        pool: &SqlitePool,
+        id: &str,
+        avatar_updated_at: Option<DateTime<Utc>>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE users SET avatar_updated_at = ?1, updated_at = ?2 WHERE id = ?3")
+            .bind(avatar_updated_at)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Versioned URL of the user's avatar, so browsers can cache it indefinitely
+    pub fn avatar_url(&self) -> Option<String> {
+        self.avatar_updated_at
+            .map(|updated_at| format!("/avatars/{}?v={}", self.id, updated_at.timestamp()))
+    }
+
+    /// The display name when set, otherwise the username
+    pub fn public_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.username)
+    }
+
+    pub async fn set_pending_email(
+        pool: &SqlitePool,
+        id: &str,
+        pending_email: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE users SET pending_email = ?1, updated_at = ?2 WHERE id = ?3")
+            .bind(pending_email)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Confirming via a link sent to the new address also verifies it
+    pub async fn confirm_pending_email(
+        pool: &SqlitePool,
+        id: &str,
+        new_email: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET email = pending_email, pending_email = NULL, email_verified = TRUE, updated_at = ?1
+            WHERE id = ?2 AND pending_email = ?3
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .bind(new_email)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn update_notify_new_sign_in(
+        pool: &SqlitePool,
+        id: &str,
+        notify_new_sign_in: bool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE users SET notify_new_sign_in = ?1, updated_at = ?2 WHERE id = ?3")
+            .bind(notify_new_sign_in)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_invitation<'e>(
+        executor: impl SqliteExecutor<'e>,
+        id: &str,
+        invitation_id: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE users SET invitation_id = ?1 WHERE id = ?2")
+            .bind(invitation_id)
+            .bind(id)
+            .execute(executor)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn verify_email(pool: &SqlitePool, id: &str) -> Result<(), sqlx::Error> {
         sqlx::query("UPDATE users SET email_verified = TRUE, updated_at = ?1 WHERE id = ?2")
             .bind(Utc::now())
@@ -156,4 +461,45 @@ impl User {
 
         Ok(())
     }
+
+    pub async fn activate(pool: &SqlitePool, id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE users SET is_active = TRUE, updated_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn soft_delete(pool: &SqlitePool, id: &str) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        sqlx::query(
+            "UPDATE users SET is_active = FALSE, deleted_at = ?1, updated_at = ?1 WHERE id = ?2",
+        )
+        .bind(now)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Hard-deletes accounts soft-deleted before `cutoff`, returning their IDs
+    /// so files stored outside the database can go too
+    pub async fn purge_deleted_before(
+        pool: &SqlitePool,
+        cutoff: DateTime<Utc>,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let purged = sqlx::query_scalar::<_, String>(
+            "DELETE FROM users WHERE deleted_at IS NOT NULL AND deleted_at < ?1 RETURNING id",
+        )
+        .bind(cutoff)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(purged)
+    }
 }
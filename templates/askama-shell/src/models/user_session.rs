@@ -0,0 +1,133 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+
+// Avoid a write on every request by only refreshing last_seen_at once a minute
+const LAST_SEEN_RESOLUTION_SECONDS: i64 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserSession {
+    pub id: String,
+    pub user_id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+impl UserSession {
+    pub async fn create(
+        pool: &SqlitePool,
+        user_id: &str,
+        user_agent: Option<&str>,
+        ip_address: Option<&str>,
+    ) -> Result<UserSession, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        let user_session = sqlx::query_as::<_, UserSession>(
+            r#"
+            INSERT INTO user_sessions (id, user_id, user_agent, ip_address, created_at, last_seen_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(user_agent)
+        .bind(ip_address)
+        .bind(now)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(user_session)
+    }
+
+    pub async fn find_by_id(
+        pool: &SqlitePool,
+        id: &str,
+    ) -> Result<Option<UserSession>, sqlx::Error> {
+        let user_session =
+            sqlx::query_as::<_, UserSession>("SELECT * FROM user_sessions WHERE id = ?1")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?;
+
+        Ok(user_session)
+    }
+
+    pub async fn find_by_user(
+        pool: &SqlitePool,
+        user_id: &str,
+    ) -> Result<Vec<UserSession>, sqlx::Error> {
+        let user_sessions = sqlx::query_as::<_, UserSession>(
+            "SELECT * FROM user_sessions WHERE user_id = ?1 ORDER BY last_seen_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(user_sessions)
+    }
+
+    pub async fn touch(pool: &SqlitePool, id: &str) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        sqlx::query(
+            "UPDATE user_sessions SET last_seen_at = ?1 WHERE id = ?2 AND last_seen_at < ?3",
+        )
+        .bind(now)
+        .bind(id)
+        .bind(now - Duration::seconds(LAST_SEEN_RESOLUTION_SECONDS))
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete(pool: &SqlitePool, id: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM user_sessions WHERE id = ?1 AND user_id = ?2")
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete_others_for_user(
+        pool: &SqlitePool,
+        user_id: &str,
+        keep_id: &str,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM user_sessions WHERE user_id = ?1 AND id != ?2")
+            .bind(user_id)
+            .bind(keep_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn delete_unused_before(
+        pool: &SqlitePool,
+        cutoff: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM user_sessions WHERE last_seen_at < ?1")
+            .bind(cutoff)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn delete_for_user(pool: &SqlitePool, user_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM user_sessions WHERE user_id = ?1")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+use webauthn_rs::prelude::Passkey;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebauthnCredential {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub passkey: Json<Passkey>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl WebauthnCredential {
+    pub async fn create(
+        pool: &SqlitePool,
+        user_id: &str,
+        name: String,
+        passkey: Passkey,
+    ) -> Result<WebauthnCredential, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+
+        let credential = sqlx::query_as::<_, WebauthnCredential>(
+            r#"
+            INSERT INTO webauthn_credentials (id, user_id, name, passkey, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(user_id)
+        .bind(&name)
+        .bind(Json(passkey))
+        .bind(Utc::now())
+        .fetch_one(pool)
+        .await?;
+
+        Ok(credential)
+    }
+
+    pub async fn find_by_user(
+        pool: &SqlitePool,
+        user_id: &str,
+    ) -> Result<Vec<WebauthnCredential>, sqlx::Error> {
+        let credentials = sqlx::query_as::<_, WebauthnCredential>(
+            "SELECT * FROM webauthn_credentials WHERE user_id = ?1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(credentials)
+    }
+
+    pub async fn record_use(
+        pool: &SqlitePool,
+        id: &str,
+        passkey: &Passkey,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE webauthn_credentials SET passkey = ?1, last_used_at = ?2 WHERE id = ?3",
+        )
+        .bind(Json(passkey))
+        .bind(Utc::now())
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+use chrono::{DateTime, Utc};
+use rand::{Rng, distributions::Alphanumeric};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+use validator::Validate;
+
+// Secrets look like `whsec_<32 random characters>`
+const WEBHOOK_SECRET_PREFIX: &str = "whsec_";
+const WEBHOOK_SECRET_LENGTH: usize = 32;
+
+pub const DELIVERY_PENDING: &str = "pending";
+pub const DELIVERY_SUCCEEDED: &str = "succeeded";
+pub const DELIVERY_FAILED: &str = "failed";
+
+/// An endpoint events are sent to, see `crate::webhooks`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    /// Space-separated list of event names
+    pub events: String,
+    pub is_active: bool,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateWebhookRequest {
+    #[validate(url(message = "Enter a valid URL"))]
+    #[validate(length(max = 2048, message = "URL must be at most 2048 characters"))]
+    pub url: String,
+
+    #[validate(length(min = 1, message = "Select at least one event"))]
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i64,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A delivery with the outcome of its latest attempt, for the admin pages
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WebhookDeliveryRow {
+    pub id: String,
+    pub event: String,
+    pub status: String,
+    pub attempts: i64,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub last_response_status: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDeliveryAttempt {
+    pub id: String,
+    pub delivery_id: String,
+    pub response_status: Option<i64>,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+    pub attempted_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    pub async fn create(
+        pool: &SqlitePool,
+        url: &str,
+        events: &[String],
+        created_by: Option<&str>,
+    ) -> Result<Webhook, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+        let random: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(WEBHOOK_SECRET_LENGTH)
+            .map(char::from)
+            .collect();
+        let now = Utc::now();
+
+        let webhook = sqlx::query_as::<_, Webhook>(
+            r#"
+            INSERT INTO webhooks (id, url, secret, events, is_active, created_by, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, TRUE, ?5, ?6, ?6)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(url)
+        .bind(format!("{}{}", WEBHOOK_SECRET_PREFIX, random))
+        .bind(events.join(" "))
+        .bind(created_by)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(webhook)
+    }
+
+    pub async fn find_all(pool: &SqlitePool) -> Result<Vec<Webhook>, sqlx::Error> {
+        let webhooks =
+            sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks ORDER BY created_at DESC")
+                .fetch_all(pool)
+                .await?;
+
+        Ok(webhooks)
+    }
+
+    pub async fn find_by_id(pool: &SqlitePool, id: &str) -> Result<Option<Webhook>, sqlx::Error> {
+        let webhook = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(webhook)
+    }
+
+    /// Active endpoints that asked for the given event
+    pub async fn find_subscribed(
+        pool: &SqlitePool,
+        event: &str,
+    ) -> Result<Vec<Webhook>, sqlx::Error> {
+        let webhooks = sqlx::query_as::<_, Webhook>(
+            "SELECT * FROM webhooks WHERE is_active = TRUE AND ' ' || events || ' ' LIKE ?1",
+        )
+        .bind(format!("% {} %", event))
+        .fetch_all(pool)
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    pub fn event_list(&self) -> Vec<&str> {
+        self.events.split_whitespace().collect()
+    }
+
+    pub async fn set_active(
+        pool: &SqlitePool,
+        id: &str,
+        active: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let result =
+            sqlx::query("UPDATE webhooks SET is_active = ?1, updated_at = ?2 WHERE id = ?3")
+                .bind(active)
+                .bind(Utc::now())
+                .bind(id)
+                .execute(pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Deletes the endpoint along with its deliveries
+    pub async fn delete(pool: &SqlitePool, id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = ?1")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+impl WebhookDelivery {
+    /// Queues an event for an endpoint, to be sent as soon as the worker runs
+    pub async fn create(
+        pool: &SqlitePool,
+        webhook_id: &str,
+        event: &str,
+        payload: &str,
+    ) -> Result<WebhookDelivery, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        let delivery = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            INSERT INTO webhook_deliveries (id, webhook_id, event, payload, status, attempts, next_attempt_at, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6, ?6, ?6)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(webhook_id)
+        .bind(event)
+        .bind(payload)
+        .bind(DELIVERY_PENDING)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(delivery)
+    }
+
+    /// Takes up to `limit` pending deliveries that are due, pushing their next
+    /// attempt back to `lease_until` so no other worker picks them up. If the
+    /// worker dies mid-send, they're retried once the lease runs out.
+    pub async fn claim_due(
+        pool: &SqlitePool,
+        limit: i64,
+        lease_until: DateTime<Utc>,
+    ) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            UPDATE webhook_deliveries
+            SET next_attempt_at = ?1
+            WHERE id IN (
+                SELECT id FROM webhook_deliveries
+                WHERE status = ?2 AND next_attempt_at <= ?3
+                ORDER BY next_attempt_at
+                LIMIT ?4
+            )
+            RETURNING *
+            "#,
+        )
+        .bind(lease_until)
+        .bind(DELIVERY_PENDING)
+        .bind(Utc::now())
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    /// Counts an attempt, setting the delivery's new status and, while it's
+    /// still pending, when to try again
+    pub async fn record_attempt(
+        pool: &SqlitePool,
+        id: &str,
+        status: &str,
+        next_attempt_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = ?1, attempts = attempts + 1, next_attempt_at = ?2, updated_at = ?3
+            WHERE id = ?4
+            "#,
+        )
+        .bind(status)
+        .bind(next_attempt_at)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Sends a delivery that gave up again, from a fresh count of attempts
+    pub async fn retry(pool: &SqlitePool, id: &str, webhook_id: &str) -> Result<bool, sqlx::Error> {
+        let now = Utc::now();
+        let result = sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = ?1, attempts = 0, next_attempt_at = ?2, updated_at = ?2
+            WHERE id = ?3 AND webhook_id = ?4 AND status = ?5
+            "#,
+        )
+        .bind(DELIVERY_PENDING)
+        .bind(now)
+        .bind(id)
+        .bind(webhook_id)
+        .bind(DELIVERY_FAILED)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Newest first, with how the latest attempt went
+    pub async fn find_recent_for_webhook(
+        pool: &SqlitePool,
+        webhook_id: &str,
+        limit: i64,
+    ) -> Result<Vec<WebhookDeliveryRow>, sqlx::Error> {
+        let deliveries = sqlx::query_as::<_, WebhookDeliveryRow>(
+            r#"
+            SELECT d.id, d.event, d.status, d.attempts, d.next_attempt_at, d.created_at,
+                   a.response_status AS last_response_status, a.error AS last_error
+            FROM webhook_deliveries d
+            LEFT JOIN webhook_delivery_attempts a ON a.id = (
+                SELECT id FROM webhook_delivery_attempts
+                WHERE delivery_id = d.id
+                ORDER BY attempted_at DESC
+                LIMIT 1
+            )
+            WHERE d.webhook_id = ?1
+            ORDER BY d.created_at DESC
+            LIMIT ?2
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    /// Clears out finished deliveries and their attempts once they're old
+    pub async fn delete_finished_before(
+        pool: &SqlitePool,
+        cutoff: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM webhook_deliveries WHERE status != ?1 AND updated_at < ?2")
+                .bind(DELIVERY_PENDING)
+                .bind(cutoff)
+                .execute(pool)
+                .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+impl WebhookDeliveryAttempt {
+    pub async fn record(
+        pool: &SqlitePool,
+        delivery_id: &str,
+        response_status: Option<i64>,
+        error: Option<&str>,
+        duration_ms: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_delivery_attempts (id, delivery_id, response_status, error, duration_ms, attempted_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(delivery_id)
+        .bind(response_status)
+        .bind(error)
+        .bind(duration_ms)
+        .bind(Utc::now())
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use crate::handlers::auth::push_flash_message;
+use axum::{
+    Form, Json, async_trait,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts},
+    response::{IntoResponse, Redirect, Response},
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::convert::Infallible;
+use tower_sessions::Session;
+
+/// How the caller wants to hear back: JSON for scripts and API clients, or
+/// redirects with flash messages for plain HTML form posts. A `?format=json`
+/// or `?format=html` query parameter overrides what the headers suggest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Json,
+    Html,
+}
+
+impl ResponseFormat {
+    pub fn from_parts(parts: &Parts) -> Self {
+        let format = parts.uri.query().and_then(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == "format")
+                .map(|(_, value)| value)
+        });
+        match format.as_deref() {
+            Some("json") => return Self::Json,
+            Some("html") => return Self::Html,
+            _ => {}
+        }
+
+        let header = |name| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+        };
+        let accept = header(header::ACCEPT);
+        if accept.contains("application/json") {
+            Self::Json
+        } else if accept.contains("text/html") || is_form(header(header::CONTENT_TYPE)) {
+            Self::Html
+        } else {
+            // fetch() sends */*, and existing clients expect JSON
+            Self::Json
+        }
+    }
+
+    /// Sends a handler's JSON reply as is, or for HTML turns it into a
+    /// redirect: to `on_success` when `success` is true, otherwise back to
+    /// `on_failure`. Its `message` and any field `errors` become flash
+    /// messages on the page redirected to. Error responses pass through.
+    pub async fn respond(
+        self,
+        session: &Session,
+        reply: Result<Json<Value>, Response>,
+        on_success: &str,
+        on_failure: &str,
+    ) -> Response {
+        let Json(body) = match reply {
+            Ok(body) => body,
+            Err(response) => return response,
+        };
+        if self == Self::Json {
+            return Json(body).into_response();
+        }
+
+        let success = body["success"].as_bool().unwrap_or(false);
+        let level = if success { "success" } else { "error" };
+        let mut messages: Vec<&str> = body["message"].as_str().into_iter().collect();
+        if let Some(errors) = body["errors"].as_object() {
+            messages.extend(errors.values().filter_map(Value::as_str));
+        }
+        for message in messages {
+            if let Err(e) = push_flash_message(session, level, message).await {
+                tracing::warn!("Failed to store flash message: {}", e);
+            }
+        }
+
+        Redirect::to(if success { on_success } else { on_failure }).into_response()
+    }
+}
+
+fn is_form(content_type: &str) -> bool {
+    content_type.starts_with("application/x-www-form-urlencoded")
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ResponseFormat
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_parts(parts))
+    }
+}
+
+/// Body extractor for handlers serving both scripts and no-JS forms: reads a
+/// urlencoded form or JSON depending on `Content-Type`, alongside the
+/// `ResponseFormat` to answer in.
+pub struct Negotiate<T>(pub ResponseFormat, pub T);
+
+#[async_trait]
+impl<S, T> FromRequest<S> for Negotiate<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let (parts, body) = request.into_parts();
+        let format = ResponseFormat::from_parts(&parts);
+        let is_form_post = parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(is_form);
+        let request = Request::from_parts(parts, body);
+
+        let value = if is_form_post {
+            Form::<T>::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?
+                .0
+        } else {
+            Json::<T>::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?
+                .0
+        };
+
+        Ok(Self(format, value))
+    }
+}
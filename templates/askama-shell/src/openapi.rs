@@ -0,0 +1,52 @@
+use crate::handlers::notes::{self, ErrorResponse, NoteListResponse, NoteResponse};
+use crate::models::{CreateNoteRequest, Note, NoteSort, UpdateNoteRequest};
+use crate::pagination::{PageInfo, SortDirection};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+/// OpenAPI description of the `/api/v1` routes, served as JSON at
+/// `/api/openapi.json` and browsable with Swagger UI at `/api/docs`.
+/// Add new handlers to `paths` and their request and response types to
+/// `schemas`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Rust Web Shell API",
+        description = "Requests authenticate with a personal access token or a JWT access token \
+                       in an `Authorization: Bearer` header. Browser requests may use the session \
+                       cookie instead, with the CSRF token in an `X-CSRF-Token` header."
+    ),
+    paths(
+        notes::list_notes,
+        notes::create_note,
+        notes::get_note,
+        notes::update_note,
+        notes::delete_note,
+    ),
+    components(schemas(
+        Note,
+        CreateNoteRequest,
+        UpdateNoteRequest,
+        NoteResponse,
+        NoteListResponse,
+        NoteSort,
+        PageInfo,
+        SortDirection,
+        ErrorResponse,
+    )),
+    modifiers(&BearerAuth),
+    tags((name = "notes", description = "Example resource: notes owned by the caller"))
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, OriginalUri, Query},
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sqlx::{QueryBuilder, Sqlite};
+use utoipa::ToSchema;
+
+/// The columns a list can be sorted by, implemented by a small enum per
+/// resource so `ORDER BY` only ever names a known column. Rows are expected
+/// to have a unique `id` column, used to break ties and for keyset pages.
+pub trait SortKey: DeserializeOwned + Default + Copy + Send + Sync + 'static {
+    /// Table the sort column lives in, for looking up keyset cursors
+    const TABLE: &'static str;
+    const DEFAULT_PER_PAGE: i64 = 20;
+    const MAX_PER_PAGE: i64 = 100;
+    /// Pages past this are clamped, keeping the offset far from overflowing
+    const MAX_PAGE: i64 = 1_000_000;
+
+    /// Must not be nullable, or keyset pages skip rows
+    fn column(self) -> &'static str;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortDirection {
+    fn sql(self) -> &'static str {
+        match self {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PageQuery<S> {
+    page: Option<i64>,
+    per_page: Option<i64>,
+    sort: Option<S>,
+    dir: Option<SortDirection>,
+    after: Option<String>,
+}
+
+/// Query extractor for list endpoints: `page`, `per_page` (capped at
+/// `S::MAX_PER_PAGE`; `page` is likewise capped at `S::MAX_PAGE`), `sort`,
+/// `dir`, and `after`, the ID of the last row already seen, for keyset
+/// pagination that stays stable while rows are added. Other parameters, such
+/// as filters, are kept in the page links.
+#[derive(Debug, Clone)]
+pub struct Paginated<S> {
+    pub page: i64,
+    pub per_page: i64,
+    pub sort: S,
+    pub dir: SortDirection,
+    pub after: Option<String>,
+    path: String,
+    params: Vec<(String, String)>,
+}
+
+#[async_trait]
+impl<S, St> FromRequestParts<St> for Paginated<S>
+where
+    S: SortKey,
+    St: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &St) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<PageQuery<S>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        // Links point at the full path, even from inside a nested router
+        let uri = parts
+            .extensions
+            .get::<OriginalUri>()
+            .map(|OriginalUri(uri)| uri.clone())
+            .unwrap_or_else(|| parts.uri.clone());
+        let params = url::form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+            .filter(|(key, _)| key != "page" && key != "after")
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+
+        let mut paginated = Self::new(
+            query.page.unwrap_or(1),
+            query.per_page.unwrap_or(S::DEFAULT_PER_PAGE),
+            query.sort.unwrap_or_default(),
+            query.dir.unwrap_or_default(),
+        );
+        paginated.after = query.after.filter(|after| !after.is_empty());
+        paginated.path = uri.path().to_string();
+        paginated.params = params;
+        Ok(paginated)
+    }
+}
+
+impl<S: SortKey> Paginated<S> {
+    /// For callers outside of HTTP handlers; the result has no links
+    pub fn new(page: i64, per_page: i64, sort: S, dir: SortDirection) -> Self {
+        Self {
+            page: page.clamp(1, S::MAX_PAGE),
+            per_page: per_page.clamp(1, S::MAX_PER_PAGE),
+            sort,
+            dir,
+            after: None,
+            path: String::new(),
+            params: Vec::new(),
+        }
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+
+    /// Appends ` AND (...)` limiting rows to those after the `after` cursor,
+    /// if there is one. Call it after starting the `WHERE` clause.
+    pub fn push_keyset_condition(&self, builder: &mut QueryBuilder<'_, Sqlite>) {
+        let Some(after) = &self.after else {
+            return;
+        };
+        let column = self.sort.column();
+        let comparison = match self.dir {
+            SortDirection::Asc => ">",
+            SortDirection::Desc => "<",
+        };
+        builder
+            .push(format!(
+                " AND ({column}, id) {comparison} ((SELECT {column} FROM {} WHERE id = ",
+                S::TABLE
+            ))
+            .push_bind(after.clone())
+            .push("), ")
+            .push_bind(after.clone())
+            .push(")");
+    }
+
+    /// Appends `ORDER BY` and `LIMIT`, plus `OFFSET` unless paging by keyset
+    pub fn push_order_and_limit(&self, builder: &mut QueryBuilder<'_, Sqlite>) {
+        let dir = self.dir.sql();
+        builder
+            .push(format!(
+                " ORDER BY {} {dir}, id {dir} LIMIT ",
+                self.sort.column()
+            ))
+            .push_bind(self.per_page);
+        if self.after.is_none() {
+            builder.push(" OFFSET ").push_bind(self.offset());
+        }
+    }
+
+    /// Counts and links for the page holding `items`; `id` reads the row ID
+    /// used as the next keyset cursor
+    pub fn page_info<T>(&self, total: i64, items: &[T], id: impl Fn(&T) -> &str) -> PageInfo {
+        let total_pages = (total + self.per_page - 1) / self.per_page;
+        let next_cursor = items
+            .last()
+            .filter(|_| items.len() as i64 == self.per_page)
+            .map(|item| id(item).to_string());
+
+        let (next, prev) = if self.after.is_some() {
+            // Keyset pages only go forward
+            let next = next_cursor
+                .as_deref()
+                .map(|cursor| self.link("after", cursor));
+            (next, None)
+        } else {
+            let next =
+                (self.page < total_pages).then(|| self.link("page", &(self.page + 1).to_string()));
+            let prev = (self.page > 1).then(|| self.link("page", &(self.page - 1).to_string()));
+            (next, prev)
+        };
+
+        PageInfo {
+            page: self.page,
+            per_page: self.per_page,
+            total,
+            total_pages,
+            next,
+            prev,
+            next_cursor,
+        }
+    }
+
+    fn link(&self, key: &str, value: &str) -> String {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        for (param, param_value) in &self.params {
+            query.append_pair(param, param_value);
+        }
+        query.append_pair(key, value);
+        format!("{}?{}", self.path, query.finish())
+    }
+}
+
+/// Pagination half of a list response, flattened next to the items
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PageInfo {
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+    pub total_pages: i64,
+    /// Link to the next page, `null` on the last one
+    pub next: Option<String>,
+    /// Link to the previous page; keyset pages don't have one
+    pub prev: Option<String>,
+    /// Pass as `after` to continue from this page with keyset pagination
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, Default, Deserialize)]
+    struct TestSort;
+
+    impl SortKey for TestSort {
+        const TABLE: &'static str = "tests";
+
+        fn column(self) -> &'static str {
+            "created_at"
+        }
+    }
+
+    #[test]
+    fn huge_pages_are_clamped_instead_of_overflowing() {
+        let paginated = Paginated::new(i64::MAX, i64::MAX, TestSort, SortDirection::Desc);
+        assert_eq!(paginated.page, TestSort::MAX_PAGE);
+        assert_eq!(paginated.per_page, TestSort::MAX_PER_PAGE);
+        assert_eq!(
+            paginated.offset(),
+            (TestSort::MAX_PAGE - 1) * TestSort::MAX_PER_PAGE
+        );
+    }
+
+    #[test]
+    fn pages_below_one_start_at_the_first() {
+        let paginated = Paginated::new(i64::MIN, 0, TestSort, SortDirection::Asc);
+        assert_eq!(paginated.page, 1);
+        assert_eq!(paginated.per_page, 1);
+        assert_eq!(paginated.offset(), 0);
+    }
+}
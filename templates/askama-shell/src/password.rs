@@ -0,0 +1,110 @@
+use crate::config::PasswordHashing;
+use argon2::password_hash::{SaltString, rand_core::OsRng};
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use serde::Serialize;
+
+// zxcvbn scores run from 0 (trivially guessable) to 4 (very unguessable)
+pub const MIN_PASSWORD_SCORE: u8 = 3;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordStrength {
+    pub score: u8,
+    pub acceptable: bool,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+impl PasswordStrength {
+    pub fn message(&self) -> String {
+        match &self.warning {
+            Some(warning) => format!("Password is too weak. {}", warning),
+            None => "Password is too weak".to_string(),
+        }
+    }
+}
+
+/// Estimates how guessable `password` is, penalising passwords built from
+/// `user_inputs` such as the account's email or username.
+pub fn estimate_strength(password: &str, user_inputs: &[&str]) -> PasswordStrength {
+    let entropy = match zxcvbn::zxcvbn(password, user_inputs) {
+        Ok(entropy) => entropy,
+        // Only blank passwords fail to estimate
+        Err(_) => {
+            return PasswordStrength {
+                score: 0,
+                acceptable: false,
+                warning: None,
+                suggestions: vec!["Enter a password".to_string()],
+            };
+        }
+    };
+
+    let (warning, suggestions) = match entropy.feedback() {
+        Some(feedback) => (
+            feedback.warning().map(|warning| warning.to_string()),
+            feedback
+                .suggestions()
+                .iter()
+                .map(|suggestion| suggestion.to_string())
+                .collect(),
+        ),
+        None => (None, Vec::new()),
+    };
+
+    PasswordStrength {
+        score: entropy.score(),
+        acceptable: entropy.score() >= MIN_PASSWORD_SCORE,
+        warning,
+        suggestions,
+    }
+}
+
+fn argon2(password_hashing: &PasswordHashing) -> Argon2<'static> {
+    Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        password_hashing.params.clone(),
+    )
+}
+
+pub fn hash_password(
+    password: &str,
+    password_hashing: &PasswordHashing,
+) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = argon2(password_hashing).hash_password(password.as_bytes(), &salt)?;
+    Ok(password_hash.to_string())
+}
+
+// The PHC string carries the parameters it was hashed with, so verification
+// works for hashes created under older settings too
+pub fn verify_password(password: &str, hash: &str) -> Result<bool, argon2::password_hash::Error> {
+    let parsed_hash = PasswordHash::new(hash)?;
+    Ok(Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_ok())
+}
+
+/// Returns true when `hash` was created with a different algorithm or with
+/// weaker parameters than currently configured.
+pub fn needs_rehash(hash: &str, password_hashing: &PasswordHashing) -> bool {
+    let Ok(parsed_hash) = PasswordHash::new(hash) else {
+        return false;
+    };
+
+    if parsed_hash.algorithm != Algorithm::Argon2id.ident()
+        || parsed_hash.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+
+    match Params::try_from(&parsed_hash) {
+        Ok(params) => {
+            let current = &password_hashing.params;
+            params.m_cost() < current.m_cost()
+                || params.t_cost() < current.t_cost()
+                || params.p_cost() < current.p_cost()
+        }
+        Err(_) => true,
+    }
+}
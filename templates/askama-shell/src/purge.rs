@@ -0,0 +1,70 @@
+use crate::config::{AccountRetention, AvatarStorage, SessionCookie};
+use crate::models::{DataExport, RefreshToken, User, UserSession, WebhookDelivery};
+use sqlx::SqlitePool;
+use std::time::Duration;
+
+// How often deleted accounts past their retention window are looked for
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+// How long finished webhook deliveries stay visible on the admin pages
+const WEBHOOK_DELIVERY_RETENTION_DAYS: i64 = 30;
+// Exports are assembled in-process, so one pending this long was lost to a
+// restart or crash and will never finish
+const STALE_DATA_EXPORT_MINUTES: i64 = 60;
+
+/// Periodically hard-deletes accounts that were soft-deleted longer ago than
+/// the retention window. Related rows go with them via `ON DELETE CASCADE`,
+/// and their avatar files are removed.
+/// Expired refresh tokens and data exports, old webhook deliveries, and
+/// sessions unused for longer than even a remembered session lasts are
+/// cleared out on the same schedule, and abandoned data exports are marked
+/// failed so they stop showing as in progress.
+pub fn spawn_account_purge(
+    pool: SqlitePool,
+    retention: AccountRetention,
+    avatars: AvatarStorage,
+    session_cookie: SessionCookie,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let cutoff = chrono::Utc::now() - retention.retention;
+            match User::purge_deleted_before(&pool, cutoff).await {
+                Ok(purged) if purged.is_empty() => {}
+                Ok(purged) => {
+                    for user_id in &purged {
+                        if let Err(e) = avatars.delete(user_id).await {
+                            tracing::error!("Failed to delete avatar of user {}: {}", user_id, e);
+                        }
+                    }
+                    tracing::info!("Purged {} deleted account(s)", purged.len());
+                }
+                Err(e) => tracing::error!("Failed to purge deleted accounts: {}", e),
+            }
+
+            if let Err(e) = RefreshToken::delete_expired(&pool).await {
+                tracing::error!("Failed to delete expired refresh tokens: {}", e);
+            }
+            let session_cutoff = chrono::Utc::now() - session_cookie.remembered_timeout;
+            if let Err(e) = UserSession::delete_unused_before(&pool, session_cutoff).await {
+                tracing::error!("Failed to delete expired user sessions: {}", e);
+            }
+            let export_cutoff =
+                chrono::Utc::now() - chrono::Duration::minutes(STALE_DATA_EXPORT_MINUTES);
+            match DataExport::fail_stale(&pool, export_cutoff).await {
+                Ok(0) => {}
+                Ok(failed) => tracing::warn!("Marked {} abandoned data export(s) failed", failed),
+                Err(e) => tracing::error!("Failed to mark abandoned data exports failed: {}", e),
+            }
+            if let Err(e) = DataExport::delete_expired(&pool).await {
+                tracing::error!("Failed to delete expired data exports: {}", e);
+            }
+            let delivery_cutoff =
+                chrono::Utc::now() - chrono::Duration::days(WEBHOOK_DELIVERY_RETENTION_DAYS);
+            if let Err(e) = WebhookDelivery::delete_finished_before(&pool, delivery_cutoff).await {
+                tracing::error!("Failed to delete old webhook deliveries: {}", e);
+            }
+        }
+    })
+}
@@ -0,0 +1,308 @@
+use crate::api_auth::ApiUser;
+use crate::handlers::ClientInfo;
+use axum::{
+    Json,
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Auth request bodies are small; larger ones aren't inspected for an account
+const MAX_INSPECTED_BODY_BYTES: usize = 64 * 1024;
+// Full buckets are dropped once this many keys are tracked
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Token buckets keyed by string, one per client, account, or user being
+/// limited. Buckets live in memory, so each instance enforces its own limit.
+#[derive(Clone)]
+struct TokenBuckets {
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+    /// Requests allowed in a burst before throttling starts
+    burst: f64,
+    /// Requests regained per second
+    refill_per_second: f64,
+}
+
+impl TokenBuckets {
+    /// Reads `<PREFIX>_PER_MINUTE` (0 disables the limit) and `<PREFIX>_BURST`
+    /// (defaults to the per-minute rate)
+    fn from_env(prefix: &str, default_per_minute: u32) -> Self {
+        let per_minute: u32 = env::var(format!("{}_PER_MINUTE", prefix))
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default_per_minute);
+        let burst: u32 = env::var(format!("{}_BURST", prefix))
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(per_minute);
+
+        Self {
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+            burst: f64::from(burst.max(1)),
+            refill_per_second: f64::from(per_minute) / 60.0,
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.refill_per_second > 0.0
+    }
+
+    /// Takes a token from every key's bucket, or returns how long until the
+    /// emptiest one has a token again. Nothing is taken unless all keys allow it.
+    fn check(&self, keys: &[String]) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() > PRUNE_THRESHOLD {
+            let burst = self.burst;
+            let refill = self.refill_per_second;
+            buckets.retain(|_, bucket| {
+                let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+                bucket.tokens + elapsed * refill < burst
+            });
+        }
+
+        let mut wait = Duration::ZERO;
+        for key in keys {
+            let bucket = buckets.entry(key.clone()).or_insert(Bucket {
+                tokens: self.burst,
+                updated_at: now,
+            });
+            let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * self.refill_per_second).min(self.burst);
+            bucket.updated_at = now;
+
+            if bucket.tokens < 1.0 {
+                let seconds = (1.0 - bucket.tokens) / self.refill_per_second;
+                wait = wait.max(Duration::from_secs_f64(seconds));
+            }
+        }
+
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+        for key in keys {
+            if let Some(bucket) = buckets.get_mut(key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Limits the sign-in and sign-up endpoints with one bucket per client IP and
+/// one per account identifier, so neither spreading attempts across accounts
+/// nor across addresses gets around the limit.
+#[derive(Clone)]
+pub struct AuthRateLimiter {
+    buckets: TokenBuckets,
+}
+
+impl AuthRateLimiter {
+    /// Reads `AUTH_RATE_LIMIT_PER_MINUTE` (default 10, 0 disables the limit)
+    /// and `AUTH_RATE_LIMIT_BURST` (defaults to the per-minute rate).
+    pub fn from_env() -> Self {
+        Self {
+            buckets: TokenBuckets::from_env("AUTH_RATE_LIMIT", 10),
+        }
+    }
+}
+
+/// Limits versioned API routes with one bucket per client IP, checked before
+/// authentication so bad credentials count too, and one per authenticated
+/// user
+#[derive(Clone)]
+pub struct ApiRateLimiter {
+    buckets: TokenBuckets,
+    ip_buckets: TokenBuckets,
+}
+
+impl ApiRateLimiter {
+    /// Reads `API_RATE_LIMIT_PER_MINUTE` (default 120, 0 disables the limit)
+    /// and `API_RATE_LIMIT_BURST` (defaults to the per-minute rate) for each
+    /// user, and `API_IP_RATE_LIMIT_PER_MINUTE` (default 600) and
+    /// `API_IP_RATE_LIMIT_BURST` for each IP, which several users may share.
+    pub fn from_env() -> Self {
+        Self {
+            buckets: TokenBuckets::from_env("API_RATE_LIMIT", 120),
+            ip_buckets: TokenBuckets::from_env("API_IP_RATE_LIMIT", 600),
+        }
+    }
+
+    /// Takes a request from the user's budget, returning the 429 response
+    /// to send instead once it's used up
+    pub fn check_user(&self, user_id: &str) -> Option<Response> {
+        if !self.buckets.is_enabled() {
+            return None;
+        }
+
+        self.buckets
+            .check(&[format!("user:{}", user_id)])
+            .err()
+            .map(|wait| too_many_requests(wait, true))
+    }
+}
+
+/// Rejects requests with 429 Too Many Requests once the caller's IP address or
+/// the account named in the body (`identifier` or `email`) has used up
+/// its budget. JSON clients get a JSON body, browsers a short HTML page.
+pub async fn limit_auth_requests(
+    State(limiter): State<AuthRateLimiter>,
+    client: ClientInfo,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !limiter.buckets.is_enabled() {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_INSPECTED_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => return (StatusCode::PAYLOAD_TOO_LARGE, "Request too large").into_response(),
+    };
+
+    let mut keys = vec![format!("ip:{}", client.ip_address)];
+    let account = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(value) => ["identifier", "email"].iter().find_map(|field| {
+            value
+                .get(field)
+                .and_then(|v| v.as_str())
+                .map(|s| s.trim().to_lowercase())
+        }),
+        // No-JS form posts
+        Err(_) => url::form_urlencoded::parse(&bytes)
+            .find(|(key, _)| key == "identifier" || key == "email")
+            .map(|(_, value)| value.trim().to_lowercase()),
+    }
+    .filter(|account| !account.is_empty());
+    if let Some(account) = account {
+        keys.push(format!("account:{}", account));
+    }
+
+    let wants_json = parts
+        .headers
+        .get(header::ACCEPT)
+        .or_else(|| parts.headers.get(header::CONTENT_TYPE))
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("application/json"));
+
+    match limiter.buckets.check(&keys) {
+        Ok(()) => {
+            next.run(Request::from_parts(parts, Body::from(bytes)))
+                .await
+        }
+        Err(wait) => {
+            tracing::warn!(
+                "Rate limited {} {} from {}",
+                parts.method,
+                parts.uri.path(),
+                client.ip_address
+            );
+            too_many_requests(wait, wants_json)
+        }
+    }
+}
+
+/// Rejects API requests with 429 Too Many Requests once the caller's IP
+/// address has used up its budget. Runs ahead of `api_auth::require_api_user`
+/// so requests with missing or guessed credentials are counted.
+pub async fn limit_api_clients(
+    State(limiter): State<ApiRateLimiter>,
+    client: ClientInfo,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !limiter.ip_buckets.is_enabled() {
+        return next.run(request).await;
+    }
+
+    match limiter
+        .ip_buckets
+        .check(&[format!("ip:{}", client.ip_address)])
+    {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            tracing::warn!(
+                "Rate limited {} {} from {}",
+                request.method(),
+                request.uri().path(),
+                client.ip_address
+            );
+            too_many_requests(wait, true)
+        }
+    }
+}
+
+/// Rejects API requests with 429 Too Many Requests once the caller has used
+/// up its budget. Callers are told apart by the `ApiUser` that
+/// `api_auth::require_api_user` leaves in the request extensions.
+pub async fn limit_api_requests(
+    State(limiter): State<ApiRateLimiter>,
+    client: ClientInfo,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Only reached through require_api_user, which always sets this
+    let Some(api_user) = request.extensions().get::<ApiUser>() else {
+        return next.run(request).await;
+    };
+
+    match limiter.check_user(&api_user.user.id) {
+        None => next.run(request).await,
+        Some(response) => {
+            tracing::warn!(
+                "Rate limited {} {} from {}",
+                request.method(),
+                request.uri().path(),
+                client.ip_address
+            );
+            response
+        }
+    }
+}
+
+fn too_many_requests(wait: Duration, wants_json: bool) -> Response {
+    let retry_after = (wait.as_secs_f64().ceil() as u64).max(1);
+    let message = format!(
+        "Too many attempts. Please wait {} second{} and try again.",
+        retry_after,
+        if retry_after == 1 { "" } else { "s" }
+    );
+
+    let mut response = if wants_json {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(json!({
+                "success": false,
+                "message": message
+            })),
+        )
+            .into_response()
+    } else {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            Html(format!(
+                "<!DOCTYPE html><html><head><title>Too Many Requests</title></head><body><h1>Too Many Requests</h1><p>{}</p></body></html>",
+                message
+            )),
+        )
+            .into_response()
+    };
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
@@ -0,0 +1,70 @@
+use crate::handlers::get_user_from_session;
+use crate::models::{Role, UserResponse};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{StatusCode, request::Parts},
+    response::{IntoResponse, Redirect, Response},
+};
+use sqlx::SqlitePool;
+use std::marker::PhantomData;
+use tower_sessions::Session;
+
+/// A role seeded by the migrations, used as the parameter of [`RequireRole`].
+pub trait RoleName {
+    const NAME: &'static str;
+}
+
+pub struct AdminRole;
+
+impl RoleName for AdminRole {
+    const NAME: &'static str = "admin";
+}
+
+pub struct UserRole;
+
+impl RoleName for UserRole {
+    const NAME: &'static str = "user";
+}
+
+/// Extracts the signed-in user, rejecting the request unless they hold role
+/// `R`. Anonymous requests are sent to the login page, signed-in users without
+/// the role get a 403.
+pub struct RequireRole<R: RoleName> {
+    pub user: UserResponse,
+    role: PhantomData<R>,
+}
+
+#[async_trait]
+impl<S, R> FromRequestParts<S> for RequireRole<R>
+where
+    SqlitePool: FromRef<S>,
+    S: Send + Sync,
+    R: RoleName,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session = Session::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| rejection.into_response())?;
+        let pool = SqlitePool::from_ref(state);
+
+        let user = match get_user_from_session(&session, &pool).await {
+            Some(user) => user,
+            None => return Err(Redirect::to("/login").into_response()),
+        };
+
+        match Role::user_has_role(&pool, &user.id, R::NAME).await {
+            Ok(true) => Ok(Self {
+                user,
+                role: PhantomData,
+            }),
+            Ok(false) => Err((StatusCode::FORBIDDEN, "Forbidden").into_response()),
+            Err(e) => {
+                tracing::error!("Database error checking roles: {}", e);
+                Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+            }
+        }
+    }
+}
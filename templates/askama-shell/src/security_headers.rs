@@ -0,0 +1,45 @@
+use crate::config::SecurityHeaders;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+
+/// Adds the configured security headers to every response. Handlers that set
+/// one of them themselves keep their own value.
+pub async fn security_headers(
+    State(config): State<SecurityHeaders>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+
+    let mut set = |name: HeaderName, value: HeaderValue| {
+        headers.entry(name).or_insert(value);
+    };
+
+    set(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    // Older browsers ignore CSP frame-ancestors
+    set(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    set(header::REFERRER_POLICY, config.referrer_policy);
+
+    if let Some(policy) = config.content_security_policy {
+        let name = if config.csp_report_only {
+            header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+        } else {
+            header::CONTENT_SECURITY_POLICY
+        };
+        set(name, policy);
+    }
+
+    if let Some(hsts) = config.strict_transport_security {
+        set(header::STRICT_TRANSPORT_SECURITY, hsts);
+    }
+
+    response
+}
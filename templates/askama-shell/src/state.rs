@@ -0,0 +1,34 @@
+use crate::abuse::AbuseProtection;
+use crate::config::{
+    AvatarStorage, CorsConfig, JwtConfig, LockoutPolicy, PasswordHashing, SecurityHeaders,
+    SessionBackend, SessionCookie, SignupMode,
+};
+use crate::email::Mailer;
+use crate::live::Hub;
+use crate::rate_limit::{ApiRateLimiter, AuthRateLimiter};
+use crate::webhooks::Webhooks;
+use axum::extract::FromRef;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use webauthn_rs::Webauthn;
+
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub pool: SqlitePool,
+    pub mailer: Mailer,
+    pub webauthn: Arc<Webauthn>,
+    pub lockout_policy: LockoutPolicy,
+    pub auth_rate_limiter: AuthRateLimiter,
+    pub api_rate_limiter: ApiRateLimiter,
+    pub abuse_protection: AbuseProtection,
+    pub password_hashing: PasswordHashing,
+    pub session_backend: SessionBackend,
+    pub session_cookie: SessionCookie,
+    pub signup_mode: SignupMode,
+    pub jwt: JwtConfig,
+    pub avatars: AvatarStorage,
+    pub security_headers: SecurityHeaders,
+    pub cors: CorsConfig,
+    pub hub: Hub,
+    pub webhooks: Webhooks,
+}
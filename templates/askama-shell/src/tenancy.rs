@@ -0,0 +1,96 @@
+use crate::handlers::get_user_from_session;
+use crate::models::{Membership, Organization, UserResponse};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{StatusCode, request::Parts},
+    response::{IntoResponse, Redirect, Response},
+};
+use sqlx::SqlitePool;
+use tower_sessions::Session;
+
+/// Session key holding the id of the organization the user is working in.
+pub const ACTIVE_ORGANIZATION_KEY: &str = "active_organization_id";
+
+/// Extracts the signed-in user along with the organization they're working in.
+/// Handlers that read or write tenant data should take this and filter every
+/// query by `organization.id`, so one organization can never see another's rows.
+///
+/// The active organization comes from the session; when it's unset or the user
+/// has since left it, their oldest membership is used instead. Anonymous
+/// requests are sent to the login page, and users without any organization to
+/// the page for creating one.
+pub struct ActiveOrganization {
+    pub user: UserResponse,
+    pub organization: Organization,
+    pub membership: Membership,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ActiveOrganization
+where
+    SqlitePool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session = Session::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| rejection.into_response())?;
+        let pool = SqlitePool::from_ref(state);
+
+        let user = match get_user_from_session(&session, &pool).await {
+            Some(user) => user,
+            None => return Err(Redirect::to("/login").into_response()),
+        };
+
+        let selected = session
+            .get::<String>(ACTIVE_ORGANIZATION_KEY)
+            .await
+            .ok()
+            .flatten();
+        let membership = match selected {
+            Some(organization_id) => Membership::find(&pool, &organization_id, &user.id).await,
+            None => Ok(None),
+        };
+        let membership = match membership {
+            Ok(Some(membership)) => Ok(Some(membership)),
+            Ok(None) => Membership::find_first_for_user(&pool, &user.id).await,
+            Err(e) => Err(e),
+        };
+
+        let membership = match membership {
+            Ok(Some(membership)) => membership,
+            Ok(None) => return Err(Redirect::to("/organizations/new").into_response()),
+            Err(e) => {
+                tracing::error!("Database error loading membership: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        };
+
+        let organization = match Organization::find_by_id(&pool, &membership.organization_id).await
+        {
+            Ok(Some(organization)) => organization,
+            Ok(None) => return Err(Redirect::to("/organizations/new").into_response()),
+            Err(e) => {
+                tracing::error!("Database error loading organization: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response());
+            }
+        };
+
+        // Remember the fallback so later requests don't have to look it up again
+        if let Err(e) = session
+            .insert(ACTIVE_ORGANIZATION_KEY, &organization.id)
+            .await
+        {
+            tracing::warn!("Failed to store active organization in session: {}", e);
+        }
+
+        Ok(Self {
+            user,
+            organization,
+            membership,
+        })
+    }
+}
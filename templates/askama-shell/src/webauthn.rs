@@ -0,0 +1,18 @@
+use std::env;
+use webauthn_rs::prelude::{Url, Webauthn, WebauthnBuilder};
+
+/// Builds the relying party configuration from `WEBAUTHN_RP_ID` and
+/// `WEBAUTHN_RP_ORIGIN`. Browsers reject IP addresses as relying party ids,
+/// so local development has to go through `localhost`.
+pub fn from_env() -> anyhow::Result<Webauthn> {
+    let rp_id = env::var("WEBAUTHN_RP_ID").unwrap_or_else(|_| "localhost".to_string());
+    let rp_origin = env::var("WEBAUTHN_RP_ORIGIN")
+        .unwrap_or_else(|_| "http://localhost:3000".to_string())
+        .parse::<Url>()?;
+
+    let webauthn = WebauthnBuilder::new(&rp_id, &rp_origin)?
+        .rp_name("Rust Web Shell")
+        .build()?;
+
+    Ok(webauthn)
+}
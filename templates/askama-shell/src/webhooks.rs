@@ -0,0 +1,404 @@
+use crate::models::{
+    DELIVERY_FAILED, DELIVERY_PENDING, DELIVERY_SUCCEEDED, Webhook, WebhookDelivery,
+    WebhookDeliveryAttempt,
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use url::{Host, Url};
+use uuid::Uuid;
+
+// Endpoints have this long to answer before the attempt counts as failed
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+// Checked for retries that came due even when nothing new was emitted
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const DELIVERY_BATCH_SIZE: i64 = 20;
+// Long enough for every delivery in a batch to time out
+const DELIVERY_LEASE_MINUTES: i64 = 10;
+// Retries wait 1, 2, 4, ... minutes, giving up after about two hours
+const MAX_DELIVERY_ATTEMPTS: i64 = 8;
+const FIRST_RETRY_DELAY_SECONDS: i64 = 60;
+// Kept short, it's only shown to admins to tell failures apart
+const MAX_RECORDED_ERROR_CHARS: usize = 500;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Events endpoints can subscribe to. `Ping` is only ever sent on request
+/// from the admin pages, to whichever endpoint it's sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEvent {
+    UserCreated,
+    UserDeleted,
+    Ping,
+}
+
+impl WebhookEvent {
+    /// The events an endpoint can subscribe to
+    pub const ALL: [WebhookEvent; 2] = [WebhookEvent::UserCreated, WebhookEvent::UserDeleted];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::UserCreated => "user.created",
+            WebhookEvent::UserDeleted => "user.deleted",
+            WebhookEvent::Ping => "ping",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            WebhookEvent::UserCreated => "An account was created",
+            WebhookEvent::UserDeleted => "An account was deleted by its owner",
+            WebhookEvent::Ping => "A test event sent from the admin pages",
+        }
+    }
+}
+
+/// Queues events for every endpoint subscribed to them and wakes the
+/// delivery worker. Deliveries are stored first, so nothing is lost if an
+/// endpoint is down or the server restarts before sending.
+#[derive(Clone)]
+pub struct Webhooks {
+    pool: SqlitePool,
+    client: reqwest::Client,
+    wake: Arc<Notify>,
+}
+
+impl Webhooks {
+    pub fn new(pool: SqlitePool) -> anyhow::Result<Self> {
+        // A redirect would send the signed payload somewhere else
+        let client = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(PublicResolver))
+            .build()?;
+
+        Ok(Self {
+            pool,
+            client,
+            wake: Arc::new(Notify::new()),
+        })
+    }
+
+    /// Queues `data` for every active endpoint subscribed to `event`. A failed
+    /// write is logged rather than returned so webhooks never block the
+    /// action that triggered them.
+    pub async fn emit(&self, event: WebhookEvent, data: Value) {
+        let webhooks = match Webhook::find_subscribed(&self.pool, event.as_str()).await {
+            Ok(webhooks) => webhooks,
+            Err(e) => {
+                tracing::error!("Failed to load webhooks for {}: {}", event.as_str(), e);
+                return;
+            }
+        };
+        if webhooks.is_empty() {
+            return;
+        }
+
+        let payload = payload(event, data);
+        for webhook in &webhooks {
+            if let Err(e) =
+                WebhookDelivery::create(&self.pool, &webhook.id, event.as_str(), &payload).await
+            {
+                tracing::error!(
+                    "Failed to queue {} for webhook {}: {}",
+                    event.as_str(),
+                    webhook.id,
+                    e
+                );
+            }
+        }
+        self.wake.notify_one();
+    }
+
+    /// Queues a `ping` for one endpoint, whether or not it's active
+    pub async fn ping(&self, webhook: &Webhook) -> Result<WebhookDelivery, sqlx::Error> {
+        let payload = payload(WebhookEvent::Ping, json!({ "webhook_id": webhook.id }));
+        let delivery = WebhookDelivery::create(
+            &self.pool,
+            &webhook.id,
+            WebhookEvent::Ping.as_str(),
+            &payload,
+        )
+        .await?;
+        self.wake.notify_one();
+        Ok(delivery)
+    }
+
+    /// Wakes the worker, e.g. after a failed delivery is queued again
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+}
+
+// The same body goes to every subscribed endpoint; `id` identifies the event
+fn payload(event: WebhookEvent, data: Value) -> String {
+    json!({
+        "id": Uuid::new_v4().to_string(),
+        "event": event.as_str(),
+        "created_at": Utc::now(),
+        "data": data
+    })
+    .to_string()
+}
+
+/// `sha256=<hex HMAC-SHA256 of "<timestamp>.<body>">`, keyed by the endpoint's
+/// secret. Signing the timestamp lets receivers reject replayed deliveries.
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Sends queued deliveries in the background until the process exits,
+/// retrying failures with exponential backoff
+pub fn spawn_webhook_delivery(webhooks: Webhooks) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let lease_until = Utc::now() + chrono::Duration::minutes(DELIVERY_LEASE_MINUTES);
+            match WebhookDelivery::claim_due(&webhooks.pool, DELIVERY_BATCH_SIZE, lease_until).await
+            {
+                Ok(deliveries) if !deliveries.is_empty() => {
+                    let full_batch = deliveries.len() as i64 == DELIVERY_BATCH_SIZE;
+                    for delivery in deliveries {
+                        deliver(&webhooks, delivery).await;
+                    }
+                    // More may be waiting
+                    if full_batch {
+                        continue;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to load webhook deliveries: {}", e),
+            }
+
+            let _ = tokio::time::timeout(POLL_INTERVAL, webhooks.wake.notified()).await;
+        }
+    })
+}
+
+/// Checks that `url` is an http(s) URL whose host, and every address it
+/// resolves to, is public, so webhooks can't be aimed at this machine or
+/// the private network. The message says what's wrong otherwise.
+pub async fn check_endpoint(url: &str) -> Result<(), String> {
+    let url = Url::parse(url).map_err(|_| "Invalid URL".to_string())?;
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return Err("URL must start with http:// or https://".to_string());
+    }
+
+    let ips: Vec<IpAddr> = match url.host() {
+        Some(Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+        Some(Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+        Some(Host::Domain(domain)) => {
+            let port = url.port_or_known_default().unwrap_or(443);
+            tokio::net::lookup_host((domain, port))
+                .await
+                .map_err(|_| format!("Couldn't resolve {}", domain))?
+                .map(|addr| addr.ip())
+                .collect()
+        }
+        None => return Err("URL must include a host".to_string()),
+    };
+
+    if ips.iter().all(|ip| is_public(*ip)) {
+        Ok(())
+    } else {
+        Err("URL must point to a public address".to_string())
+    }
+}
+
+// Loopback, private, link-local, and other addresses that aren't reachable
+// on the public internet
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64)
+                // "This network", 0.0.0.0/8
+                || a == 0)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(mapped));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // Link-local, fe80::/10
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Resolves hosts for the delivery client, refusing any that lead to a
+/// non-public address, so a hostname re-pointed after `check_endpoint`
+/// passed still can't reach the private network
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+                return Err(format!(
+                    "{} resolves to a non-public address ({})",
+                    name.as_str(),
+                    addr.ip()
+                )
+                .into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+async fn deliver(webhooks: &Webhooks, delivery: WebhookDelivery) {
+    let webhook = match Webhook::find_by_id(&webhooks.pool, &delivery.webhook_id).await {
+        Ok(Some(webhook)) => webhook,
+        // Deleted along with its deliveries since this one was claimed
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Failed to load webhook {}: {}", delivery.webhook_id, e);
+            return;
+        }
+    };
+
+    // Disabled endpoints still get pings, so they can be tested first
+    if !webhook.is_active && delivery.event != WebhookEvent::Ping.as_str() {
+        finish(
+            webhooks,
+            &delivery,
+            None,
+            Some("Webhook is disabled"),
+            0,
+            DELIVERY_FAILED,
+        )
+        .await;
+        return;
+    }
+
+    let timestamp = Utc::now().timestamp();
+    let started = Instant::now();
+    // Also covers IP addresses in the URL, which the resolver never sees
+    let result = match check_endpoint(&webhook.url).await {
+        Ok(()) => webhooks
+            .client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Webhook-Event", &delivery.event)
+            .header("X-Webhook-Delivery", &delivery.id)
+            .header("X-Webhook-Timestamp", timestamp.to_string())
+            .header(
+                "X-Webhook-Signature",
+                signature(&webhook.secret, timestamp, &delivery.payload),
+            )
+            .body(delivery.payload.clone())
+            .send()
+            .await
+            .map_err(|e| e.to_string()),
+        Err(error) => Err(error),
+    };
+    let duration_ms = started.elapsed().as_millis() as i64;
+
+    let (response_status, error) = match result {
+        Ok(response) if response.status().is_success() => (Some(response.status()), None),
+        Ok(response) => (
+            Some(response.status()),
+            Some(format!("Endpoint answered {}", response.status())),
+        ),
+        Err(error) => (None, Some(error)),
+    };
+    let response_status = response_status.map(|status| i64::from(status.as_u16()));
+    let error: Option<String> =
+        error.map(|error| error.chars().take(MAX_RECORDED_ERROR_CHARS).collect());
+
+    let attempts = delivery.attempts + 1;
+    let status = match &error {
+        None => DELIVERY_SUCCEEDED,
+        Some(error) => {
+            tracing::warn!(
+                "Webhook delivery {} to {} failed (attempt {}): {}",
+                delivery.id,
+                webhook.url,
+                attempts,
+                error
+            );
+            if attempts >= MAX_DELIVERY_ATTEMPTS {
+                DELIVERY_FAILED
+            } else {
+                DELIVERY_PENDING
+            }
+        }
+    };
+
+    finish(
+        webhooks,
+        &delivery,
+        response_status,
+        error.as_deref(),
+        duration_ms,
+        status,
+    )
+    .await;
+}
+
+// Helper function to record an attempt and move the delivery to `status`
+async fn finish(
+    webhooks: &Webhooks,
+    delivery: &WebhookDelivery,
+    response_status: Option<i64>,
+    error: Option<&str>,
+    duration_ms: i64,
+    status: &str,
+) {
+    if let Err(e) = WebhookDeliveryAttempt::record(
+        &webhooks.pool,
+        &delivery.id,
+        response_status,
+        error,
+        duration_ms,
+    )
+    .await
+    {
+        tracing::error!(
+            "Failed to record attempt for delivery {}: {}",
+            delivery.id,
+            e
+        );
+    }
+
+    let next_attempt_at = if status == DELIVERY_PENDING {
+        next_retry_at(delivery.attempts + 1)
+    } else {
+        Utc::now()
+    };
+    if let Err(e) =
+        WebhookDelivery::record_attempt(&webhooks.pool, &delivery.id, status, next_attempt_at).await
+    {
+        tracing::error!("Failed to update delivery {}: {}", delivery.id, e);
+    }
+}
+
+// Helper function to double the wait after each failed attempt
+fn next_retry_at(attempts: i64) -> DateTime<Utc> {
+    let delay = FIRST_RETRY_DELAY_SECONDS << (attempts - 1).clamp(0, 16);
+    Utc::now() + chrono::Duration::seconds(delay)
+}